@@ -0,0 +1,628 @@
+//! Terminal client for the alarm state: `alarms status`, `alarms watch` and `alarms regions`.
+//! The state is read from the store of the service or from a running API instance.
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use alerts_api_app::modules::{
+    other_data::other_data::{find_region, get_regions, Region},
+    processing::processing::{get_all_alarm_scopes, get_all_alarm_start_times, get_all_alarm_statuses, AlarmScope},
+    store::store::store,
+};
+
+/// No alarm in the selected regions
+const EXIT_NO_ALARM: u8 = 0;
+/// The state could not be read
+const EXIT_ERROR: u8 = 1;
+/// An alarm is active in at least one of the selected regions
+const EXIT_ALARM: u8 = 3;
+
+#[derive(Debug, Parser)]
+#[command(name = "alarms", about = "Air alarm state of the regions of Ukraine")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Where the alarm state is read from
+    #[arg(long, value_enum, global = true, default_value_t = Source::Store)]
+    source: Source,
+
+    /// Address of the API instance for --source api
+    #[arg(long, global = true, env = "ALARMS_API_URL", default_value = "http://localhost:8000")]
+    api_url: String,
+
+    /// API key for --source api
+    #[arg(long, global = true, env = "ALARMS_API_KEY")]
+    api_key: Option<String>,
+
+    #[arg(long, short, value_enum, global = true, default_value_t = Format::Table)]
+    format: Format,
+
+    /// Language of the region names
+    #[arg(long, value_enum, global = true, default_value_t = Language::Uk)]
+    lang: Language,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Current alarm state. Exits with 3 if an alarm is active in one of the regions, 0 otherwise
+    Status {
+        /// Region identifier or slug, all regions if not set
+        #[arg(long, short)]
+        region: Vec<String>,
+    },
+    /// Prints the current state and then every change of the state.
+    /// With --source api the changes are read from the event stream of the API
+    Watch {
+        #[arg(long, short)]
+        region: Vec<String>,
+        /// Seconds between the checks of the store, or before the event stream of the API is opened again
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Regions with their identifiers and slugs
+    Regions,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Source {
+    Store,
+    Api,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Language {
+    Uk,
+    En,
+}
+
+/// Alarm state of a region
+#[derive(Debug, Clone, Default, PartialEq)]
+struct RegionState {
+    scope: Option<AlarmScope>,
+    started_at: Option<String>,
+}
+
+/// Alarm state from the second version of the API
+#[derive(Debug, Deserialize)]
+struct ApiAlarm {
+    region_id: i32,
+    alarm_active: bool,
+    alarm_started_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiEnvelope<T> {
+    data: Option<Vec<T>>,
+}
+
+/// Position of a region in the compact status of /v2/iot
+#[derive(Debug, Deserialize)]
+struct StatusIndex {
+    index: usize,
+    region_id: i32,
+}
+
+/// Event of /v2/events, only the fields that change the alarm state
+#[derive(Debug, Deserialize)]
+struct StreamEvent {
+    event: String,
+    region_id: i32,
+    scope: Option<String>,
+    changed_at: String,
+}
+
+/// Line of the output
+#[derive(Debug, Serialize)]
+struct Row {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed_at: Option<String>,
+    region_id: i32,
+    slug: String,
+    name: String,
+    /// "none", "partial" or "full"
+    state: &'static str,
+    started_at: Option<String>,
+}
+
+impl Row {
+
+    fn new(region: &Region, state: &RegionState, language: Language) -> Self {
+        Row {
+            changed_at: None,
+            region_id: region.id,
+            slug: region.codes.slug.clone(),
+            name: region_name(region, language).to_string(),
+            state: state.scope.map(|scope| scope.as_str()).unwrap_or("none"),
+            started_at: state.started_at.clone(),
+        }
+    }
+
+    fn columns(&self) -> Vec<String> {
+        let mut columns = vec![
+            self.region_id.to_string(),
+            self.slug.clone(),
+            self.name.clone(),
+            self.state.to_string(),
+            self.started_at.clone().unwrap_or_default(),
+        ];
+        if let Some(changed_at) = &self.changed_at {
+            columns.insert(0, changed_at.clone());
+        }
+        columns
+    }
+}
+
+fn region_name(region: &Region, language: Language) -> &str {
+    match language {
+        Language::Uk => &region.names.uk,
+        Language::En => &region.names.en,
+    }
+}
+
+/// Reads the state of all regions from the store
+async fn read_store() -> Result<HashMap<i32, RegionState>, String> {
+
+    // The store functions of the service skip the errors, so the connection is checked first
+    store().hget("status", "version").await.map_err(|e| e.to_string())?;
+
+    let scopes = get_all_alarm_scopes().await;
+    let start_times = get_all_alarm_start_times().await;
+    Ok(get_all_alarm_statuses()
+        .await
+        .into_iter()
+        .map(|(region_id, is_active)| {
+            let state = match is_active {
+                true => RegionState {
+                    scope: Some(scopes.get(&region_id).copied().unwrap_or(AlarmScope::Full)),
+                    started_at: start_times.get(&region_id).cloned(),
+                },
+                false => RegionState::default(),
+            };
+            (region_id, state)
+        })
+        .collect())
+}
+
+/// Client of a running API instance
+fn api_client(timeout: Option<Duration>) -> Result<reqwest::Client, String> {
+    let builder = reqwest::Client::builder();
+    let builder = match timeout {
+        Some(timeout) => builder.timeout(timeout),
+        None => builder,
+    };
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Sends a GET request to the API with the API key and checks the status of the response
+async fn api_get(cli: &Cli, client: &reqwest::Client, path: &str) -> Result<reqwest::Response, String> {
+    let request = client.get(format!("{}{}", cli.api_url.trim_end_matches('/'), path));
+    let request = match &cli.api_key {
+        Some(key) => request.header("X-API-Key", key),
+        None => request,
+    };
+    request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())
+}
+
+/// Scopes of the alarms from the compact status, the positions are taken from the mapping of the server,
+/// so the registry of the client does not have to match the registry of the server
+fn scopes_from_status(mapping: &[StatusIndex], status: &str) -> HashMap<i32, AlarmScope> {
+    let status: Vec<char> = status.trim().chars().collect();
+    mapping
+        .iter()
+        .filter_map(|entry| match status.get(entry.index) {
+            Some('A') => Some((entry.region_id, AlarmScope::Full)),
+            Some('P') => Some((entry.region_id, AlarmScope::Partial)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Reads the state of all regions from a running API instance.
+/// The scope of the alarms is taken from the compact status of /v2/iot.
+async fn read_api(cli: &Cli) -> Result<HashMap<i32, RegionState>, String> {
+
+    let client = api_client(Some(Duration::from_secs(10)))?;
+
+    let alarms: ApiEnvelope<ApiAlarm> = api_get(cli, &client, "/v2/alarms?all=true").await?.json().await.map_err(|e| e.to_string())?;
+    let mapping: ApiEnvelope<StatusIndex> = api_get(cli, &client, "/v2/iot/mapping").await?.json().await.map_err(|e| e.to_string())?;
+    let status = api_get(cli, &client, "/v2/iot/status.txt").await?.text().await.map_err(|e| e.to_string())?;
+    let scopes = scopes_from_status(&mapping.data.unwrap_or_default(), &status);
+
+    Ok(alarms
+        .data
+        .unwrap_or_default()
+        .into_iter()
+        .map(|alarm| {
+            let state = match alarm.alarm_active {
+                true => RegionState {
+                    scope: Some(scopes.get(&alarm.region_id).copied().unwrap_or(AlarmScope::Full)),
+                    started_at: alarm.alarm_started_at,
+                },
+                false => RegionState::default(),
+            };
+            (alarm.region_id, state)
+        })
+        .collect())
+}
+
+/// Takes the complete server-sent events from the buffer and returns their data,
+/// an incomplete event is left in the buffer
+fn take_stream_events(buffer: &mut String) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Some(end) = buffer.find("\n\n") {
+        let event: String = buffer.drain(..end + 2).collect();
+        let data: Vec<&str> = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        if !data.is_empty() {
+            events.push(data.join("\n"));
+        }
+    }
+    events
+}
+
+/// Applies an event of the stream to the state of its region,
+/// returns the new state if the event changes the alarm state
+fn apply_event(states: &HashMap<i32, RegionState>, event: &StreamEvent) -> Option<RegionState> {
+    let previous = states.get(&event.region_id).cloned().unwrap_or_default();
+    let scope = event.scope.as_deref().and_then(AlarmScope::parse);
+    match event.event.as_str() {
+        "alarm_started" => Some(RegionState { scope: scope.or(Some(AlarmScope::Full)), started_at: Some(event.changed_at.clone()) }),
+        "alarm_ended" => Some(RegionState::default()),
+        "scope_changed" => Some(RegionState { scope: scope.or(previous.scope), ..previous }),
+        // The alerts of other types do not change the air alarm state
+        _ => None,
+    }
+}
+
+async fn read_states(cli: &Cli) -> Result<HashMap<i32, RegionState>, String> {
+    match cli.source {
+        Source::Store => read_store().await,
+        Source::Api => read_api(cli).await,
+    }
+}
+
+/// Returns the selected regions, all regions if none are selected
+fn select_regions(regions: &[Region], tokens: &[String]) -> Result<Vec<Region>, String> {
+    if tokens.is_empty() {
+        return Ok(regions.to_vec());
+    }
+    tokens
+        .iter()
+        .flat_map(|token| token.split(','))
+        .map(|token| find_region(regions, token.trim()).cloned().ok_or_else(|| format!("unknown region: {}", token)))
+        .collect()
+}
+
+/// Quotes a CSV field if it has a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Prints the rows in the selected format.
+/// The header of the table and CSV is printed only if requested, so the watch output has it once.
+fn print_rows(format: Format, rows: &[Row], header: Option<&[&str]>, widths: &[usize]) {
+    match format {
+        Format::Json => {
+            for row in rows {
+                println!("{}", serde_json::to_string(row).unwrap());
+            }
+        }
+        Format::Csv => {
+            if let Some(header) = header {
+                println!("{}", header.join(","));
+            }
+            for row in rows {
+                println!("{}", row.columns().iter().map(|value| csv_field(value)).collect::<Vec<_>>().join(","));
+            }
+        }
+        Format::Table => {
+            let line = |columns: Vec<String>| {
+                columns
+                    .iter()
+                    .zip(widths)
+                    .map(|(value, width)| format!("{}{}", value, " ".repeat(width.saturating_sub(value.chars().count()))))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            if let Some(header) = header {
+                println!("{}", line(header.iter().map(|value| value.to_string()).collect()));
+            }
+            for row in rows {
+                println!("{}", line(row.columns()));
+            }
+        }
+    }
+}
+
+/// Widths of the table columns for the regions with the longest values of an active alarm
+fn column_widths(header: &[&str], regions: &[Region], language: Language) -> Vec<usize> {
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let state = RegionState { scope: Some(AlarmScope::Partial), started_at: Some(now.clone()) };
+
+    let mut widths: Vec<usize> = header.iter().map(|value| value.chars().count()).collect();
+    for region in regions {
+        let mut row = Row::new(region, &state, language);
+        row.changed_at = (header.len() == WATCH_HEADER.len()).then(|| now.clone());
+        for (width, value) in widths.iter_mut().zip(row.columns()) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    widths
+}
+
+const STATUS_HEADER: [&str; 5] = ["ID", "SLUG", "NAME", "STATE", "STARTED_AT"];
+const WATCH_HEADER: [&str; 6] = ["CHANGED_AT", "ID", "SLUG", "NAME", "STATE", "STARTED_AT"];
+
+async fn status(cli: &Cli, tokens: &[String]) -> Result<u8, String> {
+
+    let selected = select_regions(&get_regions(), tokens)?;
+    let states = read_states(cli).await?;
+
+    let rows: Vec<Row> = selected
+        .iter()
+        .map(|region| Row::new(region, &states.get(&region.id).cloned().unwrap_or_default(), cli.lang))
+        .collect();
+    print_rows(cli.format, &rows, Some(&STATUS_HEADER), &column_widths(&STATUS_HEADER, &selected, cli.lang));
+
+    Ok(exit_code(&rows))
+}
+
+/// Exit code of "status" for the printed rows
+fn exit_code(rows: &[Row]) -> u8 {
+    match rows.iter().any(|row| row.state != "none") {
+        true => EXIT_ALARM,
+        false => EXIT_NO_ALARM,
+    }
+}
+
+/// Output of "watch": prints the regions whose state differs from the printed one
+struct WatchOutput<'a> {
+    cli: &'a Cli,
+    selected: Vec<Region>,
+    widths: Vec<usize>,
+    printed: HashMap<i32, RegionState>,
+    header: Option<&'static [&'static str]>,
+}
+
+impl WatchOutput<'_> {
+
+    fn print_changes(&mut self, states: &HashMap<i32, RegionState>) {
+        let changed_at = chrono::Utc::now().to_rfc3339();
+        let rows: Vec<Row> = self
+            .selected
+            .iter()
+            .filter_map(|region| {
+                let state = states.get(&region.id).cloned().unwrap_or_default();
+                if self.printed.get(&region.id) == Some(&state) {
+                    return None;
+                }
+                let mut row = Row::new(region, &state, self.cli.lang);
+                row.changed_at = Some(changed_at.clone());
+                self.printed.insert(region.id, state);
+                Some(row)
+            })
+            .collect();
+        print_rows(self.cli.format, &rows, self.header.take(), &self.widths);
+    }
+}
+
+fn print_error(e: &str) {
+    eprintln!("{} - {}", chrono::Local::now().format("%H:%M:%S"), e);
+}
+
+/// Follows /v2/events until the stream ends, the state is read first,
+/// so the changes made while the stream was closed are printed too
+async fn follow_events(output: &mut WatchOutput<'_>) -> Result<(), String> {
+
+    let client = api_client(None)?;
+    let mut response = api_get(output.cli, &client, "/v2/events").await?;
+    let mut states = read_api(output.cli).await?;
+    output.print_changes(&states);
+
+    let mut buffer = String::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        for data in take_stream_events(&mut buffer) {
+            let Ok(event) = serde_json::from_str::<StreamEvent>(&data) else {
+                continue;
+            };
+            if let Some(state) = apply_event(&states, &event) {
+                states.insert(event.region_id, state);
+                output.print_changes(&states);
+            }
+        }
+    }
+    Err("the event stream is closed".to_string())
+}
+
+async fn watch(cli: &Cli, tokens: &[String], interval: u64) -> Result<u8, String> {
+
+    let selected = select_regions(&get_regions(), tokens)?;
+    let widths = column_widths(&WATCH_HEADER, &selected, cli.lang);
+    let mut output = WatchOutput { cli, selected, widths, printed: HashMap::new(), header: Some(&WATCH_HEADER) };
+
+    loop {
+        match cli.source {
+            Source::Api => {
+                if let Err(e) = follow_events(&mut output).await {
+                    print_error(&e);
+                }
+            }
+            Source::Store => match read_store().await {
+                Ok(states) => output.print_changes(&states),
+                Err(e) => print_error(&e),
+            },
+        }
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+fn regions(cli: &Cli) -> u8 {
+
+    let header = ["ID", "SLUG", "NAME", "ISO_3166_2"];
+    let regions = get_regions();
+
+    match cli.format {
+        Format::Json => {
+            for region in &regions {
+                println!("{}", serde_json::to_string(region).unwrap());
+            }
+        }
+        Format::Csv | Format::Table => {
+            let rows: Vec<Vec<String>> = regions
+                .iter()
+                .map(|region| vec![region.id.to_string(), region.codes.slug.clone(), region_name(region, cli.lang).to_string(), region.codes.iso_3166_2.clone()])
+                .collect();
+            let mut widths: Vec<usize> = header.iter().map(|value| value.len()).collect();
+            for row in &rows {
+                for (index, value) in row.iter().enumerate() {
+                    widths[index] = widths[index].max(value.chars().count());
+                }
+            }
+            let separator = if cli.format == Format::Csv { "," } else { "  " };
+            let line = |row: Vec<String>| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(value, width)| match cli.format {
+                        Format::Csv => csv_field(value),
+                        _ => format!("{}{}", value, " ".repeat(width.saturating_sub(value.chars().count()))),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(separator)
+                    .trim_end()
+                    .to_string()
+            };
+            println!("{}", line(header.iter().map(|value| value.to_string()).collect()));
+            for row in rows {
+                println!("{}", line(row));
+            }
+        }
+    }
+    EXIT_NO_ALARM
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Status { region } => status(&cli, region).await,
+        Command::Watch { region, interval } => watch(&cli, region, *interval).await,
+        Command::Regions => Ok(regions(&cli)),
+    };
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("alarms: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn tokens(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn row(region_id: i32, scope: Option<AlarmScope>) -> Row {
+        let region = get_regions().into_iter().find(|region| region.id == region_id).unwrap();
+        Row::new(&region, &RegionState { scope, started_at: None }, Language::En)
+    }
+
+    #[test]
+    fn regions_are_selected_by_identifier_and_slug() {
+
+        let regions = get_regions();
+        assert_eq!(select_regions(&regions, &[]).unwrap().len(), regions.len());
+
+        let selected = select_regions(&regions, &tokens(&["KYIV,9", "kharkiv"])).unwrap();
+        assert_eq!(selected.iter().map(|region| region.id).collect::<Vec<_>>(), vec![31, 9, 22]);
+        assert_eq!(select_regions(&regions, &tokens(&["9", "atlantis"])).unwrap_err(), "unknown region: atlantis");
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("Kyiv"), "Kyiv");
+        assert_eq!(csv_field("Kyiv, city"), "\"Kyiv, city\"");
+        assert_eq!(csv_field("the \"city\""), "\"the \"\"city\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn exit_code_shows_an_active_alarm() {
+        assert_eq!(exit_code(&[]), EXIT_NO_ALARM);
+        assert_eq!(exit_code(&[row(9, None), row(31, None)]), EXIT_NO_ALARM);
+        assert_eq!(exit_code(&[row(9, None), row(31, Some(AlarmScope::Partial))]), EXIT_ALARM);
+    }
+
+    #[test]
+    fn watch_interval_must_be_positive() {
+        assert!(Cli::try_parse_from(["alarms", "watch", "--interval", "0"]).is_err());
+        assert!(Cli::try_parse_from(["alarms", "watch", "--interval", "1"]).is_ok());
+    }
+
+    #[test]
+    fn scopes_follow_the_mapping_of_the_server() {
+
+        // The server has the regions in another order than the client
+        let mapping = vec![StatusIndex { index: 0, region_id: 31 }, StatusIndex { index: 1, region_id: 9 }, StatusIndex { index: 2, region_id: 22 }];
+        let scopes = scopes_from_status(&mapping, "APN\n");
+        assert_eq!(scopes, HashMap::from([(31, AlarmScope::Full), (9, AlarmScope::Partial)]));
+
+        // A shorter status does not shift the regions
+        assert_eq!(scopes_from_status(&mapping, "A"), HashMap::from([(31, AlarmScope::Full)]));
+    }
+
+    #[test]
+    fn stream_events_change_the_state() {
+
+        let mut buffer = "event: alarm_started\ndata: {\"event\":\"alarm_started\",\"scope\":\"partial\",\"region_id\":9,\"changed_at\":\"t1\"}\n\n:\n\nevent: alarm_ended\ndata: {\"ev".to_string();
+        let events = take_stream_events(&mut buffer);
+        assert_eq!(events.len(), 1);
+        assert_eq!(buffer, "event: alarm_ended\ndata: {\"ev");
+
+        let started: StreamEvent = serde_json::from_str(&events[0]).unwrap();
+        let mut states = HashMap::new();
+        let state = apply_event(&states, &started).unwrap();
+        assert_eq!(state, RegionState { scope: Some(AlarmScope::Partial), started_at: Some("t1".to_string()) });
+        states.insert(9, state);
+
+        let changed = StreamEvent { event: "scope_changed".to_string(), region_id: 9, scope: Some("full".to_string()), changed_at: "t2".to_string() };
+        assert_eq!(apply_event(&states, &changed), Some(RegionState { scope: Some(AlarmScope::Full), started_at: Some("t1".to_string()) }));
+
+        let other = StreamEvent { event: "type_added".to_string(), region_id: 9, scope: None, changed_at: "t3".to_string() };
+        assert_eq!(apply_event(&states, &other), None);
+
+        let ended = StreamEvent { event: "alarm_ended".to_string(), region_id: 9, scope: None, changed_at: "t4".to_string() };
+        assert_eq!(apply_event(&states, &ended), Some(RegionState::default()));
+    }
+}
@@ -0,0 +1,214 @@
+//! Air alarms API: the poller of the alarm API and the HTTP server that serves the data from the store.
+//!
+//! The binary runs both parts or only one of them (see Mode),
+//! the modules are public for the tools built on the same data.
+
+#![allow(clippy::module_inception)]
+
+use modules::{admin::admin::{self, load_admin_tokens}, cors::cors::Cors, iot::iot, leader::leader::{self, release_leadership, run_election}, metrics::metrics, providers::providers, sse::sse, api_actions::api_actions::{response_builder_for_one_location, response_builder_for_some_locations, ApiResponse}, api_keys::api_keys::{ApiKey, AuthConfig}, api_v2::api_v2::{self, ErrorDetail}, caching::caching::{snapshot_etag, Cached, Conditions}, dashboard::dashboard::{dashboard_context, region_context}, rate_limit::rate_limit::RateLimiter, id_query::id_query::{IdQueryError, IdSelection}, other_data::other_data::{get_all_cities, get_all_cities_as_json, RegionInfo}, notifications::notifications::init_notifiers, processing::processing::{get_snapshot_info, update_data}};
+use rocket::{
+    http::Status,
+    response::status::BadRequest,
+    serde::json::{json, Json, Value},
+    catch, catchers, get, routes, Build, Request, Rocket
+};
+use rocket_dyn_templates::Template;
+
+pub mod modules {
+    pub mod fetch_data;
+    pub mod processing;
+    pub mod data_actions;
+    pub mod other_data;
+    pub mod api_actions;
+    pub mod dashboard;
+    pub mod caching;
+    pub mod api_v2;
+    pub mod openapi;
+    pub mod id_query;
+    pub mod store;
+    pub mod api_keys;
+    pub mod admin;
+    pub mod rate_limit;
+    pub mod cors;
+    pub mod events;
+    pub mod iot;
+    pub mod telegram;
+    pub mod notifications;
+    pub mod delivery_policy;
+    pub mod diff;
+    pub mod metrics;
+    pub mod leader;
+    pub mod replay;
+    pub mod quarantine;
+    pub mod providers;
+    pub mod overrides;
+    pub mod audit;
+    pub mod sse;
+    #[cfg(feature = "mqtt")]
+    pub mod mqtt;
+}
+
+#[get("/get_alarm/<id>")]
+async fn get_alarm_from_one_region(_api_key: ApiKey, id: i32, conditions: Conditions) -> Option<Cached<Json<ApiResponse>>> {
+
+    if !get_all_cities().contains_key(&id) {
+        return None;
+    }
+
+    let snapshot = get_snapshot_info().await;
+    let etag = snapshot_etag(snapshot.version);
+    if conditions.is_not_modified(&etag, snapshot.last_modified.as_deref()) {
+        return Some(Cached::not_modified(etag, snapshot.last_modified));
+    }
+
+    let response = response_builder_for_one_location(id).await?;
+    Some(Cached::new(response, etag, snapshot.last_modified))
+
+}
+#[get("/get_alarms")]
+async fn get_alarm_from_some_regions(_api_key: ApiKey, selection: Result<IdSelection, IdQueryError>, conditions: Conditions) -> Result<Cached<Json<Vec<ApiResponse>>>, BadRequest<Json<IdQueryError>>> {
+
+    let selection = selection.map_err(|e| BadRequest(Json(e)))?;
+
+    let snapshot = get_snapshot_info().await;
+    let etag = snapshot_etag(snapshot.version);
+    if conditions.is_not_modified(&etag, snapshot.last_modified.as_deref()) {
+        return Ok(Cached::not_modified(etag, snapshot.last_modified));
+    }
+
+    Ok(Cached::new(response_builder_for_some_locations(selection.ids).await, etag, snapshot.last_modified))
+}
+
+#[get("/get_regions")]
+async fn get_info_about_available_regions(_api_key: ApiKey) -> Json<Vec<RegionInfo>> {
+    get_all_cities_as_json()
+}
+
+#[get("/")]
+async fn dashboard() -> Template {
+    Template::render("dashboard", dashboard_context().await)
+}
+
+#[get("/region/<id>")]
+async fn region_dashboard(id: i32) -> Option<Template> {
+    let context = region_context(id).await?;
+    Some(Template::render("region", context))
+}
+
+#[catch(404)]
+fn error_404() -> Value {
+    json!("THE LOCATION NOT FOUND")
+}
+
+#[catch(default)]
+fn error_default(status: Status, request: &Request) -> Value {
+    match request.local_cache(ErrorDetail::default) {
+        ErrorDetail(Some(detail)) => json!(detail.to_uppercase()),
+        ErrorDetail(None) => json!(status.reason().unwrap_or("ERROR").to_uppercase()),
+    }
+}
+
+/// Parts of the service run by the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Only the HTTP server, the data is written by a poller in another process
+    Api,
+    /// Only the poller with the notifications
+    Poller,
+    /// The HTTP server and the poller in one process
+    All,
+}
+
+impl Mode {
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "api" => Some(Mode::Api),
+            "poller" => Some(Mode::Poller),
+            "all" => Some(Mode::All),
+            _ => None,
+        }
+    }
+
+    pub fn runs_api(&self) -> bool {
+        matches!(self, Mode::Api | Mode::All)
+    }
+
+    pub fn runs_poller(&self) -> bool {
+        matches!(self, Mode::Poller | Mode::All)
+    }
+}
+
+/// Builds the HTTP server with all routes, catchers and fairings
+pub fn build_rocket() -> Rocket<Build> {
+
+    // A malformed admin token fails here, not in the first request to the admin API
+    load_admin_tokens();
+
+    let rocket = rocket::build();
+    let auth_config: AuthConfig = rocket.figment().extract_inner("auth").unwrap_or_default();
+
+    rocket
+        .manage(auth_config)
+        .mount(
+            "/",
+            routes![
+                get_alarm_from_one_region,
+                get_alarm_from_some_regions,
+                get_info_about_available_regions,
+                dashboard,
+                region_dashboard
+            ],
+        )
+        .mount("/", metrics::routes())
+        .mount("/", leader::routes())
+        .mount("/v2", api_v2::routes())
+        .mount("/v2", sse::routes())
+        .mount("/v2", providers::routes())
+        .mount("/v2/admin", admin::routes())
+        .mount("/v2/iot", iot::routes())
+        .register("/", catchers![error_404, error_default])
+        .register("/v2", api_v2::catchers())
+        .attach(Template::fairing())
+        .attach(RateLimiter)
+        .attach(Cors)
+}
+
+/// Builds the HTTP server of the poller mode: only the health check and the metrics,
+/// so the poller can be probed and scraped like the API
+pub fn build_poller_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/", metrics::routes())
+        .mount("/", leader::routes())
+        .register("/", catchers![error_404, error_default])
+}
+
+/// Starts the notification channels, the leader election and the polling loop in the background
+pub async fn start_poller() {
+
+    init_notifiers().await;
+    tokio::spawn(run_election());
+    tokio::spawn(async {
+        loop {
+            update_data().await;
+        }
+    });
+}
+
+/// Runs the parts of the service selected by the mode until the process is stopped
+pub async fn run(mode: Mode) {
+
+    if mode.runs_poller() {
+        start_poller().await;
+    }
+
+    let rocket = match mode.runs_api() {
+        true => build_rocket(),
+        false => build_poller_rocket(),
+    };
+    // Rocket stops on Ctrl+C and on SIGTERM (its default shutdown settings),
+    // so the lease is released when the process is stopped by an orchestrator too
+    let _ = rocket.launch().await;
+
+    release_leadership().await;
+}
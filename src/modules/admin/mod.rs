@@ -0,0 +1,382 @@
+pub mod admin {
+
+    use dotenv::dotenv;
+    use std::env;
+    use once_cell::sync::Lazy;
+    use rocket::{
+        delete, get, post, put, routes, Route,
+        http::Status,
+        request::{self, FromRequest, Request},
+        response::status::Custom,
+        serde::json::Json,
+    };
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use crate::modules::{
+        audit::audit::{get_audit, record_audit, AuditEntry, AuditFilter},
+        api_keys::api_keys::{get_key, issue_key, list_keys, revoke_key, rotate_key, ApiKeyInfo, IssuedApiKey},
+        api_v2::api_v2::{error_response, Envelope, ErrorDetail, ErrorResponse, Meta},
+        notifications::notifications::{get_notifier_stats, NotifierStats},
+        other_data::other_data::get_all_cities,
+        overrides::overrides::{
+            expires_after, get_notes, get_overrides, remove_note, remove_override, set_note, set_override, Override, RegionNote,
+            MAX_OVERRIDE_MINUTES,
+        },
+        quarantine::quarantine::{get_quarantine, QuarantineEntry, QUARANTINE_LENGTH},
+        store::store::StoreError,
+    };
+
+    ///Get the tokens of the admin API with the names of their owners:
+    ///ADMIN_TOKENS ("alice:<token>,bob:<token>") and ADMIN_TOKEN of the owner "admin".
+    /// The admin API is disabled if no token is set.
+    fn get_admin_tokens() -> Vec<(String, String)> {
+        dotenv().ok();
+
+        let mut tokens: Vec<(String, String)> = env::var("ADMIN_TOKENS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (actor, token) = entry.split_once(':').expect("INCORRECT ADMIN_TOKENS, EXPECTED name:token");
+                (actor.trim().to_string(), token.trim().to_string())
+            })
+            .filter(|(actor, token)| !actor.is_empty() && !token.is_empty())
+            .collect();
+
+        if let Some(token) = env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty()) {
+            tokens.push(("admin".to_string(), token));
+        }
+        tokens
+    }
+
+    /// Tokens of the admin API, read once, so a malformed ADMIN_TOKENS stops the process at the start (see load_admin_tokens)
+    static ADMIN_TOKENS: Lazy<Vec<(String, String)>> = Lazy::new(get_admin_tokens);
+
+    /// Reads the tokens of the admin API, called when the HTTP server is built
+    pub fn load_admin_tokens() {
+        Lazy::force(&ADMIN_TOKENS);
+    }
+
+    /// Compares two strings in time that does not depend on the position of the first difference
+    fn constant_time_eq(a: &str, b: &str) -> bool {
+        a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+
+    /// Request guard for the admin API.
+    /// The request must have the "Authorization: Bearer <token>" header with one of the admin tokens.
+    #[derive(Debug)]
+    pub struct AdminToken {
+        /// Owner of the token, the actor of the audit entries
+        pub actor: String,
+    }
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for AdminToken {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+
+            let tokens = &*ADMIN_TOKENS;
+            if tokens.is_empty() {
+                request.local_cache(|| ErrorDetail(Some("the admin API is disabled".to_string())));
+                return request::Outcome::Error((Status::Forbidden, ()));
+            }
+
+            let provided = request
+                .headers()
+                .get_one("Authorization")
+                .and_then(|value| value.strip_prefix("Bearer "));
+
+            // Every token is compared, so the time of the check does not show which token is close
+            let actor = provided.and_then(|provided| {
+                tokens
+                    .iter()
+                    .filter(|(_, token)| constant_time_eq(provided.trim(), token))
+                    .fold(None, |found, (actor, _)| found.or(Some(actor.clone())))
+            });
+
+            match actor {
+                Some(actor) => request::Outcome::Success(AdminToken { actor }),
+                _ => {
+                    request.local_cache(|| ErrorDetail(Some("a valid admin token is required".to_string())));
+                    request::Outcome::Error((Status::Unauthorized, ()))
+                }
+            }
+        }
+    }
+
+    /// Converts an error of the store to a response
+    fn store_error(e: StoreError) -> ErrorResponse {
+        error_response(Status::ServiceUnavailable, e.to_string())
+    }
+
+    /// Target of the audit entries of a key
+    fn key_target(id: &str) -> String {
+        format!("key:{}", id)
+    }
+
+    /// Body of the request to issue a key
+    #[derive(Debug, Deserialize)]
+    pub struct IssueKeyRequest {
+        pub name: String,
+        pub daily_quota: Option<u64>,
+    }
+
+    /// Issues a new API key. The key is returned only in this response.
+    #[post("/keys", data = "<request>")]
+    pub async fn post_key(admin: AdminToken, request: Json<IssueKeyRequest>) -> Result<Custom<Json<Envelope<IssuedApiKey>>>, ErrorResponse> {
+
+        if request.name.trim().is_empty() {
+            return Err(error_response(Status::UnprocessableEntity, "the name of the key must not be empty"));
+        }
+
+        let issued = issue_key(request.name.trim(), request.daily_quota).await.map_err(store_error)?;
+        record_audit(&admin.actor, "key.issue", &key_target(&issued.info.id), None, Some(json!(issued.info))).await;
+        Ok(Custom(Status::Created, Json(Envelope::data(issued, Meta::new()))))
+    }
+
+    /// List of all keys with their usage counters
+    #[get("/keys")]
+    pub async fn get_keys(_admin: AdminToken) -> Result<Json<Envelope<Vec<ApiKeyInfo>>>, ErrorResponse> {
+
+        let keys = list_keys().await.map_err(store_error)?;
+        let meta = Meta { count: Some(keys.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(keys, meta)))
+    }
+
+    /// One key with its usage counters
+    #[get("/keys/<id>")]
+    pub async fn get_one_key(_admin: AdminToken, id: &str) -> Result<Json<Envelope<ApiKeyInfo>>, ErrorResponse> {
+
+        let key = get_key(id)
+            .await
+            .map_err(store_error)?
+            .ok_or_else(|| error_response(Status::NotFound, format!("API key {} not found", id)))?;
+        Ok(Json(Envelope::data(key, Meta::new())))
+    }
+
+    /// Replaces the key with a new one, the old key stops working immediately
+    #[post("/keys/<id>/rotate")]
+    pub async fn post_rotate_key(admin: AdminToken, id: &str) -> Result<Json<Envelope<IssuedApiKey>>, ErrorResponse> {
+
+        let before = get_key(id).await.map_err(store_error)?;
+        let issued = rotate_key(id)
+            .await
+            .map_err(store_error)?
+            .ok_or_else(|| error_response(Status::NotFound, format!("active API key {} not found", id)))?;
+        record_audit(&admin.actor, "key.rotate", &key_target(id), before.map(|before| json!(before)), Some(json!(issued.info))).await;
+        Ok(Json(Envelope::data(issued, Meta::new())))
+    }
+
+    /// Revokes the key
+    #[delete("/keys/<id>")]
+    pub async fn delete_key(admin: AdminToken, id: &str) -> Result<Json<Envelope<ApiKeyInfo>>, ErrorResponse> {
+
+        let before = get_key(id).await.map_err(store_error)?;
+        let key = revoke_key(id)
+            .await
+            .map_err(store_error)?
+            .ok_or_else(|| error_response(Status::NotFound, format!("API key {} not found", id)))?;
+        record_audit(&admin.actor, "key.revoke", &key_target(id), before.map(|before| json!(before)), Some(json!(key))).await;
+        Ok(Json(Envelope::data(key, Meta::new())))
+    }
+
+    /// Delivery statistics of the notification channels
+    #[get("/notifiers")]
+    pub async fn get_notifiers(_admin: AdminToken) -> Result<Json<Envelope<Vec<NotifierStats>>>, ErrorResponse> {
+
+        let notifiers = get_notifier_stats().await.map_err(store_error)?;
+        let meta = Meta { count: Some(notifiers.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(notifiers, meta)))
+    }
+
+    /// Returns the last records of the alarm API that were quarantined by the poller, newest first
+    #[get("/quarantine?<limit>")]
+    pub async fn get_quarantined(_admin: AdminToken, limit: Option<usize>) -> Result<Json<Envelope<Vec<QuarantineEntry>>>, ErrorResponse> {
+
+        let entries = get_quarantine(limit.unwrap_or(QUARANTINE_LENGTH)).await.map_err(store_error)?;
+        let meta = Meta { count: Some(entries.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(entries, meta)))
+    }
+
+    /// Longest note of a region
+    const MAX_NOTE_LENGTH: usize = 500;
+
+    /// Returns an error if the region is not in the registry
+    fn check_region(region_id: i32) -> Result<(), ErrorResponse> {
+        match get_all_cities().contains_key(&region_id) {
+            true => Ok(()),
+            false => Err(error_response(Status::NotFound, format!("region {} not found", region_id))),
+        }
+    }
+
+    /// Body of the request to force the alarm state of a region
+    #[derive(Debug, Deserialize)]
+    pub struct OverrideRequest {
+        pub alarm_active: bool,
+        /// Lifetime of the override, at most MAX_OVERRIDE_MINUTES
+        pub minutes: i64,
+        pub reason: Option<String>,
+    }
+
+    /// Forces the alarm state of a region for a limited time.
+    /// The previous override of the region is replaced.
+    #[put("/overrides/<region_id>", data = "<request>")]
+    pub async fn put_override(admin: AdminToken, region_id: i32, request: Json<OverrideRequest>) -> Result<Json<Envelope<Override>>, ErrorResponse> {
+
+        check_region(region_id)?;
+        if !(1..=MAX_OVERRIDE_MINUTES).contains(&request.minutes) {
+            return Err(error_response(
+                Status::UnprocessableEntity,
+                format!("the lifetime of an override must be from 1 to {} minutes", MAX_OVERRIDE_MINUTES),
+            ));
+        }
+
+        let entry = Override {
+            region_id,
+            alarm_active: request.alarm_active,
+            reason: request.reason.as_deref().map(str::trim).filter(|reason| !reason.is_empty()).map(str::to_string),
+            created_by: admin.actor,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            expires_at: expires_after(request.minutes),
+        };
+        set_override(&entry).await.map_err(store_error)?;
+        Ok(Json(Envelope::data(entry, Meta::new())))
+    }
+
+    /// Overrides that did not expire yet ordered by the region identifier
+    #[get("/overrides")]
+    pub async fn get_active_overrides(_admin: AdminToken) -> Result<Json<Envelope<Vec<Override>>>, ErrorResponse> {
+
+        let mut overrides: Vec<Override> = get_overrides().await.map_err(store_error)?.into_values().collect();
+        overrides.sort_by_key(|entry| entry.region_id);
+        let meta = Meta { count: Some(overrides.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(overrides, meta)))
+    }
+
+    /// Removes the override, the state from the alarm API is used again
+    #[delete("/overrides/<region_id>")]
+    pub async fn delete_override(admin: AdminToken, region_id: i32) -> Result<Json<Envelope<Override>>, ErrorResponse> {
+
+        let entry = remove_override(region_id, &admin.actor)
+            .await
+            .map_err(store_error)?
+            .ok_or_else(|| error_response(Status::NotFound, format!("no override of region {}", region_id)))?;
+        Ok(Json(Envelope::data(entry, Meta::new())))
+    }
+
+    /// Body of the request to attach a note to a region
+    #[derive(Debug, Deserialize)]
+    pub struct NoteRequest {
+        pub text: String,
+        /// Lifetime of the note, the note is kept until it is deleted if it is not set
+        pub minutes: Option<i64>,
+    }
+
+    /// Attaches a note to a region, the note is shown with the alarm state of the region
+    #[put("/notes/<region_id>", data = "<request>")]
+    pub async fn put_note(admin: AdminToken, region_id: i32, request: Json<NoteRequest>) -> Result<Json<Envelope<RegionNote>>, ErrorResponse> {
+
+        check_region(region_id)?;
+        let text = request.text.trim();
+        if text.is_empty() || text.chars().count() > MAX_NOTE_LENGTH {
+            return Err(error_response(
+                Status::UnprocessableEntity,
+                format!("the text of a note must have from 1 to {} characters", MAX_NOTE_LENGTH),
+            ));
+        }
+        if request.minutes.is_some_and(|minutes| minutes < 1) {
+            return Err(error_response(Status::UnprocessableEntity, "the lifetime of a note must be at least 1 minute"));
+        }
+
+        let note = RegionNote {
+            region_id,
+            text: text.to_string(),
+            created_by: admin.actor,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            expires_at: request.minutes.map(expires_after),
+        };
+        set_note(&note).await.map_err(store_error)?;
+        Ok(Json(Envelope::data(note, Meta::new())))
+    }
+
+    /// Notes that did not expire yet ordered by the region identifier
+    #[get("/notes")]
+    pub async fn get_active_notes(_admin: AdminToken) -> Result<Json<Envelope<Vec<RegionNote>>>, ErrorResponse> {
+
+        let mut notes: Vec<RegionNote> = get_notes().await.map_err(store_error)?.into_values().collect();
+        notes.sort_by_key(|note| note.region_id);
+        let meta = Meta { count: Some(notes.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(notes, meta)))
+    }
+
+    /// Removes the note of a region
+    #[delete("/notes/<region_id>")]
+    pub async fn delete_note(admin: AdminToken, region_id: i32) -> Result<Json<Envelope<RegionNote>>, ErrorResponse> {
+
+        let note = remove_note(region_id, &admin.actor)
+            .await
+            .map_err(store_error)?
+            .ok_or_else(|| error_response(Status::NotFound, format!("no note of region {}", region_id)))?;
+        Ok(Json(Envelope::data(note, Meta::new())))
+    }
+
+    /// Parses a time of the audit filter
+    fn audit_time(parameter: &str, value: Option<&str>) -> Result<Option<chrono::DateTime<chrono::Utc>>, ErrorResponse> {
+        value
+            .map(|value| {
+                chrono::DateTime::parse_from_rfc3339(value)
+                    .map(|time| time.with_timezone(&chrono::Utc))
+                    .map_err(|_| error_response(Status::BadRequest, format!("{} must be a time in the RFC 3339 format", parameter)))
+            })
+            .transpose()
+    }
+
+    /// Audit entries that match all given conditions, newest first.
+    /// "action" is the whole action ("override.set") or its object ("override"),
+    /// "since" and "until" are times in the RFC 3339 format.
+    #[get("/audit?<actor>&<action>&<target>&<since>&<until>&<limit>")]
+    pub async fn get_audit_entries(
+        _admin: AdminToken,
+        actor: Option<String>,
+        action: Option<String>,
+        target: Option<String>,
+        since: Option<&str>,
+        until: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Json<Envelope<Vec<AuditEntry>>>, ErrorResponse> {
+
+        let filter = AuditFilter {
+            actor,
+            action,
+            target,
+            since: audit_time("since", since)?,
+            until: audit_time("until", until)?,
+        };
+        let entries = get_audit(&filter, limit.unwrap_or(100)).await.map_err(store_error)?;
+        let meta = Meta { count: Some(entries.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(entries, meta)))
+    }
+
+    /// Returns all routes of the admin API
+    pub fn routes() -> Vec<Route> {
+        routes![
+            post_key,
+            get_keys,
+            get_one_key,
+            post_rotate_key,
+            delete_key,
+            get_notifiers,
+            get_quarantined,
+            put_override,
+            get_active_overrides,
+            delete_override,
+            put_note,
+            get_active_notes,
+            delete_note,
+            get_audit_entries
+        ]
+    }
+
+}
@@ -2,9 +2,12 @@ pub mod api_actions {
 
     use rocket::serde::json::Json;
     use serde::Serialize;
-    use tokio::task;
 
-    use crate::modules::{other_data::other_data::get_all_cities, processing::processing::check_alarm};
+    use crate::modules::{
+        other_data::other_data::get_all_cities,
+        overrides::overrides::{active_overrides, get_notes},
+        processing::processing::{check_alarm, get_all_alarm_start_times, get_all_alarm_statuses},
+    };
 
     ///Structure for storing data returned by a request
     #[derive(Debug, Serialize)]
@@ -14,57 +17,85 @@ pub mod api_actions {
         pub is_active_air_alarm: bool
     }
 
+    /// Alarm state of a location returned by the second version of the API
+    #[derive(Debug, Clone, Serialize)]
+    pub struct AlarmState {
+        pub region_id: i32,
+        pub region_name: String,
+        pub alarm_active: bool,
+        pub alarm_started_at: Option<String>,
+        /// "upstream" for the state from the alarm API, "manual" for a manual override
+        pub source: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub override_expires_at: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub note: Option<String>,
+    }
+
 
     /// Method for getting alarm information in the specified region.
     /// The return value is serialized to JSON.
-    /// The function returns a JSON with the data.
-    pub async fn response_builder_for_one_location(location_uid: i32) -> Json<ApiResponse> {
+    /// The function returns None if the region is unknown.
+    pub async fn response_builder_for_one_location(location_uid: i32) -> Option<Json<ApiResponse>> {
 
+        let location_name = get_all_cities().remove(&location_uid)?;
         let alert_status = check_alarm(location_uid).await;
-        let all_cities = get_all_cities();
-        let basic_location_info = all_cities.get(&location_uid).unwrap();
 
         let result = ApiResponse {
             location_uid,
-            location_name: basic_location_info.to_string(),
+            location_name,
             is_active_air_alarm: alert_status,
         };
 
-        Json(result)
+        Some(Json(result))
     }
 
     /// Method for getting alarm information in some specified regions.
     /// The return value is serialized to JSON.
     /// The function returns a JSON with the data.
     pub async fn response_builder_for_some_locations(locaion_uids: Vec<i32>) -> Json<Vec<ApiResponse>> {
-        
-        let data = {
-            let cities = get_all_cities();
-            let mut _buf = Vec::<ApiResponse>::new();
-
-            for _i in locaion_uids {
-
-                let value = task::spawn(async move {
-                    let is_alarm = check_alarm(_i).await;
-                    is_alarm
-                }).await.unwrap();
-                
-                let region_name = cities.get(&_i).unwrap().clone();
-                let response_data = ApiResponse {
-                    location_uid: _i,
-                    location_name: region_name,
-                    is_active_air_alarm: value
-                };
-                _buf.push(response_data);
-
-            }
-            _buf
-        };
+
+        let data = get_alarm_states(&locaion_uids)
+            .await
+            .into_iter()
+            .map(|state| ApiResponse {
+                location_uid: state.region_id,
+                location_name: state.region_name,
+                is_active_air_alarm: state.alarm_active,
+            })
+            .collect();
 
         Json(data)
     }
 
-    
-
+    /// Method for getting the alarm state of the specified regions
+    /// in the order of the passed identifiers.
+    /// The states are read with a few requests to the store, unknown regions are skipped.
+    pub async fn get_alarm_states(location_uids: &[i32]) -> Vec<AlarmState> {
+
+        let cities = get_all_cities();
+        let statuses = get_all_alarm_statuses().await;
+        let start_times = get_all_alarm_start_times().await;
+        let overrides = active_overrides().await;
+        let notes = get_notes().await.unwrap_or_default();
+
+        location_uids
+            .iter()
+            .filter_map(|location_uid| {
+                let region_name = cities.get(location_uid)?.clone();
+                let alarm_active = statuses.get(location_uid).copied().unwrap_or(false);
+                let manual = overrides.get(location_uid);
+                Some(AlarmState {
+                    region_id: *location_uid,
+                    region_name,
+                    alarm_active,
+                    alarm_started_at: start_times.get(location_uid).filter(|_| alarm_active).cloned(),
+                    source: if manual.is_some() { "manual" } else { "upstream" },
+                    override_expires_at: manual.map(|entry| entry.expires_at.clone()),
+                    note: notes.get(location_uid).map(|note| note.text.clone()),
+                })
+            })
+            .collect()
+    }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,319 @@
+pub mod api_keys {
+
+    use std::time::Duration;
+    use log::debug;
+    use rand::RngCore;
+    use rocket::{
+        http::Status,
+        request::{self, FromRequest, Request},
+    };
+    use serde::{Deserialize, Serialize};
+    use sha2::{Digest, Sha256};
+
+    use crate::modules::{
+        api_v2::api_v2::ErrorDetail,
+        store::store::{store, StoreResult},
+    };
+
+    /// Hash with the records of all keys, the field is the key identifier
+    const KEYS: &str = "api_keys";
+
+    /// Hash with the identifiers of active keys, the field is the SHA-256 hash of the key
+    const KEY_HASHES: &str = "api_key_hashes";
+
+    /// Hash with the number of requests made with every key since it was issued
+    const USAGE_TOTAL: &str = "api_key_usage:total";
+
+    /// Daily usage counters are kept for a week
+    const DAILY_USAGE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    /// Header with the API key
+    pub const API_KEY_HEADER: &str = "X-API-Key";
+
+    /// Authentication settings from the "auth" section of the Rocket configuration.
+    /// If a key is not required, requests without a key are served anonymously,
+    /// but requests with an unknown or revoked key are still rejected.
+    #[derive(Debug, Clone, Default, Deserialize)]
+    pub struct AuthConfig {
+        #[serde(default)]
+        pub required: bool,
+    }
+
+    /// Struct for representing an API key in the store.
+    /// Only the hash of the key is stored, the key itself is shown once when it is issued.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ApiKeyRecord {
+        pub id: String,
+        pub name: String,
+        pub prefix: String,
+        pub key_hash: String,
+        pub daily_quota: Option<u64>,
+        pub created_at: String,
+        pub rotated_at: Option<String>,
+        pub revoked_at: Option<String>,
+    }
+
+    /// Usage counters of a key
+    #[derive(Debug, Clone, Serialize)]
+    pub struct ApiKeyUsage {
+        pub today: u64,
+        pub total: u64,
+        pub daily_quota: Option<u64>,
+        pub remaining_today: Option<u64>,
+    }
+
+    /// Information about a key returned by the admin API
+    #[derive(Debug, Clone, Serialize)]
+    pub struct ApiKeyInfo {
+        pub id: String,
+        pub name: String,
+        pub prefix: String,
+        pub daily_quota: Option<u64>,
+        pub created_at: String,
+        pub rotated_at: Option<String>,
+        pub revoked_at: Option<String>,
+        pub usage: ApiKeyUsage,
+    }
+
+    /// A newly issued or rotated key. The key is returned only in this response.
+    #[derive(Debug, Clone, Serialize)]
+    pub struct IssuedApiKey {
+        pub key: String,
+        #[serde(flatten)]
+        pub info: ApiKeyInfo,
+    }
+
+    /// Returns the name of the hash with the daily usage of all keys
+    fn daily_usage_key() -> String {
+        format!("api_key_usage:{}", chrono::Utc::now().format("%Y-%m-%d"))
+    }
+
+    /// Returns random bytes as a hex string
+    fn random_hex(bytes: usize) -> String {
+        let mut buf = vec![0u8; bytes];
+        rand::thread_rng().fill_bytes(&mut buf);
+        buf.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Returns the SHA-256 hash of a key as a hex string
+    pub fn hash_key(key: &str) -> String {
+        Sha256::digest(key.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Generates a new key
+    fn generate_key() -> String {
+        format!("ak_{}", random_hex(20))
+    }
+
+    async fn save_record(record: &ApiKeyRecord) -> StoreResult<()> {
+        store().hset(KEYS, &record.id, &serde_json::to_string(record).unwrap()).await
+    }
+
+    async fn load_record(id: &str) -> StoreResult<Option<ApiKeyRecord>> {
+        let value = store().hget(KEYS, id).await?;
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    /// Adds the usage counters to the record of a key
+    async fn key_info(record: ApiKeyRecord) -> StoreResult<ApiKeyInfo> {
+
+        let today: u64 = store().hget(&daily_usage_key(), &record.id).await?.and_then(|v| v.parse().ok()).unwrap_or(0);
+        let total: u64 = store().hget(USAGE_TOTAL, &record.id).await?.and_then(|v| v.parse().ok()).unwrap_or(0);
+
+        Ok(ApiKeyInfo {
+            usage: ApiKeyUsage {
+                today,
+                total,
+                daily_quota: record.daily_quota,
+                remaining_today: record.daily_quota.map(|quota| quota.saturating_sub(today)),
+            },
+            id: record.id,
+            name: record.name,
+            prefix: record.prefix,
+            daily_quota: record.daily_quota,
+            created_at: record.created_at,
+            rotated_at: record.rotated_at,
+            revoked_at: record.revoked_at,
+        })
+    }
+
+    /// Issues a new key with an optional daily quota of requests
+    pub async fn issue_key(name: &str, daily_quota: Option<u64>) -> StoreResult<IssuedApiKey> {
+
+        let key = generate_key();
+        let record = ApiKeyRecord {
+            id: random_hex(8),
+            name: name.to_string(),
+            prefix: key[..10].to_string(),
+            key_hash: hash_key(&key),
+            daily_quota,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            rotated_at: None,
+            revoked_at: None,
+        };
+
+        save_record(&record).await?;
+        store().hset(KEY_HASHES, &record.key_hash, &record.id).await?;
+        debug!("{} - API key {} issued", chrono::Local::now(), record.id);
+
+        Ok(IssuedApiKey { key, info: key_info(record).await? })
+    }
+
+    /// Replaces the key with a new one, the old key stops working immediately.
+    /// Returns None if there is no active key with the identifier.
+    pub async fn rotate_key(id: &str) -> StoreResult<Option<IssuedApiKey>> {
+
+        let mut record = match load_record(id).await? {
+            Some(record) if record.revoked_at.is_none() => record,
+            _ => return Ok(None),
+        };
+
+        let key = generate_key();
+        store().hdel(KEY_HASHES, &record.key_hash).await?;
+
+        record.prefix = key[..10].to_string();
+        record.key_hash = hash_key(&key);
+        record.rotated_at = Some(chrono::Utc::now().to_rfc3339());
+
+        save_record(&record).await?;
+        store().hset(KEY_HASHES, &record.key_hash, &record.id).await?;
+        debug!("{} - API key {} rotated", chrono::Local::now(), record.id);
+
+        Ok(Some(IssuedApiKey { key, info: key_info(record).await? }))
+    }
+
+    /// Revokes the key. The record and usage counters are kept.
+    /// Returns None if there is no key with the identifier.
+    pub async fn revoke_key(id: &str) -> StoreResult<Option<ApiKeyInfo>> {
+
+        let mut record = match load_record(id).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        if record.revoked_at.is_none() {
+            store().hdel(KEY_HASHES, &record.key_hash).await?;
+            record.revoked_at = Some(chrono::Utc::now().to_rfc3339());
+            save_record(&record).await?;
+            debug!("{} - API key {} revoked", chrono::Local::now(), record.id);
+        }
+
+        Ok(Some(key_info(record).await?))
+    }
+
+    /// Returns the information about a key with its usage counters
+    pub async fn get_key(id: &str) -> StoreResult<Option<ApiKeyInfo>> {
+        match load_record(id).await? {
+            Some(record) => Ok(Some(key_info(record).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all keys with their usage counters ordered by the time of creation
+    pub async fn list_keys() -> StoreResult<Vec<ApiKeyInfo>> {
+
+        let mut records: Vec<ApiKeyRecord> = store()
+            .hgetall(KEYS)
+            .await?
+            .values()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .collect();
+        records.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        let mut result = Vec::with_capacity(records.len());
+        for record in records {
+            result.push(key_info(record).await?);
+        }
+        Ok(result)
+    }
+
+    /// Returns the identifier of the key, None if the key is unknown or revoked
+    pub async fn active_key_id(key: &str) -> StoreResult<Option<String>> {
+        store().hget(KEY_HASHES, &hash_key(key)).await
+    }
+
+    /// Result of the check of an active key
+    enum KeyCheck {
+        Accepted,
+        /// The request is over the daily quota of the key
+        QuotaExceeded(u64),
+    }
+
+    /// Finds the active key and counts the request if it is within the daily quota.
+    /// Rejected requests are not counted, so the usage shows only the served requests.
+    async fn authenticate(key: &str) -> StoreResult<Option<KeyCheck>> {
+
+        let id = match active_key_id(key).await? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let record = match load_record(&id).await? {
+            Some(record) if record.revoked_at.is_none() => record,
+            _ => return Ok(None),
+        };
+
+        let today = store().hincr(&daily_usage_key(), &id, 1, Some(DAILY_USAGE_TTL)).await?;
+        if let Some(quota) = record.daily_quota.filter(|quota| today.max(0) as u64 > *quota) {
+            store().hincr(&daily_usage_key(), &id, -1, None).await?;
+            return Ok(Some(KeyCheck::QuotaExceeded(quota)));
+        }
+        store().hincr(USAGE_TOTAL, &id, 1, None).await?;
+
+        Ok(Some(KeyCheck::Accepted))
+    }
+
+    /// Request guard that checks the API key of a request.
+    /// The key is passed in the X-API-Key header or in the api_key query parameter.
+    /// Anonymous requests are allowed if the key is not required.
+    #[derive(Debug, Clone)]
+    pub struct ApiKey;
+
+    /// Reason why the API key of a request was rejected
+    #[derive(Debug, Clone)]
+    pub enum ApiKeyError {
+        Missing,
+        Invalid,
+        QuotaExceeded,
+        Unavailable,
+    }
+
+    /// Returns the API key passed with the request
+    pub fn request_api_key<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+        request
+            .headers()
+            .get_one(API_KEY_HEADER)
+            .or_else(|| request.query_value::<&str>("api_key").and_then(|value| value.ok()))
+    }
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for ApiKey {
+        type Error = ApiKeyError;
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+
+            let required = request.rocket().state::<AuthConfig>().map(|config| config.required).unwrap_or(false);
+
+            let (status, error, detail) = match request_api_key(request) {
+                None if !required => return request::Outcome::Success(ApiKey),
+                None => (Status::Unauthorized, ApiKeyError::Missing, format!("the {} header is required", API_KEY_HEADER)),
+                Some(key) => match authenticate(key).await {
+                    Ok(Some(KeyCheck::Accepted)) => return request::Outcome::Success(ApiKey),
+                    Ok(Some(KeyCheck::QuotaExceeded(quota))) => (
+                        Status::TooManyRequests,
+                        ApiKeyError::QuotaExceeded,
+                        format!("the daily quota of {} requests is exceeded", quota),
+                    ),
+                    Ok(None) => (Status::Unauthorized, ApiKeyError::Invalid, "the API key is invalid or revoked".to_string()),
+                    Err(e) => {
+                        debug!("{} - error checking the API key\n{}", chrono::Local::now(), e);
+                        (Status::ServiceUnavailable, ApiKeyError::Unavailable, "the API key can not be checked".to_string())
+                    }
+                },
+            };
+
+            request.local_cache(|| ErrorDetail(Some(detail)));
+            request::Outcome::Error((status, error))
+        }
+    }
+
+}
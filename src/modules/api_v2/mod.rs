@@ -0,0 +1,290 @@
+pub mod api_v2 {
+
+    use rocket::{
+        catch, catchers, get, routes, Catcher, Request, Route,
+        http::Status,
+        response::{content::{RawHtml, RawJson}, status::Custom},
+        serde::json::{Json, Value},
+    };
+    use serde::Serialize;
+
+    use crate::modules::{
+        api_actions::api_actions::{get_alarm_states, AlarmState},
+        api_keys::api_keys::ApiKey,
+        caching::caching::{snapshot_etag, Cached, Conditions},
+        id_query::id_query::{IdQueryError, IdSelection},
+        openapi::openapi::{openapi_document, DOCS_PAGE},
+        other_data::other_data::get_regions,
+        processing::processing::{get_snapshot_info, get_source_info, SourceInfo},
+    };
+
+    /// The registry of locations changes only with a new release of the service,
+    /// so clients may keep it for a day and then revalidate it with the entity tag.
+    /// The URL is not versioned, so the registry is not cached as immutable.
+    pub const REGIONS_CACHE_CONTROL: &str = "public, max-age=86400";
+
+    /// Metadata of a response. Fields that do not apply to an endpoint are omitted.
+    #[derive(Debug, Serialize)]
+    pub struct Meta {
+        pub api_version: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub count: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub sort: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub snapshot_version: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub last_modified: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub upstream_updated_at: Option<String>,
+    }
+
+    impl Meta {
+        pub fn new() -> Self {
+            Meta { api_version: "2", count: None, sort: None, snapshot_version: None, last_modified: None, upstream_updated_at: None }
+        }
+    }
+
+    impl Default for Meta {
+        fn default() -> Self {
+            Meta::new()
+        }
+    }
+
+    /// Part of the request that caused an error
+    #[derive(Debug, Clone, Serialize)]
+    pub struct ErrorSource {
+        pub parameter: String,
+        pub token: String,
+        pub position: usize,
+    }
+
+    /// Description of an error returned in the "errors" list of a response
+    #[derive(Debug, Clone, Serialize)]
+    pub struct ApiError {
+        pub status: u16,
+        pub code: String,
+        pub detail: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub source: Option<ErrorSource>,
+    }
+
+    /// Envelope of every response of the second version of the API.
+    /// Successful responses have "data" and an empty "errors" list,
+    /// failed responses have "data" set to null and at least one error.
+    #[derive(Debug, Serialize)]
+    pub struct Envelope<T> {
+        pub data: Option<T>,
+        pub meta: Meta,
+        pub errors: Vec<ApiError>,
+    }
+
+    impl<T> Envelope<T> {
+        pub fn data(data: T, meta: Meta) -> Self {
+            Envelope { data: Some(data), meta, errors: Vec::new() }
+        }
+    }
+
+    /// Description of an error set by a request guard.
+    /// The catcher uses it instead of the generic reason phrase of the status.
+    #[derive(Debug, Default)]
+    pub struct ErrorDetail(pub Option<String>);
+
+    /// Response with an error in the envelope
+    pub type ErrorResponse = Custom<Json<Box<Envelope<()>>>>;
+
+    /// Returns the reason phrase of the status in snake case
+    fn error_code(status: Status) -> String {
+        status
+            .reason()
+            .unwrap_or("error")
+            .to_lowercase()
+            .replace([' ', '-'], "_")
+    }
+
+    /// Builds a response with the list of errors
+    pub fn errors_response(status: Status, errors: Vec<ApiError>) -> ErrorResponse {
+        let envelope = Envelope { data: None, meta: Meta::new(), errors };
+        Custom(status, Json(Box::new(envelope)))
+    }
+
+    /// Builds a response with one error.
+    /// The error code is the reason phrase of the status in snake case.
+    pub fn error_response(status: Status, detail: impl Into<String>) -> ErrorResponse {
+        let error = ApiError { status: status.code, code: error_code(status), detail: detail.into(), source: None };
+        errors_response(status, vec![error])
+    }
+
+    /// Builds a response with one error for every invalid region identifier of the request
+    fn id_query_error_response(error: IdQueryError) -> ErrorResponse {
+
+        let status = Status::BadRequest;
+        if error.invalid_tokens.is_empty() {
+            return error_response(status, error.error);
+        }
+
+        let errors = error.invalid_tokens
+            .into_iter()
+            .map(|token| ApiError {
+                status: status.code,
+                code: "invalid_region_id".to_string(),
+                detail: format!("'{}': {}", token.token, token.reason),
+                source: Some(ErrorSource { parameter: token.parameter, token: token.token, position: token.position }),
+            })
+            .collect();
+        errors_response(status, errors)
+    }
+
+    /// List of all available locations with their types, names and codes.
+    /// The locations are ordered by the identifier (sort=id, default)
+    /// or by the English name (sort=name).
+    #[get("/regions?<sort>")]
+    pub fn get_regions_v2(_api_key: ApiKey, sort: Option<&str>) -> Result<Cached<RawJson<String>>, ErrorResponse> {
+
+        let mut regions = get_regions();
+        let sort = sort.unwrap_or("id");
+
+        match sort {
+            "id" => regions.sort_by_key(|region| region.id),
+            "name" => regions.sort_by(|a, b| a.names.en.cmp(&b.names.en)),
+            other => {
+                return Err(error_response(
+                    Status::BadRequest,
+                    format!("unknown sort order '{}', expected 'id' or 'name'", other),
+                ));
+            }
+        }
+
+        let meta = Meta { count: Some(regions.len()), sort: Some(sort.to_string()), ..Meta::new() };
+        Ok(Cached::json(&Envelope::data(regions, meta), Some(REGIONS_CACHE_CONTROL)))
+    }
+
+    /// One location from the registry
+    #[get("/regions/<id>")]
+    pub fn get_region_v2(_api_key: ApiKey, id: i32) -> Result<Cached<RawJson<String>>, ErrorResponse> {
+
+        let region = get_regions()
+            .into_iter()
+            .find(|region| region.id == id)
+            .ok_or_else(|| error_response(Status::NotFound, format!("region {} not found", id)))?;
+
+        Ok(Cached::json(&Envelope::data(region, Meta::new()), Some(REGIONS_CACHE_CONTROL)))
+    }
+
+    /// Alarm state of the requested locations in the order of the request
+    /// (ids=3,9,31 or id=3&id=9) or of all available locations ordered by the location identifier
+    #[get("/alarms")]
+    pub async fn get_alarms_v2(_api_key: ApiKey, selection: Result<IdSelection, IdQueryError>, conditions: Conditions) -> Result<Cached<Json<Envelope<Vec<AlarmState>>>>, ErrorResponse> {
+
+        let selection = selection.map_err(id_query_error_response)?;
+
+        let snapshot = get_snapshot_info().await;
+        let etag = snapshot_etag(snapshot.version);
+        if conditions.is_not_modified(&etag, snapshot.last_modified.as_deref()) {
+            return Ok(Cached::not_modified(etag, snapshot.last_modified));
+        }
+
+        let location_uids: Vec<i32> = match selection.is_selected {
+            true => selection.ids,
+            false => get_regions().iter().map(|region| region.id).collect(),
+        };
+        let states = get_alarm_states(&location_uids).await;
+
+        let meta = Meta {
+            count: Some(states.len()),
+            snapshot_version: Some(snapshot.version),
+            last_modified: snapshot.last_modified.clone(),
+            upstream_updated_at: snapshot.upstream_updated_at.clone(),
+            ..Meta::new()
+        };
+        Ok(Cached::new(Json(Envelope::data(states, meta)), etag, snapshot.last_modified))
+    }
+
+    /// Alarm state of one location
+    #[get("/alarms/<id>")]
+    pub async fn get_alarm_v2(_api_key: ApiKey, id: i32, conditions: Conditions) -> Result<Cached<Json<Envelope<AlarmState>>>, ErrorResponse> {
+
+        if !get_regions().iter().any(|region| region.id == id) {
+            return Err(error_response(Status::NotFound, format!("region {} not found", id)));
+        }
+
+        let snapshot = get_snapshot_info().await;
+        let etag = snapshot_etag(snapshot.version);
+        if conditions.is_not_modified(&etag, snapshot.last_modified.as_deref()) {
+            return Ok(Cached::not_modified(etag, snapshot.last_modified));
+        }
+
+        let state = get_alarm_states(&[id])
+            .await
+            .pop()
+            .ok_or_else(|| error_response(Status::NotFound, format!("region {} not found", id)))?;
+
+        let meta = Meta {
+            snapshot_version: Some(snapshot.version),
+            last_modified: snapshot.last_modified.clone(),
+            upstream_updated_at: snapshot.upstream_updated_at.clone(),
+            ..Meta::new()
+        };
+        Ok(Cached::new(Json(Envelope::data(state, meta)), etag, snapshot.last_modified))
+    }
+
+    /// Metadata of the last response of the alarm API with the checks of its timestamps
+    #[get("/source")]
+    pub async fn get_source_v2(_api_key: ApiKey) -> Result<Json<Envelope<SourceInfo>>, ErrorResponse> {
+
+        let info = get_source_info()
+            .await
+            .ok_or_else(|| error_response(Status::ServiceUnavailable, "no response of the alarm API received yet".to_string()))?;
+
+        let meta = Meta {
+            last_modified: info.last_modified.clone(),
+            upstream_updated_at: info.last_updated_at.clone(),
+            ..Meta::new()
+        };
+        Ok(Json(Envelope::data(info, meta)))
+    }
+
+    /// OpenAPI 3 document of the second version of the API
+    #[get("/openapi.json")]
+    pub fn get_openapi_document() -> Json<Value> {
+        Json(openapi_document())
+    }
+
+    /// Interactive documentation rendered from the OpenAPI document
+    #[get("/docs")]
+    pub fn get_docs() -> RawHtml<&'static str> {
+        RawHtml(DOCS_PAGE)
+    }
+
+    /// Every error of the second version of the API is returned in the envelope
+    #[catch(default)]
+    pub fn default_catcher(status: Status, request: &Request) -> ErrorResponse {
+        if let ErrorDetail(Some(detail)) = request.local_cache(ErrorDetail::default) {
+            return error_response(status, detail.clone());
+        }
+        let detail = match status.code {
+            404 => format!("resource '{}' not found", request.uri().path()),
+            _ => status.reason().unwrap_or("unknown error").to_string(),
+        };
+        error_response(status, detail)
+    }
+
+    /// Returns all routes of the second version of the API
+    pub fn routes() -> Vec<Route> {
+        routes![
+            get_regions_v2,
+            get_region_v2,
+            get_alarms_v2,
+            get_alarm_v2,
+            get_source_v2,
+            get_openapi_document,
+            get_docs
+        ]
+    }
+
+    /// Returns all catchers of the second version of the API
+    pub fn catchers() -> Vec<Catcher> {
+        catchers![default_catcher]
+    }
+
+}
@@ -0,0 +1,216 @@
+pub mod audit {
+
+    use dotenv::dotenv;
+    use std::env;
+    use chrono::{DateTime, Duration, Utc};
+    use log::{debug, info, warn};
+    use once_cell::sync::Lazy;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::modules::store::store::{store, StoreResult};
+
+    /// List with the audit entries, newest first
+    const AUDIT_KEY: &str = "audit";
+
+    /// Hash with the last identifier of the audit entries
+    const SEQUENCE_KEY: &str = "audit:sequence";
+
+    /// Number of entries read at once by the queries and when the outdated entries are removed
+    const READ_BATCH: usize = 100;
+
+    /// Actor of the changes made by the service itself, for example the expiry of an override
+    pub const SYSTEM_ACTOR: &str = "system";
+
+    /// Settings of the retention from the AUDIT_* variables
+    #[derive(Debug, Clone)]
+    struct AuditConfig {
+        retention: Duration,
+        max_entries: usize,
+    }
+
+    ///Get the retention of the audit log: AUDIT_RETENTION_DAYS (90 by default)
+    ///and AUDIT_MAX_ENTRIES (10000 by default)
+    fn get_audit_config() -> AuditConfig {
+        dotenv().ok();
+        AuditConfig {
+            retention: Duration::days(
+                env::var("AUDIT_RETENTION_DAYS")
+                    .map(|value| value.parse().expect("INCORRECT AUDIT_RETENTION_DAYS"))
+                    .unwrap_or(90),
+            ),
+            max_entries: env::var("AUDIT_MAX_ENTRIES")
+                .map(|value| value.parse().expect("INCORRECT AUDIT_MAX_ENTRIES"))
+                .unwrap_or(10000),
+        }
+    }
+
+    static CONFIG: Lazy<AuditConfig> = Lazy::new(get_audit_config);
+
+    /// One action of an administrator or of the service
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct AuditEntry {
+        pub id: u64,
+        pub at: String,
+        pub actor: String,
+        /// Object and operation, for example "key.issue" or "override.set"
+        pub action: String,
+        /// Changed object, for example "key:<id>" or "region:9"
+        pub target: String,
+        pub before: Option<Value>,
+        pub after: Option<Value>,
+    }
+
+    /// Conditions of the audit entries to return
+    #[derive(Debug, Clone, Default)]
+    pub struct AuditFilter {
+        pub actor: Option<String>,
+        /// The whole action ("override.set") or its object ("override")
+        pub action: Option<String>,
+        pub target: Option<String>,
+        pub since: Option<DateTime<Utc>>,
+        pub until: Option<DateTime<Utc>>,
+    }
+
+    impl AuditFilter {
+
+        pub fn matches(&self, entry: &AuditEntry) -> bool {
+
+            let at = DateTime::parse_from_rfc3339(&entry.at).ok().map(|at| at.with_timezone(&Utc));
+            let action_matches = |action: &String| {
+                entry.action == *action || entry.action.strip_prefix(action.as_str()).is_some_and(|rest| rest.starts_with('.'))
+            };
+
+            self.actor.as_ref().is_none_or(|actor| entry.actor == *actor)
+                && self.action.as_ref().is_none_or(action_matches)
+                && self.target.as_ref().is_none_or(|target| entry.target == *target)
+                && self.since.is_none_or(|since| at.is_some_and(|at| at >= since))
+                && self.until.is_none_or(|until| at.is_some_and(|at| at < until))
+        }
+    }
+
+    async fn append(actor: &str, action: &str, target: &str, before: Option<Value>, after: Option<Value>) -> StoreResult<AuditEntry> {
+
+        let entry = AuditEntry {
+            id: store().hincr(SEQUENCE_KEY, "last", 1, None).await? as u64,
+            at: Utc::now().to_rfc3339(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            target: target.to_string(),
+            before,
+            after,
+        };
+        store().lpush_capped(AUDIT_KEY, &serde_json::to_string(&entry).unwrap(), CONFIG.max_entries).await?;
+        Ok(entry)
+    }
+
+    /// Appends an entry to the audit log.
+    /// The action is already done, so an error of the store is only logged.
+    pub async fn record_audit(actor: &str, action: &str, target: &str, before: Option<Value>, after: Option<Value>) {
+
+        info!("{} - audit: {} {} {}", chrono::Local::now(), actor, action, target);
+        if let Err(e) = append(actor, action, target, before, after).await {
+            warn!("{} - the audit entry {} {} {} was not saved\n{}", chrono::Local::now(), actor, action, target, e);
+        }
+    }
+
+    /// Returns the newest audit entries that match the filter.
+    /// The list is read from the head in batches until the limit is reached.
+    /// An entry recorded meanwhile shifts the list, so the entries that are not older
+    /// than the last returned one (by the identifier) are skipped.
+    pub async fn get_audit(filter: &AuditFilter, limit: usize) -> StoreResult<Vec<AuditEntry>> {
+
+        let mut found = Vec::new();
+        let mut last_id: Option<u64> = None;
+        let mut start = 0;
+        while found.len() < limit {
+            let batch = store().lrange(AUDIT_KEY, start as isize, (start + READ_BATCH) as isize - 1).await?;
+            for entry in batch.iter().filter_map(|value| serde_json::from_str::<AuditEntry>(value).ok()) {
+                if last_id.is_some_and(|last_id| entry.id >= last_id) {
+                    continue;
+                }
+                last_id = Some(entry.id);
+                if found.len() < limit && filter.matches(&entry) {
+                    found.push(entry);
+                }
+            }
+            if batch.len() < READ_BATCH {
+                break;
+            }
+            start += READ_BATCH;
+        }
+        Ok(found)
+    }
+
+    fn is_outdated(value: &str, cutoff: DateTime<Utc>) -> bool {
+        serde_json::from_str::<AuditEntry>(value)
+            .ok()
+            .and_then(|entry| DateTime::parse_from_rfc3339(&entry.at).ok())
+            .is_some_and(|at| at < cutoff)
+    }
+
+    /// Removes the entries older than the retention period.
+    /// The list is ordered by time with the oldest entries at the tail, so the entries are read from the tail
+    /// in batches until a recent one is found and removed from the tail in one step.
+    /// The indexes from the tail do not change when an entry is recorded meanwhile.
+    pub async fn prune_audit() {
+
+        let result = async {
+            let cutoff = Utc::now() - CONFIG.retention;
+            let mut outdated = 0;
+            loop {
+                let stop = -(outdated as isize) - 1;
+                let batch = store().lrange(AUDIT_KEY, stop - READ_BATCH as isize + 1, stop).await?;
+                // The batch is ordered from the newest entry to the oldest one
+                let count = batch.iter().rev().take_while(|value| is_outdated(value, cutoff)).count();
+                outdated += count;
+                if count < READ_BATCH {
+                    break;
+                }
+            }
+
+            if outdated > 0 {
+                store().rtrim(AUDIT_KEY, outdated).await?;
+            }
+            StoreResult::Ok(outdated)
+        }
+        .await;
+
+        match result {
+            Ok(0) => {}
+            Ok(removed) => info!("{} - {} outdated audit entries removed", chrono::Local::now(), removed),
+            Err(e) => debug!("{} - error removing the outdated audit entries\n{}", chrono::Local::now(), e),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::modules::store::store::{set_store, MemoryStore};
+
+        #[rocket::async_test]
+        async fn query_reads_the_newest_entries_in_batches() {
+
+            set_store(Box::new(MemoryStore::new()));
+            let count = READ_BATCH * 2 + 10;
+            for i in 0..count {
+                let actor = if i % 3 == 0 { "audit_test_bob" } else { "audit_test_alice" };
+                record_audit(actor, "note.set", &format!("region:{}", i), None, None).await;
+            }
+
+            let bob = AuditFilter { actor: Some("audit_test_bob".to_string()), ..Default::default() };
+            let newest = get_audit(&bob, 1).await.unwrap();
+            assert_eq!(newest.len(), 1);
+            let last_of_bob = (0..count).rev().find(|i| i % 3 == 0).unwrap();
+            assert_eq!(newest[0].target, format!("region:{}", last_of_bob));
+
+            // The matches span all batches, newest first without repeats
+            let all = get_audit(&bob, usize::MAX).await.unwrap();
+            assert_eq!(all.len(), count.div_ceil(3));
+            assert!(all.windows(2).all(|pair| pair[0].id > pair[1].id));
+            assert!(get_audit(&bob, 0).await.unwrap().is_empty());
+        }
+    }
+
+}
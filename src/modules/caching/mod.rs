@@ -0,0 +1,139 @@
+pub mod caching {
+
+    use chrono::DateTime;
+    use rocket::{
+        http::{Header, Status},
+        request::{self, FromRequest, Request},
+        response::{self, content::RawJson, Responder, Response},
+    };
+    use serde::Serialize;
+    use sha2::{Digest, Sha256};
+
+    /// Builds a strong entity tag from the representation of a resource.
+    /// The same representation always gets the same tag,
+    /// so the tag stays valid between restarts and between instances of the service.
+    pub fn strong_etag(content: &[u8]) -> String {
+        let digest = Sha256::digest(content);
+        let hash: String = digest.iter().take(16).map(|byte| format!("{:02x}", byte)).collect();
+        format!("\"{}\"", hash)
+    }
+
+    /// Builds an entity tag from the version of the alarm data stored in Redis.
+    /// The tag changes every time the poller receives new data from the alarm API.
+    pub fn snapshot_etag(version: u64) -> String {
+        format!("\"snapshot-{}\"", version)
+    }
+
+    /// Conditional headers of a request.
+    /// The guard never fails, absent headers are stored as None.
+    #[derive(Debug, Default)]
+    pub struct Conditions {
+        pub if_none_match: Option<String>,
+        pub if_modified_since: Option<String>,
+    }
+
+    impl Conditions {
+
+        /// Collects the conditional headers of a request
+        pub fn from_request(request: &Request<'_>) -> Self {
+            let headers = request.headers();
+            Conditions {
+                if_none_match: headers.get_one("If-None-Match").map(str::to_string),
+                if_modified_since: headers.get_one("If-Modified-Since").map(str::to_string),
+            }
+        }
+
+        /// Checks whether the client already has the representation with the given validators.
+        ///
+        /// As required by RFC 9110, "If-Modified-Since" is only evaluated
+        /// when the request has no "If-None-Match" header.
+        pub fn is_not_modified(&self, etag: &str, last_modified: Option<&str>) -> bool {
+
+            if let Some(if_none_match) = &self.if_none_match {
+                return if_none_match
+                    .split(',')
+                    .map(|tag| tag.trim())
+                    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+            }
+
+            match (&self.if_modified_since, last_modified) {
+                (Some(if_modified_since), Some(last_modified)) => {
+                    match (DateTime::parse_from_rfc2822(if_modified_since), DateTime::parse_from_rfc2822(last_modified)) {
+                        (Ok(if_modified_since), Ok(last_modified)) => last_modified <= if_modified_since,
+                        _ => false,
+                    }
+                }
+                _ => false,
+            }
+        }
+    }
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for Conditions {
+        type Error = std::convert::Infallible;
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+            request::Outcome::Success(Conditions::from_request(request))
+        }
+    }
+
+    /// Responder that adds the "ETag" and "Last-Modified" headers to a response
+    /// and answers with "304 Not Modified" if the client already has the same representation.
+    /// A response without a body (inner is None) is always sent as "304 Not Modified".
+    pub struct Cached<R> {
+        pub inner: Option<R>,
+        pub etag: String,
+        pub last_modified: Option<String>,
+        pub cache_control: Option<&'static str>,
+    }
+
+    impl<R> Cached<R> {
+
+        /// Tags a response with the validators of the representation
+        pub fn new(inner: R, etag: String, last_modified: Option<String>) -> Self {
+            Cached { inner: Some(inner), etag, last_modified, cache_control: None }
+        }
+
+        /// A "304 Not Modified" response with the validators of the representation
+        pub fn not_modified(etag: String, last_modified: Option<String>) -> Self {
+            Cached { inner: None, etag, last_modified, cache_control: None }
+        }
+    }
+
+    impl Cached<RawJson<String>> {
+
+        /// Serializes the value to JSON and tags it with a strong entity tag.
+        pub fn json<T: Serialize>(value: &T, cache_control: Option<&'static str>) -> Self {
+            let body = serde_json::to_string(value).unwrap();
+            Cached {
+                etag: strong_etag(body.as_bytes()),
+                inner: Some(RawJson(body)),
+                last_modified: None,
+                cache_control,
+            }
+        }
+    }
+
+    impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Cached<R> {
+        fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+
+            let is_not_modified = Conditions::from_request(request)
+                .is_not_modified(&self.etag, self.last_modified.as_deref());
+
+            let mut response = match self.inner {
+                Some(inner) if !is_not_modified => inner.respond_to(request)?,
+                _ => Response::build().status(Status::NotModified).finalize(),
+            };
+
+            response.set_header(Header::new("ETag", self.etag));
+            if let Some(last_modified) = self.last_modified {
+                response.set_header(Header::new("Last-Modified", last_modified));
+            }
+            if let Some(cache_control) = self.cache_control {
+                response.set_header(Header::new("Cache-Control", cache_control));
+            }
+            Ok(response)
+        }
+    }
+
+}
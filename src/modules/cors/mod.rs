@@ -0,0 +1,167 @@
+pub mod cors {
+
+    use rocket::{
+        options, routes, Build, Request, Response, Rocket,
+        fairing::{self, Fairing, Info, Kind},
+        http::{Header, Method, Status},
+    };
+    use serde::Deserialize;
+
+    /// CORS settings from the "cors" section of the Rocket configuration.
+    /// "*" in allowed_origins allows every origin.
+    #[derive(Debug, Clone, Deserialize)]
+    pub struct CorsConfig {
+        #[serde(default)]
+        pub allowed_origins: Vec<String>,
+        #[serde(default = "default_allowed_methods")]
+        pub allowed_methods: Vec<String>,
+        #[serde(default = "default_allowed_headers")]
+        pub allowed_headers: Vec<String>,
+        #[serde(default = "default_expose_headers")]
+        pub expose_headers: Vec<String>,
+        #[serde(default)]
+        pub allow_credentials: bool,
+        #[serde(default = "default_max_age")]
+        pub max_age: u64,
+    }
+
+    fn default_allowed_methods() -> Vec<String> {
+        ["GET", "HEAD", "OPTIONS"].iter().map(|method| method.to_string()).collect()
+    }
+
+    fn default_allowed_headers() -> Vec<String> {
+        ["Content-Type", "X-API-Key", "If-None-Match", "If-Modified-Since"].iter().map(|header| header.to_string()).collect()
+    }
+
+    fn default_expose_headers() -> Vec<String> {
+        ["ETag", "Last-Modified", "RateLimit-Limit", "RateLimit-Remaining", "RateLimit-Reset", "Retry-After"]
+            .iter()
+            .map(|header| header.to_string())
+            .collect()
+    }
+
+    fn default_max_age() -> u64 {
+        86400
+    }
+
+    impl Default for CorsConfig {
+        fn default() -> Self {
+            CorsConfig {
+                allowed_origins: Vec::new(),
+                allowed_methods: default_allowed_methods(),
+                allowed_headers: default_allowed_headers(),
+                expose_headers: default_expose_headers(),
+                allow_credentials: false,
+                max_age: default_max_age(),
+            }
+        }
+    }
+
+    impl CorsConfig {
+
+        fn allows_any_origin(&self) -> bool {
+            self.allowed_origins.iter().any(|origin| origin == "*")
+        }
+
+        fn allows_origin(&self, origin: &str) -> bool {
+            self.allows_any_origin() || self.allowed_origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        }
+
+        fn allows_method(&self, method: &str) -> bool {
+            self.allowed_methods.iter().any(|allowed| allowed.eq_ignore_ascii_case(method.trim()))
+        }
+
+        fn allows_header(&self, header: &str) -> bool {
+            self.allowed_headers.iter().any(|allowed| allowed.eq_ignore_ascii_case(header.trim()))
+        }
+
+        /// Value of the Access-Control-Allow-Origin header.
+        /// A wildcard can not be used with credentials, so the origin is echoed in that case.
+        fn allow_origin_value(&self, origin: &str) -> String {
+            if self.allows_any_origin() && !self.allow_credentials {
+                "*".to_string()
+            } else {
+                origin.to_string()
+            }
+        }
+    }
+
+    /// Every preflight request is answered here, the headers are added by the fairing
+    #[options("/<_..>")]
+    fn preflight() -> Status {
+        Status::NoContent
+    }
+
+    /// Checks whether the request is a CORS preflight request
+    pub fn is_preflight(request: &Request<'_>) -> bool {
+        request.method() == Method::Options
+            && request.headers().contains("Origin")
+            && request.headers().contains("Access-Control-Request-Method")
+    }
+
+    /// Fairing that adds the CORS headers to the responses for allowed origins
+    /// and answers the preflight requests of all routes.
+    pub struct Cors;
+
+    #[rocket::async_trait]
+    impl Fairing for Cors {
+
+        fn info(&self) -> Info {
+            Info { name: "CORS", kind: Kind::Ignite | Kind::Response }
+        }
+
+        async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+            let config: CorsConfig = rocket.figment().extract_inner("cors").unwrap_or_default();
+            Ok(rocket.manage(config).mount("/", routes![preflight]))
+        }
+
+        async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+
+            let config = match request.rocket().state::<CorsConfig>() {
+                Some(config) => config,
+                None => return,
+            };
+
+            // The headers depend on the origin, so a shared cache must not give a response
+            // cached for a request without the Origin header to a cross-origin caller
+            response.adjoin_header(Header::new("Vary", "Origin"));
+
+            let origin = match request.headers().get_one("Origin") {
+                Some(origin) => origin,
+                None => return,
+            };
+
+            if !config.allows_origin(origin) {
+                return;
+            }
+
+            if is_preflight(request) {
+
+                let method = request.headers().get_one("Access-Control-Request-Method").unwrap_or_default();
+                let headers: Vec<&str> = request
+                    .headers()
+                    .get("Access-Control-Request-Headers")
+                    .flat_map(|value| value.split(','))
+                    .map(str::trim)
+                    .filter(|header| !header.is_empty())
+                    .collect();
+
+                if !config.allows_method(method) || !headers.iter().all(|header| config.allows_header(header)) {
+                    return;
+                }
+
+                response.set_header(Header::new("Access-Control-Allow-Methods", config.allowed_methods.join(", ")));
+                response.set_header(Header::new("Access-Control-Allow-Headers", config.allowed_headers.join(", ")));
+                response.set_header(Header::new("Access-Control-Max-Age", config.max_age.to_string()));
+            } else if !config.expose_headers.is_empty() {
+                response.set_header(Header::new("Access-Control-Expose-Headers", config.expose_headers.join(", ")));
+            }
+
+            response.set_header(Header::new("Access-Control-Allow-Origin", config.allow_origin_value(origin)));
+            if config.allow_credentials {
+                response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+            }
+        }
+    }
+
+}
@@ -0,0 +1,138 @@
+pub mod dashboard {
+
+    use chrono::{DateTime, Local, Utc};
+    use serde::Serialize;
+
+    use crate::modules::{
+        other_data::other_data::get_all_cities,
+        processing::processing::{get_alarm_history, get_all_alarm_start_times, get_all_alarm_statuses, get_snapshot_info},
+    };
+
+    /// Number of seconds after which the dashboard page is reloaded
+    const REFRESH_INTERVAL: u32 = 7;
+
+    /// Struct for representing one row of the regions table
+    #[derive(Debug, Serialize)]
+    pub struct RegionRow {
+        pub location_uid: i32,
+        pub location_name: String,
+        pub is_active_air_alarm: bool,
+        pub started_at: Option<String>,
+        pub active_for: Option<String>,
+    }
+
+    /// Data rendered by the "dashboard" template
+    #[derive(Debug, Serialize)]
+    pub struct DashboardContext {
+        pub regions: Vec<RegionRow>,
+        pub active_count: usize,
+        pub last_modified: Option<String>,
+        pub last_poll: Option<String>,
+        pub refresh_interval: u32,
+    }
+
+    /// Struct for representing one entry of the region history
+    #[derive(Debug, Serialize)]
+    pub struct HistoryRow {
+        pub is_active_air_alarm: bool,
+        pub changed_at: String,
+    }
+
+    /// Data rendered by the "region" template
+    #[derive(Debug, Serialize)]
+    pub struct RegionContext {
+        pub region: RegionRow,
+        pub history: Vec<HistoryRow>,
+        pub last_poll: Option<String>,
+        pub refresh_interval: u32,
+    }
+
+    /// Converts an RFC 3339 time to the local time of the server
+    fn format_time(value: &str) -> String {
+        match DateTime::parse_from_rfc3339(value) {
+            Ok(time) => time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+            Err(_) => value.to_string(),
+        }
+    }
+
+    /// Returns a human-readable time passed since the start of the alarm
+    fn format_active_for(started_at: &str) -> Option<String> {
+
+        let started_at = DateTime::parse_from_rfc3339(started_at).ok()?;
+        let minutes = (Utc::now() - started_at.with_timezone(&Utc)).num_minutes().max(0);
+
+        let result = match (minutes / 1440, minutes % 1440 / 60, minutes % 60) {
+            (0, 0, m) => format!("{} min", m),
+            (0, h, m) => format!("{} h {} min", h, m),
+            (d, h, _) => format!("{} d {} h", d, h),
+        };
+        Some(result)
+    }
+
+    /// Builds a row of the regions table from the stored alarm data
+    fn build_region_row(location_uid: i32, location_name: String, is_active_air_alarm: bool, started_at: Option<&String>) -> RegionRow {
+
+        let started_at = started_at.filter(|_| is_active_air_alarm);
+
+        RegionRow {
+            location_uid,
+            location_name,
+            is_active_air_alarm,
+            started_at: started_at.map(|value| format_time(value)),
+            active_for: started_at.and_then(|value| format_active_for(value)),
+        }
+    }
+
+    /// Collects the data for the dashboard with the alarm state of all regions.
+    pub async fn dashboard_context() -> DashboardContext {
+
+        let statuses = get_all_alarm_statuses().await;
+        let start_times = get_all_alarm_start_times().await;
+        let snapshot = get_snapshot_info().await;
+
+        let mut regions: Vec<RegionRow> = get_all_cities()
+            .into_iter()
+            .map(|(location_uid, location_name)| {
+                let is_active_air_alarm = statuses.get(&location_uid).copied().unwrap_or(false);
+                build_region_row(location_uid, location_name, is_active_air_alarm, start_times.get(&location_uid))
+            })
+            .collect();
+        regions.sort_by_key(|region| region.location_uid);
+
+        DashboardContext {
+            active_count: regions.iter().filter(|region| region.is_active_air_alarm).count(),
+            regions,
+            last_modified: snapshot.last_modified,
+            last_poll: snapshot.last_poll.map(|value| format_time(&value)),
+            refresh_interval: REFRESH_INTERVAL,
+        }
+    }
+
+    /// Collects the data for the page of one region.
+    /// Returns None if the region is unknown.
+    pub async fn region_context(location_uid: i32) -> Option<RegionContext> {
+
+        let location_name = get_all_cities().remove(&location_uid)?;
+        let statuses = get_all_alarm_statuses().await;
+        let start_times = get_all_alarm_start_times().await;
+        let snapshot = get_snapshot_info().await;
+
+        let is_active_air_alarm = statuses.get(&location_uid).copied().unwrap_or(false);
+        let history = get_alarm_history(location_uid)
+            .await
+            .into_iter()
+            .map(|transition| HistoryRow {
+                is_active_air_alarm: transition.is_active_air_alarm,
+                changed_at: format_time(&transition.changed_at),
+            })
+            .collect();
+
+        Some(RegionContext {
+            region: build_region_row(location_uid, location_name, is_active_air_alarm, start_times.get(&location_uid)),
+            history,
+            last_poll: snapshot.last_poll.map(|value| format_time(&value)),
+            refresh_interval: REFRESH_INTERVAL,
+        })
+    }
+
+}
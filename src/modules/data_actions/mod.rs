@@ -3,86 +3,163 @@ pub mod data_actions {
 
     use std::collections::HashSet;
     use log::debug;
-    use crate::modules::fetch_data::fetch_data::get_alerts;
+    use serde::Deserialize;
+    use serde_json::Value;
+    use crate::modules::fetch_data::fetch_data::data_views::{AlertsResponseResult, Location};
+    use crate::modules::providers::providers::poll_sources;
     use crate::modules::other_data::other_data::get_all_cities;
+    use crate::modules::quarantine::quarantine::{quarantine, QuarantineReason};
+
+    /// Fields of the alert records of the alarm API
+    const KNOWN_FIELDS: [&str; 13] = [
+        "id", "location_title", "location_type", "started_at", "finished_at", "updated_at", "alert_type",
+        "location_uid", "location_oblast", "location_oblast_uid", "location_raion", "notes", "calculated",
+    ];
+
+    /// Alert types documented by the alarm API
+    const KNOWN_ALERT_TYPES: [&str; 5] = ["air_raid", "artillery_shelling", "urban_fights", "chemical", "nuclear"];
 
 
     /// Struct fo representing info about of the alarm data
     #[derive(Debug, Clone)]
     pub struct CurrentAlarm {
+        pub _id: i32,
         pub _location_uid: String,
         pub _location_oblast_uid: i16,
         pub _location_title: String,
         pub _location_oblast: String,
+        pub _location_type: String,
+        pub _alert_type: String,
         pub _started_at: String,
     }
 
-    /// A function that takes as input an AlertsResponseResult structure
-    /// that contains active alarms and metadata,
-    /// and a string that stores the value received
-    /// from the "last-modified" header.
+    /// Metadata of the alarm API sent together with the alerts
+    #[derive(Debug, Clone, Default)]
+    pub struct UpstreamMeta {
+        /// Name of the source of the alarm data
+        pub source: String,
+        pub last_updated_at: Option<String>,
+        pub meta_type: Option<String>,
+        pub disclaimer: Option<String>,
+    }
+
+    impl UpstreamMeta {
+        pub fn new(source: &str, response: &AlertsResponseResult) -> Self {
+            UpstreamMeta {
+                source: source.to_string(),
+                last_updated_at: response.meta.as_ref().and_then(|meta| meta.last_updated_at.clone()),
+                meta_type: response.meta.as_ref().and_then(|meta| meta.meta_type.clone()),
+                disclaimer: response.disclaimer.clone(),
+            }
+        }
+    }
+
+    /// Checks a record of the alarm API and converts it to the CurrentAlarm structure.
+    /// Records that can not be used are returned as an error with the reason,
+    /// records with unknown fields are used and returned with the reason as well.
+    fn check_record(record: &Value, city_ids: &HashSet<i32>) -> Result<(CurrentAlarm, Option<QuarantineReason>), QuarantineReason> {
+
+        let location = Location::deserialize(record).map_err(|e| QuarantineReason::InvalidRecord(e.to_string()))?;
+
+        let location_uid = location.location_uid.ok_or(QuarantineReason::MissingField("location_uid"))?;
+        let location_oblast_uid = location.location_oblast_uid.ok_or(QuarantineReason::MissingField("location_oblast_uid"))?;
+
+        if !KNOWN_ALERT_TYPES.contains(&location.alert_type.as_str()) {
+            return Err(QuarantineReason::UnknownAlertType(location.alert_type));
+        }
+
+        /*
+            Checking whether the ID is in the general list of available locations.
+            (Periodically, the API passes IDs of locations and regions
+            that have not yet been presented in their official documentation).
+        */
+        if !city_ids.contains(&(location_oblast_uid as i32)) {
+            return Err(QuarantineReason::UnknownRegion(location_oblast_uid.into()));
+        }
+
+        let unexpected_fields: Vec<String> = record
+            .as_object()
+            .map(|fields| fields.keys().filter(|field| !KNOWN_FIELDS.contains(&field.as_str())).cloned().collect())
+            .unwrap_or_default();
+
+        let compact_data = CurrentAlarm {
+            _id: location.id,
+            _location_uid: location_uid,
+            _location_oblast_uid: location_oblast_uid,
+            _location_title: location.location_title,
+            _location_type: location.location_type,
+            _alert_type: location.alert_type,
+            _started_at: location.started_at,
+            _location_oblast: location.location_oblast.unwrap_or_default(),
+        };
+
+        let drift = match unexpected_fields.is_empty() {
+            true => None,
+            false => Some(QuarantineReason::UnexpectedFields(unexpected_fields)),
+        };
+        Ok((compact_data, drift))
+    }
+
+    /// A function that polls the sources of the alarm data
+    /// and deserializes the AlertsResponseResult structure
+    /// that contains active alarms and metadata.
     /// The function returns a vector of CurrentAlarm structures
-    /// that represent the deserialized JSON
-    /// and the value from the "last-modified" header.
-    pub async fn deserialize_current_alarms_data(updated_at: String) -> Result<(Vec<CurrentAlarm>, String), String> {
+    /// that represent the deserialized JSON,
+    /// the value from the "last-modified" header
+    /// and the metadata of the response.
+    pub async fn deserialize_current_alarms_data() -> Result<(Vec<CurrentAlarm>, String, UpstreamMeta), String> {
 
         //Getting current alarm's data
-        let current_alarms_data = get_alerts(updated_at.clone()).await;
+        let current_alarms_data = poll_sources().await;
 
         //Getting IDs of all regions
         let city_ids: HashSet<i32> = get_all_cities().keys().cloned().collect();
-        let result_data: (Vec<CurrentAlarm>, String);
+        let result_data: (Vec<CurrentAlarm>, String, UpstreamMeta);
 
-        let result: Result<(Vec<CurrentAlarm>, String), String> = match current_alarms_data {
+        let result: Result<(Vec<CurrentAlarm>, String, UpstreamMeta), String> = match current_alarms_data {
             Ok(value) => {
 
                 debug!("{:?} - the current alarm data is available", chrono::Local::now());
 
-                let (alarms, header) = (value.0.alerts, value.1);
+                let upstream_meta = UpstreamMeta::new(&value.source, &value.response);
+                let (alarms, header) = (value.response.alerts, value.last_modified);
                 let deserialized_alarms = {
 
                     let mut collected_data = Vec::<CurrentAlarm>::new();
+                    let mut quarantined = Vec::<(Value, QuarantineReason)>::new();
                     debug!("{:?} - starting deserializing", chrono::Local::now());
 
                     for v in alarms { 
 
                         /*
-                            Sorting data by alarm type and checking whether
-                            the ID passed to the function is in the general list
-                            of available locations.
-                            (Periodically, the API passes IDs of locations and regions
-                            that have not yet been presented in their official documentation).
+                            If the check is successful, the data is generated
+                            in the CurrentAlarm structure.
+                            Records with problems go to the quarantine instead of stopping the poller.
                         */
-
-                        if (v.alert_type == String::from("air_raid")) && city_ids.contains(&(v.location_oblast_uid.unwrap() as i32)) {
-
-                            /*
-                                If the check is successful, the data is generated
-                                in the CurrentAlarm structure.
-                            */
-                            let compact_data = CurrentAlarm{
-                                _location_uid: v.location_uid.unwrap(),
-                                _location_oblast_uid: v.location_oblast_uid.unwrap(),
-                                _location_title: v.location_title,
-                                _started_at: v.started_at,
-                                _location_oblast: v.location_oblast.unwrap(),
-                            };
-
-                            debug!("{:?} - deserialized location data:\n{:?}", chrono::Local::now(), compact_data);
-                            collected_data.push(compact_data);
-
-                        }else{
-
-                            debug!("{:?} - not deserialized location data:\n{:?}", chrono::Local::now(), v);
+                        match check_record(&v, &city_ids) {
+                            Ok((compact_data, drift)) => {
+
+                                debug!("{:?} - deserialized location data:\n{:?}", chrono::Local::now(), compact_data);
+                                collected_data.push(compact_data);
+                                if let Some(reason) = drift {
+                                    quarantined.push((v, reason));
+                                }
+                            }
+                            Err(reason) => {
+
+                                debug!("{:?} - not deserialized location data ({}):\n{:?}", chrono::Local::now(), reason, v);
+                                quarantined.push((v, reason));
+                            }
                         }
 
                     }
+                    quarantine(quarantined).await;
                     collected_data
 
                 };
 
                 //Returning the tuple
-                result_data = (deserialized_alarms, header);
+                result_data = (deserialized_alarms, header, upstream_meta);
                 Ok(result_data)
 
             },
@@ -90,14 +167,11 @@ pub mod data_actions {
 
                 debug!("{:?} - deserialize data error\n{:?}", chrono::Local::now(), e);
 
-                let err = String::from(format!("{:?} - deserialize data error {:?}", chrono::Local::now(), e));
-                Err(err.into())
+                let err = format!("{:?} - deserialize data error {:?}", chrono::Local::now(), e);
+                Err(err)
             }
         };
 
-        return result
-
-
-
+        result
     }
 }
\ No newline at end of file
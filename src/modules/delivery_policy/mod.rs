@@ -0,0 +1,369 @@
+pub mod delivery_policy {
+
+    use std::collections::HashMap;
+    use std::hash::Hash;
+    use std::time::Duration;
+    use chrono::NaiveTime;
+    use tokio::time::Instant;
+
+    use crate::modules::diff::diff::{AlarmEvent, AlarmEventKind, AIR_RAID};
+
+    /// Daily period when the events of the non-critical alert types are not delivered.
+    /// The period may pass midnight, for example 23:00-07:00.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct QuietHours {
+        pub start: NaiveTime,
+        pub end: NaiveTime,
+    }
+
+    impl QuietHours {
+
+        /// Parses a period in the "HH:MM-HH:MM" format
+        pub fn parse(value: &str) -> Option<Self> {
+            let (start, end) = value.trim().split_once('-')?;
+            Some(QuietHours {
+                start: NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?,
+                end: NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?,
+            })
+        }
+
+        pub fn contains(&self, time: NaiveTime) -> bool {
+            match self.start <= self.end {
+                true => self.start <= time && time < self.end,
+                false => time >= self.start || time < self.end,
+            }
+        }
+    }
+
+    impl std::fmt::Display for QuietHours {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+        }
+    }
+
+    /// Rules of the delivery of the events to one subscriber
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct DeliveryPolicy {
+        /// An alarm is announced only if it is still active after this time
+        pub debounce: Duration,
+        /// The end of an alarm is announced only if the alarm does not start again within this time,
+        /// otherwise the end and the new start are merged into the alarm that is already announced
+        pub flap_window: Duration,
+        /// Period when the events of the non-critical alert types are dropped
+        pub quiet_hours: Option<QuietHours>,
+    }
+
+    /// Event that passes through the delivery rules
+    pub trait PolicyEvent {
+
+        /// Alert of the event and whether the event starts or ends it.
+        /// An event that does not toggle the alert (a change of the scope) follows the pending toggle of the alert.
+        fn toggle(&self) -> (String, Option<bool>);
+
+        /// Critical events are delivered during the quiet hours
+        fn is_critical(&self) -> bool;
+    }
+
+    impl PolicyEvent for AlarmEvent {
+
+        fn toggle(&self) -> (String, Option<bool>) {
+            match &self.kind {
+                AlarmEventKind::Started { .. } => (AIR_RAID.to_string(), Some(true)),
+                AlarmEventKind::Ended => (AIR_RAID.to_string(), Some(false)),
+                AlarmEventKind::ScopeChanged { .. } => (AIR_RAID.to_string(), None),
+                AlarmEventKind::TypeAdded { alert_type } => (alert_type.clone(), Some(true)),
+                AlarmEventKind::TypeRemoved { alert_type } => (alert_type.clone(), Some(false)),
+            }
+        }
+
+        /// All events of the air raid alert are critical, both its start and its end,
+        /// the events of the other alert types are not
+        fn is_critical(&self) -> bool {
+            self.toggle().0 == AIR_RAID
+        }
+    }
+
+    /// State of the events of one alert of a key (for example, a region of a subscriber)
+    struct KeyState<T> {
+        policy: DeliveryPolicy,
+        /// The last delivered state
+        announced_active: bool,
+        /// Event waiting for the end of the debounce or flap window
+        pending: Option<(T, Instant)>,
+        /// Events without a toggle that came after the pending one, they are delivered or dropped with it
+        followers: Vec<T>,
+    }
+
+    /// Applies the delivery rules to a flow of events.
+    ///
+    /// The filter does not use timers itself: the caller pushes the events,
+    /// waits until next_deadline and takes the events that are ready with take_ready.
+    pub struct PolicyFilter<K, T> {
+        states: HashMap<(K, String), KeyState<T>>,
+        /// Events without a toggle and without a pending toggle, with the time they were pushed
+        immediate: Vec<(K, T, DeliveryPolicy, Instant)>,
+    }
+
+    impl<K: Hash + Eq + Clone, T: PolicyEvent> Default for PolicyFilter<K, T> {
+        fn default() -> Self {
+            PolicyFilter { states: HashMap::new(), immediate: Vec::new() }
+        }
+    }
+
+    impl<K: Hash + Eq + Clone, T: PolicyEvent> PolicyFilter<K, T> {
+
+        /// Adds an event of the key.
+        /// An event opposite to the pending one of the same alert cancels both: the toggle is merged.
+        pub fn push(&mut self, key: K, event: T, policy: DeliveryPolicy, now: Instant) {
+
+            let (topic, toggle) = event.toggle();
+            let state_key = (key, topic);
+
+            let Some(is_active) = toggle else {
+                match self.states.get_mut(&state_key) {
+                    Some(state) if state.pending.is_some() => state.followers.push(event),
+                    _ => self.immediate.push((state_key.0, event, policy, now)),
+                }
+                return;
+            };
+
+            let state = self.states.entry(state_key).or_insert(KeyState { policy, announced_active: false, pending: None, followers: Vec::new() });
+            state.policy = policy;
+
+            if let Some((pending, _)) = &state.pending {
+                if pending.toggle().1 != Some(is_active) {
+                    state.pending = None;
+                    state.followers.clear();
+                    return;
+                }
+            }
+            if state.pending.is_none() && state.announced_active == is_active {
+                return;
+            }
+
+            let delay = match is_active {
+                true => policy.debounce,
+                false => policy.flap_window,
+            };
+            // A repeated event keeps the deadline of the pending one
+            let deadline = match &state.pending {
+                Some((_, deadline)) => *deadline,
+                None => now + delay,
+            };
+            state.pending = Some((event, deadline));
+        }
+
+        /// Time when the next pending event becomes ready
+        pub fn next_deadline(&self) -> Option<Instant> {
+            self.states
+                .values()
+                .filter_map(|state| state.pending.as_ref().map(|(_, deadline)| *deadline))
+                .chain(self.immediate.iter().map(|(_, _, _, pushed_at)| *pushed_at))
+                .min()
+        }
+
+        /// Takes the events whose waiting time is over.
+        /// Non-critical events are dropped during the quiet hours.
+        pub fn take_ready(&mut self, now: Instant, local_time: NaiveTime) -> Vec<(K, T)> {
+
+            let is_quiet = |policy: &DeliveryPolicy| policy.quiet_hours.is_some_and(|quiet_hours| quiet_hours.contains(local_time));
+
+            let mut result = Vec::new();
+            for (key, event, policy, _) in self.immediate.drain(..) {
+                if event.is_critical() || !is_quiet(&policy) {
+                    result.push((key, event));
+                }
+            }
+
+            for ((key, _), state) in self.states.iter_mut() {
+
+                let is_ready = matches!(&state.pending, Some((_, deadline)) if *deadline <= now);
+                if !is_ready {
+                    continue;
+                }
+
+                let (event, _) = state.pending.take().unwrap();
+                state.announced_active = event.toggle().1 == Some(true);
+
+                for event in std::iter::once(event).chain(state.followers.drain(..)) {
+                    if event.is_critical() || !is_quiet(&state.policy) {
+                        result.push((key.clone(), event));
+                    }
+                }
+            }
+
+            // Keys without an active alarm and pending events have no state to keep
+            self.states.retain(|_, state| state.announced_active || state.pending.is_some());
+            result
+        }
+    }
+
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::modules::processing::processing::AlarmScope;
+
+        const MINUTE: Duration = Duration::from_secs(60);
+
+        fn event(kind: AlarmEventKind) -> AlarmEvent {
+            AlarmEvent {
+                alarm_active: !matches!(kind, AlarmEventKind::Ended),
+                kind,
+                region_id: 9,
+                region_name: "Дніпропетровська область".to_string(),
+                changed_at: "2026-10-19T05:00:00Z".to_string(),
+                detected_at: "2026-10-19T05:00:10Z".to_string(),
+                upstream_ids: vec![1],
+            }
+        }
+
+        fn started() -> AlarmEvent {
+            event(AlarmEventKind::Started { scope: AlarmScope::Partial })
+        }
+
+        fn ended() -> AlarmEvent {
+            event(AlarmEventKind::Ended)
+        }
+
+        fn scope_changed() -> AlarmEvent {
+            event(AlarmEventKind::ScopeChanged { previous_scope: AlarmScope::Partial, scope: AlarmScope::Full })
+        }
+
+        fn artillery(is_added: bool) -> AlarmEvent {
+            let alert_type = "artillery_shelling".to_string();
+            match is_added {
+                true => event(AlarmEventKind::TypeAdded { alert_type }),
+                false => event(AlarmEventKind::TypeRemoved { alert_type }),
+            }
+        }
+
+        fn time(value: &str) -> NaiveTime {
+            NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+        }
+
+        fn policy(debounce: Duration, flap_window: Duration, quiet_hours: Option<&str>) -> DeliveryPolicy {
+            DeliveryPolicy { debounce, flap_window, quiet_hours: quiet_hours.and_then(QuietHours::parse) }
+        }
+
+        fn kinds(events: Vec<(i32, AlarmEvent)>) -> Vec<AlarmEventKind> {
+            events.into_iter().map(|(_, event)| event.kind).collect()
+        }
+
+        #[test]
+        fn alarm_is_announced_only_after_the_debounce() {
+
+            let policy = policy(MINUTE, Duration::ZERO, None);
+            let mut filter = PolicyFilter::default();
+            let start = Instant::now();
+
+            filter.push(9, started(), policy, start);
+            assert_eq!(filter.next_deadline(), Some(start + MINUTE));
+            assert!(filter.take_ready(start + MINUTE / 2, time("12:00")).is_empty());
+            assert_eq!(kinds(filter.take_ready(start + MINUTE, time("12:00"))), vec![started().kind]);
+            assert_eq!(filter.next_deadline(), None);
+        }
+
+        #[test]
+        fn alarm_shorter_than_the_debounce_is_not_announced() {
+
+            let policy = policy(MINUTE, Duration::ZERO, None);
+            let mut filter = PolicyFilter::default();
+            let start = Instant::now();
+
+            filter.push(9, started(), policy, start);
+            filter.push(9, ended(), policy, start + MINUTE / 2);
+            assert_eq!(filter.next_deadline(), None);
+            assert!(filter.take_ready(start + MINUTE * 2, time("12:00")).is_empty());
+        }
+
+        #[test]
+        fn flap_within_the_window_is_merged() {
+
+            let policy = policy(Duration::ZERO, MINUTE, None);
+            let mut filter = PolicyFilter::default();
+            let start = Instant::now();
+
+            filter.push(9, started(), policy, start);
+            assert_eq!(kinds(filter.take_ready(start, time("12:00"))), vec![started().kind]);
+
+            // The alarm starts again before the window is over: nothing is announced
+            filter.push(9, ended(), policy, start + MINUTE);
+            filter.push(9, started(), policy, start + MINUTE * 3 / 2);
+            assert!(filter.take_ready(start + MINUTE * 3, time("12:00")).is_empty());
+
+            // The end after the window is announced
+            filter.push(9, ended(), policy, start + MINUTE * 4);
+            assert!(filter.take_ready(start + MINUTE * 4, time("12:00")).is_empty());
+            assert_eq!(kinds(filter.take_ready(start + MINUTE * 5, time("12:00"))), vec![ended().kind]);
+        }
+
+        #[test]
+        fn scope_change_follows_the_pending_start() {
+
+            let policy = policy(MINUTE, Duration::ZERO, None);
+            let mut filter = PolicyFilter::default();
+            let start = Instant::now();
+
+            filter.push(9, started(), policy, start);
+            filter.push(9, scope_changed(), policy, start + MINUTE / 2);
+            assert!(filter.take_ready(start + MINUTE / 2, time("12:00")).is_empty());
+            assert_eq!(kinds(filter.take_ready(start + MINUTE, time("12:00"))), vec![started().kind, scope_changed().kind]);
+
+            // A change of the scope of an announced alarm is not delayed
+            filter.push(9, scope_changed(), policy, start + MINUTE * 2);
+            assert_eq!(kinds(filter.take_ready(start + MINUTE * 2, time("12:00"))), vec![scope_changed().kind]);
+        }
+
+        #[test]
+        fn scope_change_of_a_cancelled_alarm_is_dropped() {
+
+            let policy = policy(MINUTE, Duration::ZERO, None);
+            let mut filter = PolicyFilter::default();
+            let start = Instant::now();
+
+            filter.push(9, started(), policy, start);
+            filter.push(9, scope_changed(), policy, start + MINUTE / 4);
+            filter.push(9, ended(), policy, start + MINUTE / 2);
+            assert!(filter.take_ready(start + MINUTE * 2, time("12:00")).is_empty());
+        }
+
+        #[test]
+        fn quiet_hours_drop_only_the_other_alert_types() {
+
+            let policy = policy(Duration::ZERO, Duration::ZERO, Some("23:00-07:00"));
+            let mut filter = PolicyFilter::default();
+            let start = Instant::now();
+
+            for event in [started(), artillery(true), scope_changed(), ended(), artillery(false)] {
+                filter.push(9, event, policy, start);
+                filter.take_ready(start, time("12:00"));
+            }
+
+            let mut quiet = Vec::new();
+            for event in [started(), artillery(true), scope_changed(), ended(), artillery(false)] {
+                filter.push(9, event, policy, start);
+                quiet.extend(kinds(filter.take_ready(start, time("02:00"))));
+            }
+            assert_eq!(quiet, vec![started().kind, scope_changed().kind, ended().kind]);
+        }
+
+        #[test]
+        fn quiet_hours_may_pass_midnight() {
+
+            let night = QuietHours::parse("23:00-07:00").unwrap();
+            assert!(night.contains(time("23:30")));
+            assert!(night.contains(time("06:59")));
+            assert!(!night.contains(time("07:00")));
+            assert!(!night.contains(time("12:00")));
+
+            let day = QuietHours::parse(" 13:00 - 14:00 ").unwrap();
+            assert!(day.contains(time("13:00")));
+            assert!(!day.contains(time("14:00")));
+            assert_eq!(day.to_string(), "13:00-14:00");
+            assert!(QuietHours::parse("23:00").is_none());
+        }
+    }
+
+}
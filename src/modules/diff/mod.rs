@@ -0,0 +1,384 @@
+pub mod diff {
+
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use serde::{Deserialize, Serialize};
+
+    use crate::modules::processing::processing::AlarmScope;
+
+    /// Type of the alerts that make the air alarm of a region
+    pub const AIR_RAID: &str = "air_raid";
+
+    /// One active alert of the alarm API
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct AlertRecord {
+        /// Identifier of the alert in the alarm API.
+        /// None for the alerts restored from the alarm state saved before the snapshots were kept.
+        pub id: Option<i32>,
+        pub region_id: i32,
+        pub location_uid: String,
+        pub location_type: String,
+        pub alert_type: String,
+        pub started_at: String,
+    }
+
+    impl AlertRecord {
+
+        /// Whether the alert is declared for the whole region and not for a part of it
+        pub fn covers_region(&self) -> bool {
+            self.location_type == "oblast" || self.location_uid == self.region_id.to_string()
+        }
+    }
+
+    /// All alerts that were active at one moment
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    pub struct AlarmSnapshot {
+        pub alerts: Vec<AlertRecord>,
+    }
+
+    /// Air alarm of a region derived from a snapshot
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RegionAlarm {
+        pub started_at: String,
+        pub scope: AlarmScope,
+    }
+
+    impl AlarmSnapshot {
+
+        /// Alerts of a region grouped by type
+        fn region_alerts(&self) -> BTreeMap<i32, BTreeMap<&str, Vec<&AlertRecord>>> {
+            let mut result: BTreeMap<i32, BTreeMap<&str, Vec<&AlertRecord>>> = BTreeMap::new();
+            for alert in &self.alerts {
+                result
+                    .entry(alert.region_id)
+                    .or_default()
+                    .entry(alert.alert_type.as_str())
+                    .or_default()
+                    .push(alert);
+            }
+            result
+        }
+
+        /// Air alarms of all regions with the earliest start time and the scope of the alarm
+        pub fn region_alarms(&self) -> HashMap<i32, RegionAlarm> {
+            self.region_alerts()
+                .into_iter()
+                .filter_map(|(region_id, types)| {
+                    let alerts = types.get(AIR_RAID)?;
+                    Some((region_id, RegionAlarm { started_at: earliest_start(alerts), scope: scope(alerts) }))
+                })
+                .collect()
+        }
+    }
+
+    /// Change of the alerts of a region
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    #[serde(tag = "event", rename_all = "snake_case")]
+    pub enum AlarmEventKind {
+        /// The air alarm started in the region
+        #[serde(rename = "alarm_started")]
+        Started { scope: AlarmScope },
+        /// The air alarm ended in the region
+        #[serde(rename = "alarm_ended")]
+        Ended,
+        /// An alert of another type (artillery shelling, urban fights...) started in the region
+        TypeAdded { alert_type: String },
+        /// All alerts of another type ended in the region
+        TypeRemoved { alert_type: String },
+        /// The air alarm moved from the whole region to some of its raions or back
+        ScopeChanged { previous_scope: AlarmScope, scope: AlarmScope },
+    }
+
+    impl AlarmEventKind {
+
+        /// Name of the event in the messages and metrics
+        pub fn name(&self) -> &'static str {
+            match self {
+                AlarmEventKind::Started { .. } => "alarm_started",
+                AlarmEventKind::Ended => "alarm_ended",
+                AlarmEventKind::TypeAdded { .. } => "type_added",
+                AlarmEventKind::TypeRemoved { .. } => "type_removed",
+                AlarmEventKind::ScopeChanged { .. } => "scope_changed",
+            }
+        }
+    }
+
+    /// Typed change of the alarm state of a region between two snapshots
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct AlarmEvent {
+        #[serde(flatten)]
+        pub kind: AlarmEventKind,
+        pub region_id: i32,
+        pub region_name: String,
+        /// Whether the air alarm of the region is active after the event
+        pub alarm_active: bool,
+        /// Start time of the alerts for the started events, detection time for the others
+        pub changed_at: String,
+        /// Time when the change was found by the poller
+        pub detected_at: String,
+        /// Identifiers of the alerts of the alarm API that caused the event
+        pub upstream_ids: Vec<i32>,
+    }
+
+    impl AlarmEvent {
+
+        /// One line description of the event for the text channels
+        pub fn summary(&self) -> String {
+            match &self.kind {
+                AlarmEventKind::Started { .. } => format!("Air raid alert started: {} ({})", self.region_name, self.changed_at),
+                AlarmEventKind::Ended => format!("Air raid alert ended: {} ({})", self.region_name, self.changed_at),
+                AlarmEventKind::TypeAdded { alert_type } => format!("Alert '{}' started: {} ({})", alert_type, self.region_name, self.changed_at),
+                AlarmEventKind::TypeRemoved { alert_type } => format!("Alert '{}' ended: {} ({})", alert_type, self.region_name, self.changed_at),
+                AlarmEventKind::ScopeChanged { scope, .. } => format!("Air raid alert in {} is now {} ({})", self.region_name, scope.as_str(), self.changed_at),
+            }
+        }
+    }
+
+    fn earliest_start(alerts: &[&AlertRecord]) -> String {
+        alerts.iter().map(|alert| alert.started_at.clone()).min().unwrap_or_default()
+    }
+
+    fn scope(alerts: &[&AlertRecord]) -> AlarmScope {
+        match alerts.iter().any(|alert| alert.covers_region()) {
+            true => AlarmScope::Full,
+            false => AlarmScope::Partial,
+        }
+    }
+
+    fn upstream_ids(alerts: &[&AlertRecord]) -> Vec<i32> {
+        let ids: BTreeSet<i32> = alerts.iter().filter_map(|alert| alert.id).collect();
+        ids.into_iter().collect()
+    }
+
+    /// Finds the changes between two snapshots.
+    ///
+    /// The air raid alerts make the alarm of a region (Started, Ended, ScopeChanged),
+    /// the alerts of other types are reported with TypeAdded and TypeRemoved.
+    /// The events are ordered by the region identifier, the events of a region keep the order of the variants.
+    pub fn diff(previous: &AlarmSnapshot, next: &AlarmSnapshot, region_names: &HashMap<i32, String>, detected_at: &str) -> Vec<AlarmEvent> {
+
+        let previous_alerts = previous.region_alerts();
+        let next_alerts = next.region_alerts();
+        let empty = BTreeMap::new();
+
+        let region_ids: BTreeSet<i32> = previous_alerts.keys().chain(next_alerts.keys()).cloned().collect();
+        let mut events = Vec::new();
+
+        for region_id in region_ids {
+
+            let before = previous_alerts.get(&region_id).unwrap_or(&empty);
+            let after = next_alerts.get(&region_id).unwrap_or(&empty);
+            let alarm_active = after.contains_key(AIR_RAID);
+
+            let event = |kind: AlarmEventKind, changed_at: String, alerts: &[&AlertRecord]| AlarmEvent {
+                kind,
+                region_id,
+                region_name: region_names.get(&region_id).cloned().unwrap_or_default(),
+                alarm_active,
+                changed_at,
+                detected_at: detected_at.to_string(),
+                upstream_ids: upstream_ids(alerts),
+            };
+
+            match (before.get(AIR_RAID), after.get(AIR_RAID)) {
+                (None, Some(alerts)) => {
+                    events.push(event(AlarmEventKind::Started { scope: scope(alerts) }, earliest_start(alerts), alerts));
+                }
+                (Some(alerts), None) => {
+                    events.push(event(AlarmEventKind::Ended, detected_at.to_string(), alerts));
+                }
+                (Some(previous_alerts), Some(alerts)) if scope(previous_alerts) != scope(alerts) => {
+                    let kind = AlarmEventKind::ScopeChanged { previous_scope: scope(previous_alerts), scope: scope(alerts) };
+                    events.push(event(kind, detected_at.to_string(), alerts));
+                }
+                _ => {}
+            }
+
+            for (alert_type, alerts) in after.iter().filter(|(alert_type, _)| **alert_type != AIR_RAID) {
+                if !before.contains_key(alert_type) {
+                    let kind = AlarmEventKind::TypeAdded { alert_type: alert_type.to_string() };
+                    events.push(event(kind, earliest_start(alerts), alerts));
+                }
+            }
+            for (alert_type, alerts) in before.iter().filter(|(alert_type, _)| **alert_type != AIR_RAID) {
+                if !after.contains_key(alert_type) {
+                    let kind = AlarmEventKind::TypeRemoved { alert_type: alert_type.to_string() };
+                    events.push(event(kind, detected_at.to_string(), alerts));
+                }
+            }
+        }
+
+        events
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        const DETECTED_AT: &str = "2026-10-19T06:00:00+00:00";
+
+        fn alert(id: i32, region_id: i32, location_uid: &str, location_type: &str, alert_type: &str, started_at: &str) -> AlertRecord {
+            AlertRecord {
+                id: Some(id),
+                region_id,
+                location_uid: location_uid.to_string(),
+                location_type: location_type.to_string(),
+                alert_type: alert_type.to_string(),
+                started_at: started_at.to_string(),
+            }
+        }
+
+        fn oblast_alert(id: i32, region_id: i32, alert_type: &str) -> AlertRecord {
+            alert(id, region_id, &region_id.to_string(), "oblast", alert_type, "2026-10-19T05:00:00Z")
+        }
+
+        fn raion_alert(id: i32, region_id: i32, alert_type: &str) -> AlertRecord {
+            alert(id, region_id, "555", "raion", alert_type, "2026-10-19T05:30:00Z")
+        }
+
+        fn snapshot(alerts: Vec<AlertRecord>) -> AlarmSnapshot {
+            AlarmSnapshot { alerts }
+        }
+
+        fn names() -> HashMap<i32, String> {
+            HashMap::from([(9, "Дніпропетровська область".to_string()), (14, "Київська область".to_string())])
+        }
+
+        fn kinds(events: &[AlarmEvent]) -> Vec<(i32, AlarmEventKind)> {
+            events.iter().map(|event| (event.region_id, event.kind.clone())).collect()
+        }
+
+        #[test]
+        fn same_snapshots_have_no_events() {
+            let alerts = snapshot(vec![oblast_alert(1, 9, AIR_RAID), raion_alert(2, 14, "artillery_shelling")]);
+            assert!(diff(&alerts, &alerts.clone(), &names(), DETECTED_AT).is_empty());
+            assert!(diff(&AlarmSnapshot::default(), &AlarmSnapshot::default(), &names(), DETECTED_AT).is_empty());
+        }
+
+        #[test]
+        fn started_has_scope_start_time_and_upstream_ids() {
+            let next = snapshot(vec![raion_alert(7, 9, AIR_RAID), alert(3, 9, "556", "hromada", AIR_RAID, "2026-10-19T05:10:00Z")]);
+            let events = diff(&AlarmSnapshot::default(), &next, &names(), DETECTED_AT);
+
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].kind, AlarmEventKind::Started { scope: AlarmScope::Partial });
+            assert_eq!(events[0].region_name, "Дніпропетровська область");
+            assert!(events[0].alarm_active);
+            assert_eq!(events[0].changed_at, "2026-10-19T05:10:00Z");
+            assert_eq!(events[0].detected_at, DETECTED_AT);
+            assert_eq!(events[0].upstream_ids, vec![3, 7]);
+        }
+
+        #[test]
+        fn ended_uses_detection_time_and_previous_ids() {
+            let previous = snapshot(vec![oblast_alert(1, 9, AIR_RAID)]);
+            let events = diff(&previous, &AlarmSnapshot::default(), &names(), DETECTED_AT);
+
+            assert_eq!(kinds(&events), vec![(9, AlarmEventKind::Ended)]);
+            assert!(!events[0].alarm_active);
+            assert_eq!(events[0].changed_at, DETECTED_AT);
+            assert_eq!(events[0].upstream_ids, vec![1]);
+        }
+
+        #[test]
+        fn scope_changes_between_oblast_and_raion() {
+            let oblast = snapshot(vec![oblast_alert(1, 9, AIR_RAID)]);
+            let raion = snapshot(vec![raion_alert(2, 9, AIR_RAID)]);
+
+            let narrowed = diff(&oblast, &raion, &names(), DETECTED_AT);
+            assert_eq!(kinds(&narrowed), vec![(9, AlarmEventKind::ScopeChanged { previous_scope: AlarmScope::Full, scope: AlarmScope::Partial })]);
+            assert_eq!(narrowed[0].upstream_ids, vec![2]);
+            assert!(narrowed[0].alarm_active);
+
+            let widened = diff(&raion, &oblast, &names(), DETECTED_AT);
+            assert_eq!(kinds(&widened), vec![(9, AlarmEventKind::ScopeChanged { previous_scope: AlarmScope::Partial, scope: AlarmScope::Full })]);
+        }
+
+        #[test]
+        fn alert_for_the_city_of_the_region_covers_the_region() {
+            let kyiv = snapshot(vec![alert(5, 31, "31", "city", AIR_RAID, "2026-10-19T05:00:00Z")]);
+            let events = diff(&AlarmSnapshot::default(), &kyiv, &names(), DETECTED_AT);
+            assert_eq!(kinds(&events), vec![(31, AlarmEventKind::Started { scope: AlarmScope::Full })]);
+            assert_eq!(events[0].region_name, "");
+        }
+
+        #[test]
+        fn new_alerts_of_the_same_scope_do_not_change_the_alarm() {
+            let previous = snapshot(vec![raion_alert(1, 9, AIR_RAID)]);
+            let next = snapshot(vec![raion_alert(1, 9, AIR_RAID), raion_alert(2, 9, AIR_RAID)]);
+            assert!(diff(&previous, &next, &names(), DETECTED_AT).is_empty());
+        }
+
+        #[test]
+        fn other_types_are_added_and_removed() {
+            let previous = snapshot(vec![oblast_alert(1, 9, AIR_RAID), raion_alert(2, 9, "artillery_shelling")]);
+            let next = snapshot(vec![oblast_alert(1, 9, AIR_RAID), raion_alert(3, 9, "urban_fights")]);
+            let events = diff(&previous, &next, &names(), DETECTED_AT);
+
+            assert_eq!(kinds(&events), vec![
+                (9, AlarmEventKind::TypeAdded { alert_type: "urban_fights".to_string() }),
+                (9, AlarmEventKind::TypeRemoved { alert_type: "artillery_shelling".to_string() }),
+            ]);
+            assert_eq!(events[0].changed_at, "2026-10-19T05:30:00Z");
+            assert_eq!(events[0].upstream_ids, vec![3]);
+            assert_eq!(events[1].changed_at, DETECTED_AT);
+            assert_eq!(events[1].upstream_ids, vec![2]);
+            assert!(events.iter().all(|event| event.alarm_active));
+        }
+
+        #[test]
+        fn other_types_do_not_start_the_alarm() {
+            let next = snapshot(vec![raion_alert(4, 14, "artillery_shelling")]);
+            let events = diff(&AlarmSnapshot::default(), &next, &names(), DETECTED_AT);
+            assert_eq!(kinds(&events), vec![(14, AlarmEventKind::TypeAdded { alert_type: "artillery_shelling".to_string() })]);
+            assert!(!events[0].alarm_active);
+        }
+
+        #[test]
+        fn events_are_ordered_by_region_and_variant() {
+            let previous = snapshot(vec![oblast_alert(1, 14, AIR_RAID), raion_alert(2, 14, "chemical")]);
+            let next = snapshot(vec![oblast_alert(3, 9, AIR_RAID), raion_alert(4, 9, "nuclear")]);
+            let events = diff(&previous, &next, &names(), DETECTED_AT);
+
+            assert_eq!(kinds(&events), vec![
+                (9, AlarmEventKind::Started { scope: AlarmScope::Full }),
+                (9, AlarmEventKind::TypeAdded { alert_type: "nuclear".to_string() }),
+                (14, AlarmEventKind::Ended),
+                (14, AlarmEventKind::TypeRemoved { alert_type: "chemical".to_string() }),
+            ]);
+        }
+
+        #[test]
+        fn restored_alerts_have_no_upstream_ids() {
+            let mut restored = oblast_alert(0, 9, AIR_RAID);
+            restored.id = None;
+            let events = diff(&snapshot(vec![restored]), &AlarmSnapshot::default(), &names(), DETECTED_AT);
+            assert!(events[0].upstream_ids.is_empty());
+        }
+
+        #[test]
+        fn region_alarms_use_the_earliest_air_raid_alert() {
+            let alarms = snapshot(vec![
+                raion_alert(1, 9, AIR_RAID),
+                alert(2, 9, "9", "oblast", AIR_RAID, "2026-10-19T05:45:00Z"),
+                raion_alert(3, 14, "artillery_shelling"),
+            ]).region_alarms();
+
+            assert_eq!(alarms.len(), 1);
+            assert_eq!(alarms[&9], RegionAlarm { started_at: "2026-10-19T05:30:00Z".to_string(), scope: AlarmScope::Full });
+        }
+
+        #[test]
+        fn events_are_serialized_with_the_event_name() {
+            let event = diff(&AlarmSnapshot::default(), &snapshot(vec![oblast_alert(1, 9, AIR_RAID)]), &names(), DETECTED_AT).remove(0);
+            let value = serde_json::to_value(&event).unwrap();
+
+            assert_eq!(value["event"], "alarm_started");
+            assert_eq!(value["scope"], "full");
+            assert_eq!(value["region_id"], 9);
+            assert_eq!(value["upstream_ids"], serde_json::json!([1]));
+            assert_eq!(event.kind.name(), "alarm_started");
+        }
+    }
+
+}
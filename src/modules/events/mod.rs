@@ -0,0 +1,107 @@
+pub mod events {
+
+    use dotenv::dotenv;
+    use std::env;
+    use log::debug;
+
+    use crate::modules::{
+        diff::diff::AlarmEvent,
+        notifications::notifications::{Notifier, NotifyError},
+        store::store::store,
+    };
+
+    /// Maximum number of entries kept in the stream if ALARM_EVENTS_STREAM_MAXLEN is not set
+    const DEFAULT_STREAM_MAX_LEN: usize = 10000;
+
+    /// Where the alarm events are published.
+    /// An empty channel or stream name disables that kind of publication.
+    #[derive(Debug, Clone)]
+    pub struct EventsConfig {
+        pub channel: String,
+        pub stream: String,
+        pub stream_max_len: usize,
+        pub group: Option<String>,
+    }
+
+    ///Get the settings of the publication from the ALARM_EVENTS_* variables
+    pub fn get_events_config() -> EventsConfig {
+        dotenv().ok();
+        EventsConfig {
+            channel: env::var("ALARM_EVENTS_CHANNEL").unwrap_or_else(|_| "alarm_events".to_string()),
+            stream: env::var("ALARM_EVENTS_STREAM").unwrap_or_else(|_| "alarm_events:stream".to_string()),
+            stream_max_len: env::var("ALARM_EVENTS_STREAM_MAXLEN")
+                .map(|value| value.parse().expect("INCORRECT ALARM_EVENTS_STREAM_MAXLEN"))
+                .unwrap_or(DEFAULT_STREAM_MAX_LEN),
+            group: env::var("ALARM_EVENTS_GROUP").ok().filter(|value| !value.is_empty()),
+        }
+    }
+
+
+    /// Publishes the alarm events to the Redis channel and appends them to the Redis stream.
+    /// The stream entry has the event, region_id and changed_at fields for filtering
+    /// and the whole message as JSON in the payload field.
+    pub struct RedisEventsNotifier {
+        config: EventsConfig,
+    }
+
+    impl RedisEventsNotifier {
+
+        /// Creates the consumer group of the stream if ALARM_EVENTS_GROUP is set,
+        /// so the workers of the group receive every event published after the start of the poller.
+        /// Returns None if both the channel and the stream are disabled.
+        pub async fn init() -> Option<Self> {
+
+            let config = get_events_config();
+            if config.channel.is_empty() && config.stream.is_empty() {
+                return None;
+            }
+
+            if let (false, Some(group)) = (config.stream.is_empty(), &config.group) {
+                match store().xgroup_create(&config.stream, group).await {
+                    Ok(_) => debug!("{} - consumer group {} of stream {} is ready", chrono::Local::now(), group, config.stream),
+                    Err(e) => debug!("{} - error creating consumer group {}\n{}", chrono::Local::now(), group, e),
+                }
+            }
+            Some(RedisEventsNotifier { config })
+        }
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for RedisEventsNotifier {
+
+        fn name(&self) -> String {
+            "redis".to_string()
+        }
+
+        /// The consumers of the channel and the stream get every event, they apply their own rules
+        fn uses_delivery_policy(&self) -> bool {
+            false
+        }
+
+        async fn notify(&self, message: &AlarmEvent) -> Result<(), NotifyError> {
+
+            let config = &self.config;
+            let payload = serde_json::to_string(message).unwrap();
+
+            if !config.channel.is_empty() {
+                store().publish(&config.channel, &payload).await.map_err(|e| NotifyError(e.to_string()))?;
+            }
+
+            if !config.stream.is_empty() {
+                let fields = [
+                    ("event".to_string(), message.kind.name().to_string()),
+                    ("region_id".to_string(), message.region_id.to_string()),
+                    ("changed_at".to_string(), message.changed_at.clone()),
+                    ("payload".to_string(), payload),
+                ];
+                let id = store()
+                    .xadd_capped(&config.stream, &fields, config.stream_max_len)
+                    .await
+                    .map_err(|e| NotifyError(e.to_string()))?;
+                debug!("{} - alarm event added to stream {} with id {}", chrono::Local::now(), config.stream, id);
+            }
+            Ok(())
+        }
+    }
+
+}
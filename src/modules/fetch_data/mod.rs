@@ -3,95 +3,234 @@ pub mod fetch_data {
 
     use data_views::AlertsResponseResult;
     use dotenv::dotenv;
+    use std::collections::BTreeMap;
     use std::env;
+    use std::time::Duration;
+    use once_cell::sync::OnceCell;
     use reqwest::StatusCode;
+    use serde::{Deserialize, Serialize};
+
+    use crate::modules::{
+        providers::providers::StaticSource,
+        replay::replay::{Recorder, ReplaySource},
+    };
     
     // Getting a copy of the connection string to the alarm API
     fn get_alerts_api_connection_url() -> String {
         dotenv().ok();
     
         let token = env::var("TOKEN").expect("INCORRECT ALERT API TOKEN");
-        let url = format!(
+        format!(
                 "https://api.alerts.in.ua/v1/alerts/active.json?token={}",
                 token
-        );
-        url
+        )
     }
     
-    /// Function to get data, where :
-    ///
-    /// - last_modified - header from API response
-    ///     contains data when alarm data was last updated on the server
-    ///
-    /// - the return value is of type Result<(AlertsResponseResult, String)>
-    ///      the return value is a tuple of two elements:
-    ///         - struct AlertsResponseResult
-    ///         - String (the response header value "If-Modified-Since" is returned as a string)
-    ///
-    pub async fn get_alerts(last_modified: String) -> Result<(AlertsResponseResult, String), Box<dyn std::error::Error>> {
-
-        // Getting a copy of the connection string to the alarm API
-        let url = get_alerts_api_connection_url();
-
-        // Create a client to make an HTTP request
-        let client = reqwest::Client::new();
-
-        /* 
-            Sending request using the header "If-Modified-Since",
-            in order not to start data processing if there were
-            no changes on the server and there is no new data.
-        */
-        let response = client.get(&url)
-            .header("If-Modified-Since", last_modified.to_string())
-            .send()
-            .await?;
-
-        // Processing the query result
-        if response.status() == StatusCode::NOT_MODIFIED {
-            return Err("No new data available (304 Not Modified)".into());
+    /// Error of a source of the alarm data
+    pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+    /// Raw response of the alarm API
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RawResponse {
+        pub status: u16,
+        pub headers: BTreeMap<String, String>,
+        pub body: String,
+    }
+
+    impl RawResponse {
+
+        /// Deserializes the alerts and returns them with the value of the "last-modified" header
+        pub fn parse(&self) -> Result<(AlertsResponseResult, String), SourceError> {
+
+            // Processing the query result
+            if self.status == StatusCode::NOT_MODIFIED.as_u16() {
+                return Err("No new data available (304 Not Modified)".into());
+            }
+
+            //Deserializing data to AlertsResponseResult type
+            let result = serde_json::from_str::<AlertsResponseResult>(&self.body)?;
+
+            match self.headers.get("last-modified") {
+                Some(header) => Ok((result, header.to_string())),
+                None => Err("Failed to process the response headers".into()),
+            }
         }
+    }
 
-        // Getting all response headers
-        let updated_at = response.headers().clone();
+    /// Pause of the poller between the requests to an HTTP source
+    pub const HTTP_POLL_INTERVAL: Duration = Duration::from_secs(60 / 9);
 
-        //Deserializing data to AlertsResponseResult type
-        let result = response.json::<AlertsResponseResult>().await?;
+    /// Time limit of a request to an HTTP source
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
-        if let Some(header_value) = updated_at.get("last-modified") {
+    /// Source of the alarm data: the alarm API, another provider or the recorded responses of the alarm API.
+    /// Every source returns the responses in the format of the alarm API.
+    #[rocket::async_trait]
+    pub trait AlertSource: Send + Sync {
 
-            // Getting the "last-modified" header value
-            if let Ok(header) = header_value.to_str() {
+        /// Name of the source in the health reports
+        fn name(&self) -> &str;
 
-                // Returning the result of a method as a tuple
-                return Ok((result, header.to_string()));
+        /// Returns the response that follows the response with the last_modified header
+        async fn fetch(&self, last_modified: &str) -> Result<RawResponse, SourceError>;
+
+        /// Pause of the poller between the requests
+        fn poll_interval(&self) -> Duration;
+    }
+
+    /// The alarm API or a mirror with the same format of the responses
+    pub struct HttpSource {
+        name: &'static str,
+        client: reqwest::Client,
+        url: String,
+        recorder: Option<Recorder>,
+    }
+
+    impl HttpSource {
+
+        fn new(name: &'static str, url: String, recorder: Option<Recorder>) -> Self {
+            HttpSource {
+                name,
+                client: reqwest::Client::builder()
+                    .timeout(REQUEST_TIMEOUT)
+                    .build()
+                    .expect("INCORRECT HTTP CLIENT SETTINGS"),
+                url,
+                recorder,
             }
         }
-        Err("Failed to process the response headers".into())
 
+        /// Creates the source from the TOKEN variable.
+        /// The responses are recorded if RECORD_DIR is set.
+        pub fn from_env() -> Self {
+            HttpSource::new("alerts_in_ua", get_alerts_api_connection_url(), Recorder::from_env())
+        }
+
+        /// Creates the mirror of the alarm API from the MIRROR_URL variable
+        pub fn mirror_from_env() -> Self {
+            dotenv().ok();
+            HttpSource::new("mirror", env::var("MIRROR_URL").expect("INCORRECT MIRROR_URL"), None)
+        }
+    }
 
+    #[rocket::async_trait]
+    impl AlertSource for HttpSource {
 
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn fetch(&self, last_modified: &str) -> Result<RawResponse, SourceError> {
+
+            /* 
+                Sending request using the header "If-Modified-Since",
+                in order not to start data processing if there were
+                no changes on the server and there is no new data.
+            */
+            let response = self.client.get(&self.url)
+                .header("If-Modified-Since", last_modified)
+                .send()
+                .await?;
+
+            // Getting all response headers
+            let headers = response
+                .headers()
+                .iter()
+                .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string()))
+                .collect();
+
+            let raw = RawResponse {
+                status: response.status().as_u16(),
+                headers,
+                body: response.text().await?,
+            };
+
+            if let Some(recorder) = &self.recorder {
+                recorder.record(&raw).await;
+            }
+            Ok(raw)
+        }
+
+        fn poll_interval(&self) -> Duration {
+            HTTP_POLL_INTERVAL
+        }
+    }
+
+    /// Creates the sources listed in ALERT_SOURCES in the order of their priority:
+    /// alerts_in_ua (the alarm API, default), mirror (MIRROR_URL) and static (STATIC_FEED_PATH)
+    fn sources_from_env() -> Vec<Box<dyn AlertSource>> {
+        dotenv().ok();
+        env::var("ALERT_SOURCES")
+            .unwrap_or_else(|_| "alerts_in_ua".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| -> Box<dyn AlertSource> {
+                match name {
+                    "alerts_in_ua" => Box::new(HttpSource::from_env()),
+                    "mirror" => Box::new(HttpSource::mirror_from_env()),
+                    "static" => Box::new(StaticSource::from_env()),
+                    _ => panic!("INCORRECT ALERT_SOURCES, UNKNOWN SOURCE {}", name),
+                }
+            })
+            .collect()
     }
 
+    static SOURCES: OnceCell<Vec<Box<dyn AlertSource>>> = OnceCell::new();
+
+    /// Returns the sources of the alarm data in the order of their priority:
+    /// the recorded responses if REPLAY_DIR is set, the sources of ALERT_SOURCES otherwise.
+    pub fn alert_sources() -> &'static [Box<dyn AlertSource>] {
+        SOURCES.get_or_init(|| match ReplaySource::from_env() {
+            Some(source) => vec![Box::new(source)],
+            None => {
+                let sources = sources_from_env();
+                assert!(!sources.is_empty(), "INCORRECT ALERT_SOURCES, NO SOURCES");
+                sources
+            }
+        })
+    }
+
+    /// Replaces the sources of the alarm data before their first use.
+    /// Returns false if the sources are already initialized.
+    pub fn set_alert_sources(sources: Vec<Box<dyn AlertSource>>) -> bool {
+        !sources.is_empty() && SOURCES.set(sources).is_ok()
+    }
+
+    /// Replaces the sources of the alarm data with one source before their first use.
+    /// Returns false if the sources are already initialized.
+    pub fn set_alert_source(source: Box<dyn AlertSource>) -> bool {
+        set_alert_sources(vec![source])
+    }
+
+    /// Pause of the poller between the polls, the pause of the source with the highest priority
+    pub fn poll_interval() -> Duration {
+        alert_sources()[0].poll_interval()
+    }
 
     pub mod data_views {
 
         /// General structure of data received from the API alarms
         #[derive(Debug, serde::Deserialize)]
         pub struct AlertsResponseResult{
-            pub alerts: Vec<Location>,
-            meta: Option<Meta>,
-            disclaimer: Option<String>
+            /// The records are checked one by one, so a record with unexpected data does not break the whole response
+            pub alerts: Vec<serde_json::Value>,
+            pub meta: Option<Meta>,
+            pub disclaimer: Option<String>
         }
 
         /// Meta structure is part of the data received
         /// from the official alarm API
-        #[derive(Debug, serde::Deserialize)]
-        struct Meta{
-            last_updated_at: Option<String>,
-            meta_type: Option<String>
+        #[derive(Debug, Clone, serde::Deserialize)]
+        pub struct Meta{
+            /// Time when the alarm API updated the data
+            pub last_updated_at: Option<String>,
+            #[serde(rename = "type")]
+            pub meta_type: Option<String>
         }
     
         /// Location data
+        #[allow(dead_code)]
         #[derive(Debug, serde::Deserialize, Clone)]
         pub struct Location {
             pub id: i32,
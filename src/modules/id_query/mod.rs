@@ -0,0 +1,141 @@
+pub mod id_query {
+
+    use std::collections::HashSet;
+    use rocket::request::{self, FromRequest, Request};
+    use serde::Serialize;
+
+    use crate::modules::other_data::other_data::get_regions;
+
+    /// Maximum number of identifiers accepted in one request, duplicates included
+    pub const MAX_REQUESTED_IDS: usize = 64;
+
+    /// Description of a value from the query string that is not a valid region identifier
+    #[derive(Debug, Clone, Serialize)]
+    pub struct InvalidToken {
+        pub parameter: String,
+        pub token: String,
+        /// 1-based position of the token among all tokens of the request
+        pub position: usize,
+        pub reason: String,
+    }
+
+    /// Error returned when the requested identifiers can not be used
+    #[derive(Debug, Clone, Serialize)]
+    pub struct IdQueryError {
+        pub error: String,
+        pub invalid_tokens: Vec<InvalidToken>,
+    }
+
+    /// Identifiers of regions requested in the query string.
+    ///
+    /// The identifiers are passed as a list (ids=3,9,31), as repeated parameters (id=3&id=9)
+    /// or all at once (all=true). The parameters of the first version of the API
+    /// (location1=3&location2=9) are accepted as well. Other parameters are ignored.
+    /// The identifiers keep the order of the request, duplicates are removed.
+    #[derive(Debug, Clone, Default)]
+    pub struct IdSelection {
+        pub ids: Vec<i32>,
+        /// false if the request has no parameter that selects regions
+        pub is_selected: bool,
+    }
+
+    /// Checks whether the parameter is one of the parameters of the first version of the API
+    fn is_legacy_parameter(name: &str) -> bool {
+        name.strip_prefix("location")
+            .is_some_and(|suffix| suffix.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    impl IdSelection {
+
+        /// Parses the pairs of names and values of the query string in their order
+        pub fn parse<'a>(segments: impl Iterator<Item = (&'a str, &'a str)>) -> Result<Self, IdQueryError> {
+
+            let known_ids: HashSet<i32> = get_regions().iter().map(|region| region.id).collect();
+            let mut tokens = Vec::<(&str, &str)>::new();
+            let mut is_selected = false;
+
+            for (name, value) in segments {
+                match name {
+                    "ids" => tokens.extend(value.split(',').map(str::trim).filter(|token| !token.is_empty()).map(|token| (name, token))),
+                    "id" | "all" => tokens.push((name, value.trim())),
+                    _ if is_legacy_parameter(name) => tokens.push((name, value.trim())),
+                    _ => continue,
+                }
+                is_selected = true;
+            }
+
+            if tokens.len() > MAX_REQUESTED_IDS {
+                return Err(IdQueryError {
+                    error: format!("too many region identifiers: {}, at most {} are allowed", tokens.len(), MAX_REQUESTED_IDS),
+                    invalid_tokens: Vec::new(),
+                });
+            }
+
+            let mut ids = Vec::<i32>::new();
+            let mut seen = HashSet::<i32>::new();
+            let mut invalid_tokens = Vec::<InvalidToken>::new();
+            let mut select_all = false;
+
+            for (index, (parameter, token)) in tokens.into_iter().enumerate() {
+
+                let reason = match (parameter, token.parse::<i32>()) {
+                    ("all", _) => match token {
+                        "true" | "1" => {
+                            select_all = true;
+                            continue;
+                        }
+                        "false" | "0" => continue,
+                        _ => "expected 'true' or 'false'",
+                    },
+                    (_, Ok(id)) if known_ids.contains(&id) => {
+                        if seen.insert(id) {
+                            ids.push(id);
+                        }
+                        continue;
+                    }
+                    (_, Ok(_)) => "unknown region identifier",
+                    (_, Err(_)) => "not an integer",
+                };
+
+                invalid_tokens.push(InvalidToken {
+                    parameter: parameter.to_string(),
+                    token: token.to_string(),
+                    position: index + 1,
+                    reason: reason.to_string(),
+                });
+            }
+
+            if !invalid_tokens.is_empty() {
+                return Err(IdQueryError {
+                    error: "invalid region identifiers in the request".to_string(),
+                    invalid_tokens,
+                });
+            }
+
+            if select_all {
+                ids.extend(get_regions().iter().map(|region| region.id).filter(|id| seen.insert(*id)));
+            }
+
+            Ok(IdSelection { ids, is_selected })
+        }
+    }
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for IdSelection {
+        type Error = IdQueryError;
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+
+            let result = match request.uri().query() {
+                Some(query) => IdSelection::parse(query.segments()),
+                None => Ok(IdSelection::default()),
+            };
+
+            match result {
+                Ok(selection) => request::Outcome::Success(selection),
+                Err(error) => request::Outcome::Error((rocket::http::Status::BadRequest, error)),
+            }
+        }
+    }
+
+}
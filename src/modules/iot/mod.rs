@@ -0,0 +1,128 @@
+pub mod iot {
+
+    use rocket::{
+        get, routes, Route,
+        http::ContentType,
+        response::content::{RawJson, RawText},
+    };
+    use serde::Serialize;
+
+    use crate::modules::{
+        api_keys::api_keys::ApiKey,
+        api_v2::api_v2::{Envelope, Meta, REGIONS_CACHE_CONTROL},
+        caching::caching::{strong_etag, Cached},
+        other_data::other_data::get_regions,
+        processing::processing::{get_all_alarm_scopes, get_all_alarm_statuses, get_snapshot_info, AlarmScope},
+    };
+
+    /// Devices poll the status often, so they have to revalidate it every time
+    const STATUS_CACHE_CONTROL: &str = "no-cache";
+
+    /// Position of a region in the status string
+    #[derive(Debug, Serialize)]
+    pub struct StatusIndex {
+        pub index: usize,
+        pub region_id: i32,
+        pub name: String,
+        pub slug: String,
+    }
+
+    /// Alarm state of a region in the compact status
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum RegionStatus {
+        None,
+        Partial,
+        Full,
+    }
+
+    impl RegionStatus {
+
+        fn as_char(&self) -> char {
+            match self {
+                RegionStatus::None => 'N',
+                RegionStatus::Partial => 'P',
+                RegionStatus::Full => 'A',
+            }
+        }
+
+        fn as_byte(&self) -> u8 {
+            match self {
+                RegionStatus::None => 0,
+                RegionStatus::Partial => 1,
+                RegionStatus::Full => 2,
+            }
+        }
+    }
+
+    /// Returns the state of all regions in the order of the registry and the "last-modified" value of the data
+    async fn region_statuses() -> (Vec<RegionStatus>, Option<String>) {
+
+        let statuses = get_all_alarm_statuses().await;
+        let scopes = get_all_alarm_scopes().await;
+        let snapshot = get_snapshot_info().await;
+
+        let result = get_regions()
+            .iter()
+            .map(|region| match (statuses.get(&region.id).copied().unwrap_or(false), scopes.get(&region.id)) {
+                (false, _) => RegionStatus::None,
+                (true, Some(AlarmScope::Partial)) => RegionStatus::Partial,
+                (true, _) => RegionStatus::Full,
+            })
+            .collect();
+        (result, snapshot.last_modified)
+    }
+
+    /// Tags the body with a strong entity tag of its content
+    fn cached<R>(inner: R, body: &[u8], last_modified: Option<String>) -> Cached<R> {
+        Cached {
+            cache_control: Some(STATUS_CACHE_CONTROL),
+            ..Cached::new(inner, strong_etag(body), last_modified)
+        }
+    }
+
+    /// Alarm state of all regions as a JSON string with one character per region
+    /// in the order of the registry: N - no alarm, P - alarm in a part of the region, A - alarm in the whole region
+    #[get("/status")]
+    pub async fn get_status(_api_key: ApiKey) -> Cached<RawJson<String>> {
+        let (statuses, last_modified) = region_statuses().await;
+        let body = format!("\"{}\"", statuses.iter().map(RegionStatus::as_char).collect::<String>());
+        cached(RawJson(body.clone()), body.as_bytes(), last_modified)
+    }
+
+    /// The same status as plain text
+    #[get("/status.txt")]
+    pub async fn get_status_text(_api_key: ApiKey) -> Cached<RawText<String>> {
+        let (statuses, last_modified) = region_statuses().await;
+        let body: String = statuses.iter().map(RegionStatus::as_char).collect();
+        cached(RawText(body.clone()), body.as_bytes(), last_modified)
+    }
+
+    /// The same status as bytes: 0 - no alarm, 1 - partial alarm, 2 - alarm in the whole region
+    #[get("/status.bin")]
+    pub async fn get_status_bytes(_api_key: ApiKey) -> Cached<(ContentType, Vec<u8>)> {
+        let (statuses, last_modified) = region_statuses().await;
+        let body: Vec<u8> = statuses.iter().map(RegionStatus::as_byte).collect();
+        let etag_body = body.clone();
+        cached((ContentType::Binary, body), &etag_body, last_modified)
+    }
+
+    /// Regions in the order of the status string
+    #[get("/mapping")]
+    pub fn get_mapping(_api_key: ApiKey) -> Cached<RawJson<String>> {
+
+        let mapping: Vec<StatusIndex> = get_regions()
+            .into_iter()
+            .enumerate()
+            .map(|(index, region)| StatusIndex { index, region_id: region.id, name: region.names.en, slug: region.codes.slug })
+            .collect();
+
+        let meta = Meta { count: Some(mapping.len()), ..Meta::new() };
+        Cached::json(&Envelope::data(mapping, meta), Some(REGIONS_CACHE_CONTROL))
+    }
+
+    /// Returns all routes of the compact status for devices
+    pub fn routes() -> Vec<Route> {
+        routes![get_status, get_status_text, get_status_bytes, get_mapping]
+    }
+
+}
@@ -0,0 +1,207 @@
+pub mod leader {
+
+    use dotenv::dotenv;
+    use std::env;
+    use std::sync::RwLock;
+    use std::time::Duration;
+    use log::{debug, info};
+    use once_cell::sync::Lazy;
+    use rand::Rng;
+    use rocket::{get, routes, Route, serde::json::Json};
+    use serde::Serialize;
+    use tokio::time::Instant;
+
+    use crate::modules::{
+        processing::processing::get_snapshot_info,
+        store::store::{store, HashWrite, StoreResult},
+    };
+
+    /// Lease of the poller
+    const LEASE_KEY: &str = "poller:leader";
+
+    /// Counter of the leases, every new leader gets the next value as its fencing token
+    const TOKEN_KEY: &str = "poller:fencing_token";
+
+    /// Lifetime of the lease if LEADER_LEASE_MS is not set
+    const DEFAULT_LEASE_MS: u64 = 15000;
+
+    /// Settings of the election from the LEADER_* variables
+    #[derive(Debug, Clone)]
+    pub struct LeaderConfig {
+        /// Without the election every instance polls the alarm API
+        pub enabled: bool,
+        pub lease: Duration,
+        pub instance_id: String,
+    }
+
+    ///Get the settings of the election.
+    ///The instance ID is INSTANCE_ID or the host name with the process ID and a random suffix.
+    fn get_leader_config() -> LeaderConfig {
+        dotenv().ok();
+        LeaderConfig {
+            enabled: !matches!(env::var("LEADER_ELECTION").as_deref(), Ok("false") | Ok("0")),
+            lease: Duration::from_millis(
+                env::var("LEADER_LEASE_MS")
+                    .map(|value| value.parse().expect("INCORRECT LEADER_LEASE_MS"))
+                    .unwrap_or(DEFAULT_LEASE_MS),
+            ),
+            instance_id: env::var("INSTANCE_ID").ok().filter(|value| !value.is_empty()).unwrap_or_else(|| {
+                format!(
+                    "{}-{}-{:04x}",
+                    env::var("HOSTNAME").unwrap_or_else(|_| "instance".to_string()),
+                    std::process::id(),
+                    rand::thread_rng().gen::<u16>()
+                )
+            }),
+        }
+    }
+
+    static CONFIG: Lazy<LeaderConfig> = Lazy::new(get_leader_config);
+
+    /// Leadership of this instance
+    #[derive(Debug, Clone, Serialize)]
+    pub struct LeaderStatus {
+        pub instance_id: String,
+        pub election_enabled: bool,
+        pub is_leader: bool,
+        /// Token of the current lease, it grows with every change of the leader
+        pub fencing_token: Option<u64>,
+        pub since: Option<String>,
+    }
+
+    static STATUS: Lazy<RwLock<LeaderStatus>> = Lazy::new(|| {
+        RwLock::new(LeaderStatus {
+            instance_id: CONFIG.instance_id.clone(),
+            election_enabled: CONFIG.enabled,
+            is_leader: !CONFIG.enabled,
+            fencing_token: None,
+            since: None,
+        })
+    });
+
+    pub fn leader_status() -> LeaderStatus {
+        STATUS.read().unwrap().clone()
+    }
+
+    pub fn is_leader() -> bool {
+        STATUS.read().unwrap().is_leader
+    }
+
+    /// Saves the result of an attempt to take the lease
+    fn set_leadership(token: Option<u64>) {
+
+        let mut status = STATUS.write().unwrap();
+        if status.fencing_token == token {
+            return;
+        }
+
+        match token {
+            Some(token) => info!("{} - {} is the leader with fencing token {}", chrono::Local::now(), status.instance_id, token),
+            None => info!("{} - {} is a follower", chrono::Local::now(), status.instance_id),
+        }
+        status.is_leader = token.is_some();
+        status.fencing_token = token;
+        status.since = Some(chrono::Utc::now().to_rfc3339());
+    }
+
+    /// Tries to take or renew the lease.
+    /// The leadership is lost if the store is not available, because another instance may take the lease meanwhile.
+    async fn try_acquire() -> Option<u64> {
+        match store().acquire_lease(LEASE_KEY, TOKEN_KEY, &CONFIG.instance_id, CONFIG.lease).await {
+            Ok(token) => token,
+            Err(e) => {
+                debug!("{} - error renewing the leader lease\n{}", chrono::Local::now(), e);
+                None
+            }
+        }
+    }
+
+    /// Checks the lease before the poller writes the data.
+    /// Returns false if another instance has taken the lease or has a newer fencing token.
+    pub async fn confirm_leadership() -> bool {
+
+        if !CONFIG.enabled {
+            return true;
+        }
+        let Some(token) = leader_status().fencing_token else {
+            return false;
+        };
+
+        let current = try_acquire().await;
+        if current != Some(token) {
+            set_leadership(current);
+        }
+        current == Some(token)
+    }
+
+    /// Writes the data of the poller fenced by the token of this instance.
+    /// The write is rejected if another instance has taken the lease since this one got its token,
+    /// for example while a slow poll was in progress. Without the election the data is written as is.
+    pub async fn write_as_leader(writes: &[HashWrite]) -> StoreResult<bool> {
+
+        if !CONFIG.enabled {
+            return store().hwrite(writes, None).await;
+        }
+        let Some(token) = leader_status().fencing_token else {
+            return Ok(false);
+        };
+
+        let written = store().hwrite(writes, Some((TOKEN_KEY, token))).await?;
+        if !written {
+            set_leadership(None);
+        }
+        Ok(written)
+    }
+
+    /// Takes part in the election: every third of the lease the instance
+    /// tries to take the lease or renews its own one.
+    pub async fn run_election() {
+
+        if !CONFIG.enabled {
+            return;
+        }
+
+        let interval = CONFIG.lease / 3;
+        loop {
+            let started = Instant::now();
+            set_leadership(try_acquire().await);
+            tokio::time::sleep_until(started + interval).await;
+        }
+    }
+
+    /// Gives the lease to the other instances when the service stops
+    pub async fn release_leadership() {
+        if CONFIG.enabled && is_leader() {
+            if let Err(e) = store().release_lease(LEASE_KEY, &CONFIG.instance_id).await {
+                debug!("{} - error releasing the leader lease\n{}", chrono::Local::now(), e);
+            }
+            set_leadership(None);
+        }
+    }
+
+    /// State of the instance for the health checks
+    #[derive(Debug, Serialize)]
+    struct Health {
+        status: &'static str,
+        role: &'static str,
+        leader: LeaderStatus,
+        last_poll: Option<String>,
+    }
+
+    #[get("/health")]
+    async fn get_health() -> Json<Health> {
+        let leader = leader_status();
+        Json(Health {
+            status: "ok",
+            role: if leader.is_leader { "leader" } else { "follower" },
+            leader,
+            last_poll: get_snapshot_info().await.last_poll,
+        })
+    }
+
+    /// Returns all routes of the health check
+    pub fn routes() -> Vec<Route> {
+        routes![get_health]
+    }
+
+}
@@ -0,0 +1,117 @@
+pub mod metrics {
+
+    use std::fmt::{Display, Write};
+    use log::debug;
+    use rocket::{get, routes, Route};
+
+    use crate::modules::{
+        diff::diff::AlarmEvent,
+        leader::leader::leader_status,
+        processing::processing::{get_all_alarm_statuses, get_snapshot_info, get_source_info},
+        providers::providers::get_sources_status,
+        quarantine::quarantine::get_quarantine_counts,
+        store::store::store,
+    };
+
+    /// Hash with the number of the alarm events of every type
+    const EVENTS_KEY: &str = "metrics:events";
+
+    /// Counts the events found by the poller.
+    /// The counters are kept in the store, so every instance of the service reports the same values.
+    pub async fn record_events(events: &[AlarmEvent]) {
+        for event in events {
+            if let Err(e) = store().hincr(EVENTS_KEY, event.kind.name(), 1, None).await {
+                debug!("{} - error updating the event metrics\n{}", chrono::Local::now(), e);
+            }
+        }
+    }
+
+    /// Appends a metric in the Prometheus text format
+    fn write_metric<T: Display>(output: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, T)]) {
+        let _ = writeln!(output, "# HELP {} {}", name, help);
+        let _ = writeln!(output, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(output, "{}{} {}", name, labels, value);
+        }
+    }
+
+    /// Metrics of the service in the Prometheus text format.
+    /// The leadership metrics describe the instance that answers the request.
+    #[get("/metrics")]
+    async fn get_metrics() -> String {
+
+        let mut events: Vec<(String, String)> = store().hgetall(EVENTS_KEY).await.unwrap_or_default().into_iter().collect();
+        events.sort();
+        let active_alarms = get_all_alarm_statuses().await.values().filter(|is_active| **is_active).count();
+        let snapshot = get_snapshot_info().await;
+
+        let mut output = String::new();
+        write_metric(
+            &mut output,
+            "alarm_events_total",
+            "counter",
+            "Alarm events found by comparing the snapshots of the alarm API",
+            &events
+                .into_iter()
+                .map(|(event, value)| (format!("{{event=\"{}\"}}", event), value.parse().unwrap_or(0)))
+                .collect::<Vec<_>>(),
+        );
+        let mut quarantined: Vec<(String, u64)> = get_quarantine_counts().await.into_iter().collect();
+        quarantined.sort();
+        write_metric(
+            &mut output,
+            "quarantined_records_total",
+            "counter",
+            "Records of the alarm API that were quarantined by the poller",
+            &quarantined
+                .into_iter()
+                .map(|(kind, value)| (format!("{{kind=\"{}\"}}", kind), value))
+                .collect::<Vec<_>>(),
+        );
+        write_metric(&mut output, "active_alarms", "gauge", "Regions with an active air raid alert", &[(String::new(), active_alarms as u64)]);
+        write_metric(&mut output, "snapshot_version", "gauge", "Version of the alarm data in the store", &[(String::new(), snapshot.version)]);
+        if let Some(source) = get_source_info().await {
+            if let Some(lag) = source.lag_seconds {
+                write_metric(&mut output, "upstream_lag_seconds", "gauge", "Age of the data of the alarm API when it was received", &[(String::new(), lag)]);
+            }
+            if let Some(skew) = source.skew_seconds {
+                write_metric(&mut output, "upstream_skew_seconds", "gauge", "Difference between the last-modified header and the update time reported by the alarm API", &[(String::new(), skew)]);
+            }
+        }
+        if let Some(sources) = get_sources_status().await {
+            write_metric(
+                &mut output,
+                "source_up",
+                "gauge",
+                "Whether the source of the alarm data works (status ok)",
+                &sources.sources.iter().map(|source| (format!("{{source=\"{}\"}}", source.name), (source.status == "ok") as u64)).collect::<Vec<_>>(),
+            );
+            write_metric(
+                &mut output,
+                "source_active",
+                "gauge",
+                "Whether the poller uses the data of the source",
+                &sources.sources.iter().map(|source| (format!("{{source=\"{}\"}}", source.name), source.active as u64)).collect::<Vec<_>>(),
+            );
+            write_metric(
+                &mut output,
+                "source_consecutive_failures",
+                "gauge",
+                "Failed requests to the source in a row",
+                &sources.sources.iter().map(|source| (format!("{{source=\"{}\"}}", source.name), source.consecutive_failures as u64)).collect::<Vec<_>>(),
+            );
+            let disagreement = sources.disagreement.map(|disagreement| disagreement.regions.len()).unwrap_or(0);
+            write_metric(&mut output, "source_disagreement_regions", "gauge", "Regions with different air raid alerts in the sources", &[(String::new(), disagreement as u64)]);
+        }
+        let leader = leader_status();
+        write_metric(&mut output, "poller_leader", "gauge", "Whether this instance is the leader that polls the alarm API", &[(String::new(), leader.is_leader as u64)]);
+        write_metric(&mut output, "leader_fencing_token", "gauge", "Fencing token of the lease of this instance, 0 for a follower", &[(String::new(), leader.fencing_token.unwrap_or(0))]);
+        output
+    }
+
+    /// Returns all routes of the metrics
+    pub fn routes() -> Vec<Route> {
+        routes![get_metrics]
+    }
+
+}
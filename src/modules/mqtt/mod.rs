@@ -0,0 +1,251 @@
+pub mod mqtt {
+
+    use dotenv::dotenv;
+    use std::env;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use log::debug;
+    use once_cell::sync::OnceCell;
+    use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS, TlsConfiguration, Transport};
+    use serde::Serialize;
+    use tokio::sync::Mutex;
+
+    use crate::modules::{
+        diff::diff::AlarmEvent,
+        notifications::notifications::{Notifier, NotifyError},
+        other_data::other_data::get_all_cities,
+        processing::processing::get_all_alarm_statuses,
+    };
+
+    /// Payload of the status topic while the poller is connected.
+    /// The broker publishes the "offline" last will when the connection is lost.
+    const ONLINE: &str = "online";
+    const OFFLINE: &str = "offline";
+
+    /// Settings of the MQTT output from the MQTT_* variables
+    #[derive(Debug, Clone)]
+    pub struct MqttConfig {
+        pub host: String,
+        pub port: u16,
+        pub client_id: String,
+        pub username: Option<String>,
+        pub password: Option<String>,
+        pub qos: QoS,
+        pub topic_prefix: String,
+        pub tls: bool,
+        pub ca_file: Option<String>,
+    }
+
+    /// Returns the value of an optional variable, empty values are ignored
+    fn get_optional_var(name: &str) -> Option<String> {
+        env::var(name).ok().filter(|value| !value.is_empty())
+    }
+
+    ///Get the settings of the MQTT output.
+    ///Returns None if MQTT_HOST is not set.
+    fn get_mqtt_config() -> Option<MqttConfig> {
+        dotenv().ok();
+        let host = get_optional_var("MQTT_HOST")?;
+        let qos: u8 = get_optional_var("MQTT_QOS").map(|value| value.parse().expect("INCORRECT MQTT_QOS")).unwrap_or(1);
+
+        Some(MqttConfig {
+            host,
+            port: get_optional_var("MQTT_PORT").map(|value| value.parse().expect("INCORRECT MQTT_PORT")).unwrap_or(1883),
+            client_id: get_optional_var("MQTT_CLIENT_ID").unwrap_or_else(|| "alerts_api_app".to_string()),
+            username: get_optional_var("MQTT_USERNAME"),
+            password: get_optional_var("MQTT_PASSWORD"),
+            qos: rumqttc::qos(qos).expect("INCORRECT MQTT_QOS"),
+            topic_prefix: get_optional_var("MQTT_TOPIC_PREFIX").unwrap_or_else(|| "alarms".to_string()),
+            tls: get_optional_var("MQTT_TLS").is_some_and(|value| value == "true" || value == "1"),
+            ca_file: get_optional_var("MQTT_CA_FILE"),
+        })
+    }
+
+    /// Connected client of the MQTT output
+    struct Publisher {
+        client: AsyncClient,
+        config: MqttConfig,
+        /// Last published state of every region, the lock keeps the publications in order
+        published: Mutex<HashMap<i32, bool>>,
+    }
+
+    /// Publisher of the MQTT output, if it is enabled
+    static PUBLISHER: OnceCell<Arc<Publisher>> = OnceCell::new();
+
+    fn state_payload(is_active: bool) -> String {
+        let state = if is_active { "active" } else { "inactive" };
+        state.to_string()
+    }
+
+    impl Publisher {
+
+        /// Publishes a retained message to a topic under the prefix
+        async fn publish(&self, topic: &str, payload: String) -> Result<(), NotifyError> {
+            let topic = format!("{}/{}", self.config.topic_prefix, topic);
+            self.client
+                .publish(topic.clone(), self.config.qos, true, payload)
+                .await
+                .map_err(|e| NotifyError(format!("error publishing to MQTT topic {}: {}", topic, e)))
+        }
+
+        /// Publishes the list of regions with an active alarm to the "<prefix>/all" topic
+        async fn publish_aggregate(&self, statuses: &HashMap<i32, bool>) -> Result<(), NotifyError> {
+
+            let mut active_region_ids: Vec<i32> = statuses
+                .iter()
+                .filter(|(_, is_active)| **is_active)
+                .map(|(id, _)| *id)
+                .collect();
+            active_region_ids.sort();
+
+            let aggregate = AggregateState {
+                active_region_ids: &active_region_ids,
+                active_count: active_region_ids.len(),
+                updated_at: chrono::Utc::now().to_rfc3339(),
+            };
+            self.publish("all", serde_json::to_string(&aggregate).unwrap()).await
+        }
+
+        /// Publishes the state of the regions that differs from the last published state.
+        /// The state includes the manual overrides, so it is the same as the state of the API.
+        /// With "all" the state of every region is published, so the broker has retained messages for every region.
+        async fn publish_changes(&self, all: bool) -> Result<(), NotifyError> {
+
+            let mut published = self.published.lock().await;
+            let statuses = get_all_alarm_statuses().await;
+
+            let mut changed = false;
+            for id in get_all_cities().keys() {
+                let is_active = statuses.get(id).copied().unwrap_or(false);
+                if all || published.get(id) != Some(&is_active) {
+                    self.publish(&format!("{}/state", id), state_payload(is_active)).await?;
+                    published.insert(*id, is_active);
+                    changed = true;
+                }
+            }
+
+            match changed {
+                true => self.publish_aggregate(&statuses).await,
+                false => Ok(()),
+            }
+        }
+    }
+
+    /// Aggregate state published to the "<prefix>/all" topic
+    #[derive(Debug, Serialize)]
+    struct AggregateState<'a> {
+        active_region_ids: &'a [i32],
+        active_count: usize,
+        updated_at: String,
+    }
+
+    /// Builds the connection options with the last will, credentials and TLS settings
+    fn mqtt_options(config: &MqttConfig) -> MqttOptions {
+
+        let mut options = MqttOptions::new(config.client_id.clone(), config.host.clone(), config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(format!("{}/status", config.topic_prefix), OFFLINE, QoS::AtLeastOnce, true));
+
+        if let Some(username) = &config.username {
+            options.set_credentials(username.clone(), config.password.clone().unwrap_or_default());
+        }
+
+        if config.tls {
+            let transport = match &config.ca_file {
+                Some(ca_file) => {
+                    let ca = std::fs::read(ca_file).expect("THE MQTT CA FILE IS UNAVAILABLE");
+                    Transport::tls_with_config(TlsConfiguration::Simple { ca, alpn: None, client_auth: None })
+                }
+                None => Transport::tls_with_default_config(),
+            };
+            options.set_transport(transport);
+        }
+        options
+    }
+
+    /// Publishes the alarm state of the regions changed by the alarm events to the retained
+    /// "<prefix>/<region_id>/state" topics ("active" or "inactive")
+    /// and the list of regions with an active alarm to the retained "<prefix>/all" topic.
+    pub struct MqttNotifier {
+        publisher: Arc<Publisher>,
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for MqttNotifier {
+
+        fn name(&self) -> String {
+            "mqtt".to_string()
+        }
+
+        /// The retained state of the regions follows the alarms without a delay
+        fn uses_delivery_policy(&self) -> bool {
+            false
+        }
+
+        async fn notify(&self, _event: &AlarmEvent) -> Result<(), NotifyError> {
+            self.publisher.publish_changes(false).await
+        }
+    }
+
+    /// Connects to the broker if MQTT_HOST is set.
+    /// The connection is kept by a background task, which reconnects after errors.
+    /// After every connection "online" is published to the status topic
+    /// and the state of all regions is published again.
+    pub fn init_mqtt() -> Option<MqttNotifier> {
+
+        let config = match get_mqtt_config() {
+            Some(config) => config,
+            None => {
+                debug!("{} - MQTT_HOST is not set, the MQTT output is disabled", chrono::Local::now());
+                return None;
+            }
+        };
+
+        let (client, mut event_loop) = AsyncClient::new(mqtt_options(&config), 100);
+        let status_topic = format!("{}/status", config.topic_prefix);
+        let publisher = Arc::new(Publisher { client, config, published: Mutex::new(HashMap::new()) });
+        let connection_publisher = Arc::clone(&publisher);
+        let _ = PUBLISHER.set(Arc::clone(&publisher));
+
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        debug!("{} - connected to the MQTT broker", chrono::Local::now());
+
+                        // The event loop must keep running while the messages are queued
+                        let publisher = Arc::clone(&connection_publisher);
+                        let status_topic = status_topic.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = publisher.client.publish(status_topic, QoS::AtLeastOnce, true, ONLINE).await {
+                                debug!("{} - error publishing the MQTT status\n{}", chrono::Local::now(), e);
+                            }
+                            if let Err(e) = publisher.publish_changes(true).await {
+                                debug!("{} - error publishing the MQTT state\n{}", chrono::Local::now(), e);
+                            }
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        debug!("{} - MQTT connection error, reconnecting\n{}", chrono::Local::now(), e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        Some(MqttNotifier { publisher })
+    }
+
+    /// Publishes the changes of the alarm state made without alarm events,
+    /// for example the manual overrides and their expiry. Called by the poller after every poll.
+    pub async fn sync_mqtt_state() {
+        if let Some(publisher) = PUBLISHER.get() {
+            if let Err(e) = publisher.publish_changes(false).await {
+                debug!("{} - error publishing the MQTT state\n{}", chrono::Local::now(), e);
+            }
+        }
+    }
+
+}
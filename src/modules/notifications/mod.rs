@@ -0,0 +1,433 @@
+pub mod notifications {
+
+    use dotenv::dotenv;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fmt;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use lettre::{
+        AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+        message::Mailbox,
+        transport::smtp::authentication::Credentials,
+    };
+    use log::debug;
+    use once_cell::sync::OnceCell;
+    use serde::Serialize;
+    use tokio::sync::mpsc;
+    use tokio::time::Instant;
+
+    use crate::modules::{
+        delivery_policy::delivery_policy::{DeliveryPolicy, PolicyFilter, QuietHours},
+        diff::diff::AlarmEvent,
+        events::events::RedisEventsNotifier,
+        store::store::{store, StoreResult},
+        telegram::telegram::start_telegram_bot,
+    };
+    #[cfg(feature = "mqtt")]
+    use crate::modules::mqtt::mqtt::init_mqtt;
+
+    /// Number of events waiting in the queue of a channel if NOTIFY_QUEUE_CAPACITY is not set
+    const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+    /// Number of retries of a failed notification if NOTIFY_RETRIES is not set
+    const DEFAULT_RETRIES: u32 = 2;
+
+    /// Time limit of the HTTP requests of the webhook channels
+    const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Hash with the names of the registered channels and the capacity of their queues
+    const CHANNELS_KEY: &str = "notifiers";
+
+    /// Error of a notification channel
+    #[derive(Debug, Clone)]
+    pub struct NotifyError(pub String);
+
+    impl fmt::Display for NotifyError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl std::error::Error for NotifyError {}
+
+    impl From<reqwest::Error> for NotifyError {
+        fn from(e: reqwest::Error) -> Self {
+            NotifyError(e.to_string())
+        }
+    }
+
+    /// Channel that delivers alarm events outside of the service.
+    ///
+    /// Every channel registered in the dispatcher gets its own queue and task,
+    /// so a slow or failing channel never delays the poller or the other channels.
+    /// The task applies the delivery rules of the channel before the events reach it.
+    #[rocket::async_trait]
+    pub trait Notifier: Send + Sync {
+
+        /// Name of the channel in the logs and in the statistics
+        fn name(&self) -> String;
+
+        async fn notify(&self, event: &AlarmEvent) -> Result<(), NotifyError>;
+
+        /// Whether the dispatcher applies the NOTIFY_* delivery rules to the events of the channel.
+        /// Channels with their own rules turn it off, so the events are not delayed twice.
+        fn uses_delivery_policy(&self) -> bool {
+            true
+        }
+    }
+
+    /// Delivery statistics of a channel
+    #[derive(Debug, Clone, Serialize)]
+    pub struct NotifierStats {
+        pub name: String,
+        pub queue_capacity: usize,
+        pub sent: u64,
+        pub failed: u64,
+        pub dropped: u64,
+        pub last_error: Option<String>,
+        pub last_error_at: Option<String>,
+    }
+
+    fn stats_key(name: &str) -> String {
+        format!("notifier_stats:{}", name)
+    }
+
+    /// Increments a delivery counter of a channel
+    async fn count(name: &str, counter: &str) {
+        if let Err(e) = store().hincr(&stats_key(name), counter, 1, None).await {
+            debug!("{} - {} - error updating notifier statistics\n{}", chrono::Local::now(), name, e);
+        }
+    }
+
+    /// Saves the last error of a channel
+    async fn save_error(name: &str, error: &NotifyError) {
+        let values = [
+            ("last_error".to_string(), error.to_string()),
+            ("last_error_at".to_string(), chrono::Utc::now().to_rfc3339()),
+        ];
+        if let Err(e) = store().hset_multiple(&stats_key(name), &values).await {
+            debug!("{} - {} - error updating notifier statistics\n{}", chrono::Local::now(), name, e);
+        }
+    }
+
+    /// Returns the delivery statistics of all registered channels.
+    /// The statistics are kept in the store, so they are available in every instance of the service.
+    pub async fn get_notifier_stats() -> StoreResult<Vec<NotifierStats>> {
+
+        let mut channels: Vec<(String, String)> = store().hgetall(CHANNELS_KEY).await?.into_iter().collect();
+        channels.sort();
+
+        let mut result = Vec::new();
+        for (name, queue_capacity) in channels {
+            let mut values = store().hgetall(&stats_key(&name)).await?;
+            let counter = |values: &HashMap<String, String>, field: &str| values.get(field).and_then(|value| value.parse().ok()).unwrap_or(0);
+            result.push(NotifierStats {
+                queue_capacity: queue_capacity.parse().unwrap_or(0),
+                sent: counter(&values, "sent"),
+                failed: counter(&values, "failed"),
+                dropped: counter(&values, "dropped"),
+                last_error: values.remove("last_error"),
+                last_error_at: values.remove("last_error_at"),
+                name,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Sends an event with the channel.
+    /// A failed notification is retried with a growing pause.
+    async fn deliver(notifier: &dyn Notifier, event: &AlarmEvent, retries: u32) {
+
+        let name = notifier.name();
+        let mut attempt = 0;
+        loop {
+            match notifier.notify(event).await {
+                Ok(_) => {
+                    count(&name, "sent").await;
+                    return;
+                }
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    debug!("{} - {} - notification failed, retry {}\n{}", chrono::Local::now(), name, attempt, e);
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                }
+                Err(e) => {
+                    debug!("{} - {} - notification failed\n{}", chrono::Local::now(), name, e);
+                    count(&name, "failed").await;
+                    save_error(&name, &e).await;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the value of a setting of a channel: NOTIFY_<CHANNEL>_<SETTING> or NOTIFY_<SETTING> for all channels
+    fn get_channel_var(channel: &str, setting: &str) -> Option<String> {
+        get_optional_var(&format!("NOTIFY_{}_{}", channel.to_uppercase(), setting))
+            .or_else(|| get_optional_var(&format!("NOTIFY_{}", setting)))
+    }
+
+    ///Get the delivery rules of a channel from the DEBOUNCE_SECS, FLAP_WINDOW_SECS and QUIET_HOURS settings
+    fn get_delivery_policy(channel: &str) -> DeliveryPolicy {
+        let seconds = |setting: &str| {
+            get_channel_var(channel, setting)
+                .map(|value| Duration::from_secs(value.parse().expect("INCORRECT NOTIFY DELAY")))
+                .unwrap_or_default()
+        };
+        DeliveryPolicy {
+            debounce: seconds("DEBOUNCE_SECS"),
+            flap_window: seconds("FLAP_WINDOW_SECS"),
+            quiet_hours: get_channel_var(channel, "QUIET_HOURS")
+                .map(|value| QuietHours::parse(&value).expect("INCORRECT NOTIFY QUIET HOURS, EXPECTED HH:MM-HH:MM")),
+        }
+    }
+
+    /// Queue of a registered channel
+    struct Channel {
+        name: String,
+        queue: mpsc::Sender<AlarmEvent>,
+    }
+
+    /// Fans out the alarm events to the registered channels
+    pub struct Dispatcher {
+        channels: Vec<Channel>,
+        queue_capacity: usize,
+        retries: u32,
+    }
+
+    impl Dispatcher {
+
+        pub fn new(queue_capacity: usize, retries: u32) -> Self {
+            Dispatcher { channels: Vec::new(), queue_capacity, retries }
+        }
+
+        /// Starts the task of a channel that delivers the events from its queue
+        /// according to the delivery rules of the channel.
+        pub async fn register(&mut self, notifier: Arc<dyn Notifier>) {
+
+            let name = notifier.name();
+            let (sender, mut receiver) = mpsc::channel::<AlarmEvent>(self.queue_capacity);
+            let retries = self.retries;
+
+            if let Err(e) = store().hset(CHANNELS_KEY, &name, &self.queue_capacity.to_string()).await {
+                debug!("{} - {} - error registering the notifier\n{}", chrono::Local::now(), name, e);
+            }
+
+            let policy = notifier.uses_delivery_policy().then(|| get_delivery_policy(&name));
+
+            tokio::spawn(async move {
+
+                let Some(policy) = policy else {
+                    while let Some(event) = receiver.recv().await {
+                        deliver(notifier.as_ref(), &event, retries).await;
+                    }
+                    return;
+                };
+
+                let mut filter = PolicyFilter::<i32, AlarmEvent>::default();
+
+                loop {
+                    let deadline = filter.next_deadline();
+                    tokio::select! {
+                        event = receiver.recv() => match event {
+                            Some(event) => filter.push(event.region_id, event, policy, Instant::now()),
+                            None => return,
+                        },
+                        _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+                    }
+
+                    for (_, event) in filter.take_ready(Instant::now(), chrono::Local::now().time()) {
+                        deliver(notifier.as_ref(), &event, retries).await;
+                    }
+                }
+            });
+
+            debug!("{} - notifier {} registered", chrono::Local::now(), name);
+            self.channels.push(Channel { name, queue: sender });
+        }
+
+        /// Puts the event in the queue of every channel without waiting.
+        /// The event is dropped for a channel whose queue is full.
+        pub fn dispatch(&self, event: &AlarmEvent) {
+            for channel in &self.channels {
+                if channel.queue.try_send(event.clone()).is_err() {
+                    debug!("{} - {} - the queue is full, an alarm event is dropped", chrono::Local::now(), channel.name);
+                    let name = channel.name.clone();
+                    tokio::spawn(async move { count(&name, "dropped").await });
+                }
+            }
+        }
+    }
+
+    static DISPATCHER: OnceCell<Dispatcher> = OnceCell::new();
+
+    /// Sends the event to all registered channels
+    pub fn dispatch(event: &AlarmEvent) {
+        match DISPATCHER.get() {
+            Some(dispatcher) => dispatcher.dispatch(event),
+            None => debug!("{} - the notification dispatcher is not initialized", chrono::Local::now()),
+        }
+    }
+
+    /// Writes the events to the standard output as JSON lines
+    pub struct StdoutNotifier;
+
+    #[rocket::async_trait]
+    impl Notifier for StdoutNotifier {
+
+        fn name(&self) -> String {
+            "stdout".to_string()
+        }
+
+        async fn notify(&self, event: &AlarmEvent) -> Result<(), NotifyError> {
+            println!("{}", serde_json::to_string(event).map_err(|e| NotifyError(e.to_string()))?);
+            Ok(())
+        }
+    }
+
+    /// Sends the events as JSON to an HTTP endpoint
+    pub struct WebhookNotifier {
+        client: reqwest::Client,
+        url: String,
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for WebhookNotifier {
+
+        fn name(&self) -> String {
+            "webhook".to_string()
+        }
+
+        async fn notify(&self, event: &AlarmEvent) -> Result<(), NotifyError> {
+            self.client.post(&self.url).json(event).send().await?.error_for_status()?;
+            Ok(())
+        }
+    }
+
+    /// Sends the events as text to a Slack-compatible incoming webhook
+    pub struct SlackNotifier {
+        client: reqwest::Client,
+        url: String,
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for SlackNotifier {
+
+        fn name(&self) -> String {
+            "slack".to_string()
+        }
+
+        async fn notify(&self, event: &AlarmEvent) -> Result<(), NotifyError> {
+            let body = serde_json::json!({ "text": event.summary() });
+            self.client.post(&self.url).json(&body).send().await?.error_for_status()?;
+            Ok(())
+        }
+    }
+
+    /// Sends the events by email
+    pub struct SmtpNotifier {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: Mailbox,
+        to: Vec<Mailbox>,
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for SmtpNotifier {
+
+        fn name(&self) -> String {
+            "smtp".to_string()
+        }
+
+        async fn notify(&self, event: &AlarmEvent) -> Result<(), NotifyError> {
+
+            let mut builder = Message::builder().from(self.from.clone()).subject(event.summary());
+            for to in &self.to {
+                builder = builder.to(to.clone());
+            }
+            let message = builder
+                .body(serde_json::to_string_pretty(event).unwrap())
+                .map_err(|e| NotifyError(e.to_string()))?;
+
+            self.transport.send(message).await.map_err(|e| NotifyError(e.to_string()))?;
+            Ok(())
+        }
+    }
+
+    /// Returns the value of an optional variable, empty values are ignored
+    fn get_optional_var(name: &str) -> Option<String> {
+        env::var(name).ok().filter(|value| !value.is_empty())
+    }
+
+    ///Get the SMTP channel from the NOTIFY_SMTP_* variables.
+    ///Returns None if NOTIFY_SMTP_HOST is not set.
+    fn get_smtp_notifier() -> Option<SmtpNotifier> {
+
+        let host = get_optional_var("NOTIFY_SMTP_HOST")?;
+        let from = get_optional_var("NOTIFY_SMTP_FROM").expect("NOTIFY_SMTP_FROM IS REQUIRED FOR THE SMTP NOTIFIER");
+        let to = get_optional_var("NOTIFY_SMTP_TO").expect("NOTIFY_SMTP_TO IS REQUIRED FOR THE SMTP NOTIFIER");
+
+        let mut builder = match get_optional_var("NOTIFY_SMTP_TLS").as_deref() {
+            Some("false") | Some("0") => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host).expect("INCORRECT NOTIFY_SMTP_HOST"),
+        };
+        if let Some(port) = get_optional_var("NOTIFY_SMTP_PORT") {
+            builder = builder.port(port.parse().expect("INCORRECT NOTIFY_SMTP_PORT"));
+        }
+        if let Some(username) = get_optional_var("NOTIFY_SMTP_USERNAME") {
+            builder = builder.credentials(Credentials::new(username, get_optional_var("NOTIFY_SMTP_PASSWORD").unwrap_or_default()));
+        }
+
+        Some(SmtpNotifier {
+            transport: builder.timeout(Some(HTTP_TIMEOUT)).build(),
+            from: from.parse().expect("INCORRECT NOTIFY_SMTP_FROM"),
+            to: to.split(',').map(|address| address.trim().parse().expect("INCORRECT NOTIFY_SMTP_TO")).collect(),
+        })
+    }
+
+    /// Registers the channels configured with the environment variables
+    /// and starts the dispatcher. Only the first call has an effect.
+    pub async fn init_notifiers() {
+
+        if DISPATCHER.get().is_some() {
+            return;
+        }
+
+        dotenv().ok();
+        let queue_capacity = get_optional_var("NOTIFY_QUEUE_CAPACITY")
+            .map(|value| value.parse().expect("INCORRECT NOTIFY_QUEUE_CAPACITY"))
+            .unwrap_or(DEFAULT_QUEUE_CAPACITY);
+        let retries = get_optional_var("NOTIFY_RETRIES")
+            .map(|value| value.parse().expect("INCORRECT NOTIFY_RETRIES"))
+            .unwrap_or(DEFAULT_RETRIES);
+
+        let client = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build().unwrap();
+        let mut dispatcher = Dispatcher::new(queue_capacity, retries);
+
+        if let Some(notifier) = RedisEventsNotifier::init().await {
+            dispatcher.register(Arc::new(notifier)).await;
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(notifier) = init_mqtt() {
+            dispatcher.register(Arc::new(notifier)).await;
+        }
+        if let Some(notifier) = start_telegram_bot() {
+            dispatcher.register(Arc::new(notifier)).await;
+        }
+        if let Some(url) = get_optional_var("NOTIFY_WEBHOOK_URL") {
+            dispatcher.register(Arc::new(WebhookNotifier { client: client.clone(), url })).await;
+        }
+        if let Some(url) = get_optional_var("NOTIFY_SLACK_WEBHOOK_URL") {
+            dispatcher.register(Arc::new(SlackNotifier { client: client.clone(), url })).await;
+        }
+        if let Some(notifier) = get_smtp_notifier() {
+            dispatcher.register(Arc::new(notifier)).await;
+        }
+        if get_optional_var("NOTIFY_STDOUT").is_some_and(|value| value == "true" || value == "1") {
+            dispatcher.register(Arc::new(StdoutNotifier)).await;
+        }
+
+        let _ = DISPATCHER.set(dispatcher);
+    }
+
+}
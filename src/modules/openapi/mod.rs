@@ -0,0 +1,416 @@
+pub mod openapi {
+
+    use rocket::serde::json::{json, Value};
+    use serde_json::Map;
+
+    /// Page with the interactive documentation of the second version of the API.
+    /// The page loads Redoc and renders the document from /v2/openapi.json.
+    pub const DOCS_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>Air alarms API v2</title>
+</head>
+<body>
+    <redoc spec-url="/v2/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+</body>
+</html>
+"#;
+
+    /// Description of one operation of the API
+    struct Operation {
+        method: &'static str,
+        path: &'static str,
+        summary: &'static str,
+        parameters: Vec<Value>,
+        data: Value,
+        /// Media type of a response without the envelope, None for the enveloped JSON
+        raw: Option<&'static str>,
+        conditional: bool,
+        errors: &'static [u16],
+    }
+
+    /// Reference to a schema from the components of the document
+    fn schema_ref(name: &str) -> Value {
+        json!({ "$ref": format!("#/components/schemas/{}", name) })
+    }
+
+    /// Parameter with the identifier of a region in the path
+    fn region_id_parameter() -> Value {
+        json!({
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "description": "Region identifier",
+            "schema": { "type": "integer", "format": "int32" }
+        })
+    }
+
+    /// All operations of the second version of the API
+    fn operations() -> Vec<Operation> {
+        vec![
+            Operation {
+                method: "get",
+                path: "/regions",
+                summary: "List of all available regions",
+                parameters: vec![json!({
+                    "name": "sort",
+                    "in": "query",
+                    "required": false,
+                    "description": "Order of the regions: by identifier or by English name",
+                    "schema": { "type": "string", "enum": ["id", "name"], "default": "id" }
+                })],
+                data: json!({ "type": "array", "items": schema_ref("Region") }),
+                raw: None,
+                conditional: true,
+                errors: &[400],
+            },
+            Operation {
+                method: "get",
+                path: "/regions/{id}",
+                summary: "One region from the registry",
+                parameters: vec![region_id_parameter()],
+                data: schema_ref("Region"),
+                raw: None,
+                conditional: true,
+                errors: &[404],
+            },
+            Operation {
+                method: "get",
+                path: "/alarms",
+                summary: "Alarm state of the requested regions or of all regions",
+                parameters: vec![
+                    json!({
+                        "name": "ids",
+                        "in": "query",
+                        "required": false,
+                        "description": "Comma-separated region identifiers, the response keeps their order",
+                        "schema": { "type": "string" },
+                        "example": "3,9,31"
+                    }),
+                    json!({
+                        "name": "id",
+                        "in": "query",
+                        "required": false,
+                        "description": "Region identifier, may be repeated",
+                        "schema": { "type": "array", "items": { "type": "integer", "format": "int32" } },
+                        "style": "form",
+                        "explode": true
+                    }),
+                    json!({
+                        "name": "all",
+                        "in": "query",
+                        "required": false,
+                        "description": "Select all regions",
+                        "schema": { "type": "boolean" }
+                    }),
+                ],
+                data: json!({ "type": "array", "items": schema_ref("AlarmState") }),
+                raw: None,
+                conditional: true,
+                errors: &[400],
+            },
+            Operation {
+                method: "get",
+                path: "/alarms/{id}",
+                summary: "Alarm state of one region",
+                parameters: vec![region_id_parameter()],
+                data: schema_ref("AlarmState"),
+                raw: None,
+                conditional: true,
+                errors: &[404],
+            },
+            Operation {
+                method: "get",
+                path: "/source",
+                summary: "Metadata of the last response of the upstream alarm API with the lag of its data and the skew of its clock",
+                parameters: Vec::new(),
+                data: schema_ref("SourceInfo"),
+                raw: None,
+                conditional: false,
+                errors: &[503],
+            },
+            Operation {
+                method: "get",
+                path: "/sources",
+                summary: "Health of the sources of the alarm data and the regions where they disagree",
+                parameters: Vec::new(),
+                data: schema_ref("SourcesStatus"),
+                raw: None,
+                conditional: false,
+                errors: &[503],
+            },
+            Operation {
+                method: "get",
+                path: "/events",
+                summary: "Alarm events as server-sent events: alarm_started, alarm_ended, scope_changed, type_added and type_removed",
+                parameters: Vec::new(),
+                data: json!({ "type": "string", "example": "event: alarm_started\ndata: {\"event\":\"alarm_started\",\"scope\":\"full\",\"region_id\":9}\n\n" }),
+                raw: Some("text/event-stream"),
+                conditional: false,
+                errors: &[],
+            },
+            Operation {
+                method: "get",
+                path: "/iot/status",
+                summary: "Alarm state of all regions as a string with one character per region in the order of /iot/mapping: N - no alarm, P - alarm in a part of the region, A - alarm in the whole region",
+                parameters: Vec::new(),
+                data: json!({ "type": "string", "example": "NNAPNNNNNNNNNNNNNNNNNNNNNNN" }),
+                raw: Some("application/json"),
+                conditional: true,
+                errors: &[],
+            },
+            Operation {
+                method: "get",
+                path: "/iot/status.txt",
+                summary: "The compact alarm state as plain text",
+                parameters: Vec::new(),
+                data: json!({ "type": "string", "example": "NNAPNNNNNNNNNNNNNNNNNNNNNNN" }),
+                raw: Some("text/plain"),
+                conditional: true,
+                errors: &[],
+            },
+            Operation {
+                method: "get",
+                path: "/iot/status.bin",
+                summary: "The compact alarm state as bytes: 0 - no alarm, 1 - partial alarm, 2 - alarm in the whole region",
+                parameters: Vec::new(),
+                data: json!({ "type": "string", "format": "binary" }),
+                raw: Some("application/octet-stream"),
+                conditional: true,
+                errors: &[],
+            },
+            Operation {
+                method: "get",
+                path: "/iot/mapping",
+                summary: "Regions in the order of the compact alarm state",
+                parameters: Vec::new(),
+                data: json!({ "type": "array", "items": schema_ref("StatusIndex") }),
+                raw: None,
+                conditional: true,
+                errors: &[],
+            },
+        ]
+    }
+
+    /// Builds the description of an operation with its responses
+    fn operation_object(operation: &Operation) -> Value {
+
+        let content = match operation.raw {
+            Some(media_type) => json!({ media_type: { "schema": operation.data } }),
+            None => json!({
+                "application/json": {
+                    "schema": {
+                        "type": "object",
+                        "required": ["data", "meta", "errors"],
+                        "properties": {
+                            "data": operation.data,
+                            "meta": schema_ref("Meta"),
+                            "errors": { "type": "array", "items": schema_ref("ApiError") }
+                        }
+                    }
+                }
+            }),
+        };
+
+        let mut responses = Map::new();
+        responses.insert("200".to_string(), json!({
+            "description": "Successful response",
+            "headers": {
+                "ETag": { "schema": { "type": "string" } },
+                "Last-Modified": { "schema": { "type": "string" } }
+            },
+            "content": content
+        }));
+
+        if operation.conditional {
+            responses.insert("304".to_string(), json!({
+                "description": "The representation was not modified since the request with the same ETag or time"
+            }));
+        }
+
+        for status in operation.errors {
+            responses.insert(status.to_string(), json!({ "$ref": "#/components/responses/Error" }));
+        }
+
+        let mut parameters = operation.parameters.clone();
+        if operation.conditional {
+            parameters.push(json!({ "name": "If-None-Match", "in": "header", "required": false, "schema": { "type": "string" } }));
+            parameters.push(json!({ "name": "If-Modified-Since", "in": "header", "required": false, "schema": { "type": "string" } }));
+        }
+
+        json!({
+            "summary": operation.summary,
+            "parameters": parameters,
+            "responses": responses
+        })
+    }
+
+    /// Schemas of the objects returned by the API
+    fn schemas() -> Value {
+        json!({
+            "Region": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer", "format": "int32" },
+                    "type": { "type": "string", "enum": ["oblast", "city", "autonomous_republic"] },
+                    "parent_id": { "type": "integer", "format": "int32", "nullable": true },
+                    "names": {
+                        "type": "object",
+                        "properties": {
+                            "uk": { "type": "string" },
+                            "en": { "type": "string" }
+                        }
+                    },
+                    "codes": {
+                        "type": "object",
+                        "properties": {
+                            "iso_3166_2": { "type": "string" },
+                            "slug": { "type": "string" }
+                        }
+                    }
+                }
+            },
+            "AlarmState": {
+                "type": "object",
+                "properties": {
+                    "region_id": { "type": "integer", "format": "int32" },
+                    "region_name": { "type": "string" },
+                    "alarm_active": { "type": "boolean" },
+                    "alarm_started_at": { "type": "string", "format": "date-time", "nullable": true },
+                    "source": { "type": "string", "enum": ["upstream", "manual"] },
+                    "override_expires_at": { "type": "string", "format": "date-time" },
+                    "note": { "type": "string" }
+                }
+            },
+            "StatusIndex": {
+                "type": "object",
+                "properties": {
+                    "index": { "type": "integer" },
+                    "region_id": { "type": "integer", "format": "int32" },
+                    "name": { "type": "string" },
+                    "slug": { "type": "string" }
+                }
+            },
+            "Meta": {
+                "type": "object",
+                "properties": {
+                    "api_version": { "type": "string" },
+                    "count": { "type": "integer" },
+                    "sort": { "type": "string" },
+                    "snapshot_version": { "type": "integer" },
+                    "last_modified": { "type": "string" },
+                    "upstream_updated_at": { "type": "string" }
+                }
+            },
+            "SourceInfo": {
+                "type": "object",
+                "properties": {
+                    "source": { "type": "string" },
+                    "last_updated_at": { "type": "string", "nullable": true },
+                    "type": { "type": "string", "nullable": true },
+                    "disclaimer": { "type": "string", "nullable": true },
+                    "last_modified": { "type": "string", "nullable": true },
+                    "received_at": { "type": "string", "format": "date-time" },
+                    "lag_seconds": { "type": "integer", "nullable": true },
+                    "skew_seconds": { "type": "integer", "nullable": true },
+                    "status": { "type": "string", "enum": ["ok", "lagging", "skewed", "unknown"] }
+                }
+            },
+            "SourceHealth": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "priority": { "type": "integer" },
+                    "status": { "type": "string", "enum": ["ok", "failing", "stale", "unknown"] },
+                    "active": { "type": "boolean" },
+                    "consecutive_failures": { "type": "integer" },
+                    "last_success": { "type": "string", "format": "date-time", "nullable": true },
+                    "last_error": { "type": "string", "nullable": true },
+                    "lag_seconds": { "type": "integer", "nullable": true },
+                    "alarm_regions": { "type": "array", "items": { "type": "integer", "format": "int32" }, "nullable": true }
+                }
+            },
+            "SourcesStatus": {
+                "type": "object",
+                "properties": {
+                    "mode": { "type": "string", "enum": ["failover", "consensus"] },
+                    "active": { "type": "string", "nullable": true },
+                    "sources": { "type": "array", "items": schema_ref("SourceHealth") },
+                    "disagreement": {
+                        "type": "object",
+                        "nullable": true,
+                        "properties": {
+                            "regions": { "type": "array", "items": { "type": "integer", "format": "int32" } },
+                            "alarm_regions": { "type": "object", "additionalProperties": { "type": "array", "items": { "type": "integer" } } },
+                            "detected_at": { "type": "string", "format": "date-time" }
+                        }
+                    },
+                    "updated_at": { "type": "string", "format": "date-time" }
+                }
+            },
+            "ApiError": {
+                "type": "object",
+                "properties": {
+                    "status": { "type": "integer" },
+                    "code": { "type": "string" },
+                    "detail": { "type": "string" },
+                    "source": {
+                        "type": "object",
+                        "properties": {
+                            "parameter": { "type": "string" },
+                            "token": { "type": "string" },
+                            "position": { "type": "integer" }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Generates the OpenAPI 3 document of the second version of the API
+    pub fn openapi_document() -> Value {
+
+        let mut paths = Map::new();
+        for operation in operations() {
+            let item = paths
+                .entry(operation.path.to_string())
+                .or_insert_with(|| json!({}));
+            item[operation.method] = operation_object(&operation);
+        }
+
+        json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "Air alarms API",
+                "description": "Current state of air raid alarms in the regions of Ukraine",
+                "version": env!("CARGO_PKG_VERSION")
+            },
+            "servers": [{ "url": "/v2" }],
+            "paths": paths,
+            "components": {
+                "schemas": schemas(),
+                "responses": {
+                    "Error": {
+                        "description": "Error response",
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "type": "object",
+                                    "properties": {
+                                        "data": { "nullable": true },
+                                        "meta": schema_ref("Meta"),
+                                        "errors": { "type": "array", "items": schema_ref("ApiError") }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+}
@@ -8,62 +8,118 @@ pub mod other_data {
     /// Struct fo representing info from API about available locations 
     #[derive(Debug, Serialize)]
     pub struct RegionInfo {
-        id: i32,
-        name: String
+        pub id: i32,
+        pub name: String
+    }
+
+    /// Administrative type of a location
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum RegionType {
+        Oblast,
+        City,
+        AutonomousRepublic,
+    }
+
+    /// Names of a location in every supported locale
+    #[derive(Debug, Clone, Serialize)]
+    pub struct RegionNames {
+        pub uk: String,
+        pub en: String,
+    }
+
+    /// Codes that identify a location outside of the alarm API
+    #[derive(Debug, Clone, Serialize)]
+    pub struct RegionCodes {
+        pub iso_3166_2: String,
+        pub slug: String,
+    }
+
+    /// Struct for representing a location from the registry of available locations
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Region {
+        pub id: i32,
+        #[serde(rename = "type")]
+        pub region_type: RegionType,
+        pub parent_id: Option<i32>,
+        pub names: RegionNames,
+        pub codes: RegionCodes,
+    }
+
+    /// Function that returns the registry of all available locations ordered by the location identifier.
+    pub fn get_regions() -> Vec<Region> {
+        use RegionType::*;
+
+        let regions = [
+            (3, Oblast, "Хмельницька область", "Khmelnytskyi Oblast", "UA-68", "khmelnytskyi"),
+            (4, Oblast, "Вінницька область", "Vinnytsia Oblast", "UA-05", "vinnytsia"),
+            (5, Oblast, "Рівненська область", "Rivne Oblast", "UA-56", "rivne"),
+            (8, Oblast, "Волинська область", "Volyn Oblast", "UA-07", "volyn"),
+            (9, Oblast, "Дніпропетровська область", "Dnipropetrovsk Oblast", "UA-12", "dnipropetrovsk"),
+            (10, Oblast, "Житомирська область", "Zhytomyr Oblast", "UA-18", "zhytomyr"),
+            (11, Oblast, "Закарпатська область", "Zakarpattia Oblast", "UA-21", "zakarpattia"),
+            (12, Oblast, "Запорізька область", "Zaporizhzhia Oblast", "UA-23", "zaporizhzhia"),
+            (13, Oblast, "Івано-Франківська область", "Ivano-Frankivsk Oblast", "UA-26", "ivano-frankivsk"),
+            (14, Oblast, "Київська область", "Kyiv Oblast", "UA-32", "kyiv-oblast"),
+            (15, Oblast, "Кіровоградська область", "Kirovohrad Oblast", "UA-35", "kirovohrad"),
+            (16, Oblast, "Луганська область", "Luhansk Oblast", "UA-09", "luhansk"),
+            (17, Oblast, "Миколаївська область", "Mykolaiv Oblast", "UA-48", "mykolaiv"),
+            (18, Oblast, "Одеська область", "Odesa Oblast", "UA-51", "odesa"),
+            (19, Oblast, "Полтавська область", "Poltava Oblast", "UA-53", "poltava"),
+            (20, Oblast, "Сумська область", "Sumy Oblast", "UA-59", "sumy"),
+            (21, Oblast, "Тернопільська область", "Ternopil Oblast", "UA-61", "ternopil"),
+            (22, Oblast, "Харківська область", "Kharkiv Oblast", "UA-63", "kharkiv"),
+            (23, Oblast, "Херсонська область", "Kherson Oblast", "UA-65", "kherson"),
+            (24, Oblast, "Черкаська область", "Cherkasy Oblast", "UA-71", "cherkasy"),
+            (25, Oblast, "Чернігівська область", "Chernihiv Oblast", "UA-74", "chernihiv"),
+            (26, Oblast, "Чернівецька область", "Chernivtsi Oblast", "UA-77", "chernivtsi"),
+            (27, Oblast, "Львівська область", "Lviv Oblast", "UA-46", "lviv"),
+            (28, Oblast, "Донецька область", "Donetsk Oblast", "UA-14", "donetsk"),
+            (29, AutonomousRepublic, "Автономна Республіка Крим", "Autonomous Republic of Crimea", "UA-43", "crimea"),
+            (30, City, "м. Севастополь", "Sevastopol", "UA-40", "sevastopol"),
+            (31, City, "м. Київ", "Kyiv", "UA-30", "kyiv"),
+        ];
+
+        regions
+            .into_iter()
+            .map(|(id, region_type, uk, en, iso_3166_2, slug)| Region {
+                id,
+                region_type,
+                parent_id: None,
+                names: RegionNames { uk: uk.to_string(), en: en.to_string() },
+                codes: RegionCodes { iso_3166_2: iso_3166_2.to_string(), slug: slug.to_string() },
+            })
+            .collect()
+    }
+
+    /// Finds a region by its identifier or slug
+    pub fn find_region<'a>(regions: &'a [Region], token: &str) -> Option<&'a Region> {
+        regions
+            .iter()
+            .find(|region| region.id.to_string() == token || region.codes.slug.eq_ignore_ascii_case(token))
     }
 
     /// Function that returns a hashmap of pairs of i32 and string
     /// where key (i32) is the location identifier and the string is the region name.
     pub fn get_all_cities() -> HashMap<i32, String> {
-        let cities: HashMap<i32, String> = HashMap::from(
-            [
-                (3, "Хмельницька область".to_string()),
-                (4, "Вінницька область".to_string()),
-                (5, "Рівненська область".to_string()),
-                (8, "Волинська область".to_string()),
-                (9, "Дніпропетровська область".to_string()),
-                (10, "Житомирська область".to_string()),
-                (11, "Закарпатська область".to_string()),
-                (12, "Запорізька область".to_string()),
-                (13, "Івано-Франківська область".to_string()),
-                (14, "Київська область".to_string()),
-                (15, "Кіровоградська область".to_string()),
-                (16, "Луганська область".to_string()),
-                (17, "Миколаївська область".to_string()),
-                (18, "Одеська область".to_string()),
-                (19, "Полтавська область".to_string()),
-                (20, "Сумська область".to_string()),
-                (21, "Тернопільська область".to_string()),
-                (22, "Харківська область".to_string()),
-                (23, "Херсонська область".to_string()),
-                (24, "Черкаська область".to_string()),
-                (25, "Чернігівська область".to_string()),
-                (26, "Чернівецька область".to_string()),
-                (27, "Львівська область".to_string()),
-                (28, "Донецька область".to_string()),
-                (29, "Автономна Республіка Крим".to_string()),
-                (30, "м. Севастополь".to_string()),
-                (31, "м. Київ".to_string()),
-            ]
-        );
-        cities
+        get_regions()
+            .into_iter()
+            .map(|region| (region.id, region.names.uk))
+            .collect()
     }
 
-    /// A function that returns data about available locations as a RegionInfo vector.
+    /// A function that returns data about available locations as a RegionInfo vector
+    /// ordered by the location identifier.
     pub fn get_all_cities_as_json() -> Json<Vec<RegionInfo>> {
 
-        let mut result = Vec::<RegionInfo>::new();
-
-        for (region_id, region_name) in get_all_cities() {
-            let _r = RegionInfo{
-                id: region_id,
-                name: region_name
-            };
-            result.push(_r);
-        }
+        let result = get_regions()
+            .into_iter()
+            .map(|region| RegionInfo {
+                id: region.id,
+                name: region.names.uk
+            })
+            .collect();
         Json(result)
     }
 
-
-
-}
\ No newline at end of file
+}
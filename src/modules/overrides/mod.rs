@@ -0,0 +1,226 @@
+pub mod overrides {
+
+    use std::collections::HashMap;
+    use chrono::{DateTime, Duration, Utc};
+    use log::debug;
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use serde_json::json;
+
+    use crate::modules::{
+        audit::audit::{record_audit, SYSTEM_ACTOR},
+        processing::processing::{bump_snapshot_version, set_next_expiry},
+        store::store::{store, StoreResult},
+    };
+
+    /// Hash with the manual alarm states by the region identifier
+    const OVERRIDES_KEY: &str = "overrides";
+
+    /// Hash with the notes by the region identifier
+    const NOTES_KEY: &str = "notes";
+
+    /// Longest override, so a forgotten override does not hide the upstream data for long
+    pub const MAX_OVERRIDE_MINUTES: i64 = 24 * 60;
+
+    /// Alarm state of a region set by hand instead of the state from the alarm API
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Override {
+        pub region_id: i32,
+        pub alarm_active: bool,
+        pub reason: Option<String>,
+        pub created_by: String,
+        pub created_at: String,
+        pub expires_at: String,
+    }
+
+    /// Free-text note shown with the alarm state of a region
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct RegionNote {
+        pub region_id: i32,
+        pub text: String,
+        pub created_by: String,
+        pub created_at: String,
+        /// The note is kept until it is deleted if the time is not set
+        pub expires_at: Option<String>,
+    }
+
+    /// Entries of the store that stop working at some time
+    trait Expiring {
+        fn expires_at(&self) -> Option<&str>;
+
+        fn expiry_time(&self) -> Option<DateTime<Utc>> {
+            self.expires_at()
+                .and_then(|expires_at| DateTime::parse_from_rfc3339(expires_at).ok())
+                .map(|expires_at| expires_at.with_timezone(&Utc))
+        }
+
+        fn is_expired(&self, now: DateTime<Utc>) -> bool {
+            self.expiry_time().is_some_and(|expires_at| expires_at <= now)
+        }
+    }
+
+    impl Expiring for Override {
+        fn expires_at(&self) -> Option<&str> {
+            Some(&self.expires_at)
+        }
+    }
+
+    impl Expiring for RegionNote {
+        fn expires_at(&self) -> Option<&str> {
+            self.expires_at.as_deref()
+        }
+    }
+
+    /// Returns the time after the given number of minutes
+    pub fn expires_after(minutes: i64) -> String {
+        (Utc::now() + Duration::minutes(minutes)).to_rfc3339()
+    }
+
+    /// Reads the entries of a hash that did not expire yet
+    async fn read_active<T: DeserializeOwned + Expiring>(key: &str) -> StoreResult<HashMap<i32, T>> {
+        let now = Utc::now();
+        Ok(store()
+            .hgetall(key)
+            .await?
+            .into_iter()
+            .filter_map(|(region_id, value)| Some((region_id.parse().ok()?, serde_json::from_str::<T>(&value).ok()?)))
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .collect())
+    }
+
+    /// Target of the audit entries of a region
+    fn region_target(region_id: impl std::fmt::Display) -> String {
+        format!("region:{}", region_id)
+    }
+
+    /// Reads an entry of a hash
+    async fn read<T: DeserializeOwned>(key: &str, region_id: i32) -> StoreResult<Option<T>> {
+        let value = store().hget(key, &region_id.to_string()).await?;
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    /// Removes an entry of a hash and returns it.
+    /// Nothing is returned if the entry was removed by another request meanwhile.
+    async fn take<T: DeserializeOwned>(key: &str, region_id: i32) -> StoreResult<Option<T>> {
+        let entry = read(key, region_id).await?;
+        if entry.is_some() && !store().hdel(key, &region_id.to_string()).await? {
+            return Ok(None);
+        }
+        Ok(entry)
+    }
+
+    /// Returns the earliest expiry time of the entries of a hash
+    async fn next_expiry<T: DeserializeOwned + Expiring>(key: &str) -> StoreResult<Option<DateTime<Utc>>> {
+        Ok(read_active::<T>(key).await?.values().filter_map(Expiring::expiry_time).min())
+    }
+
+    /// Saves the time when the next override or note expires
+    async fn save_next_expiry() -> StoreResult<()> {
+        let overrides = next_expiry::<Override>(OVERRIDES_KEY).await?;
+        let notes = next_expiry::<RegionNote>(NOTES_KEY).await?;
+        set_next_expiry(overrides.into_iter().chain(notes).min()).await
+    }
+
+    /// Changes both validators of the alarm data (the version and the time of the last modification)
+    /// after a change of the overrides or the notes, so the clients do not keep the cached state
+    async fn commit_change() -> StoreResult<()> {
+        save_next_expiry().await?;
+        bump_snapshot_version(None).await
+    }
+
+    /// Forces the alarm state of a region until the override expires
+    pub async fn set_override(entry: &Override) -> StoreResult<()> {
+        let previous: Option<Override> = read(OVERRIDES_KEY, entry.region_id).await?;
+        store().hset(OVERRIDES_KEY, &entry.region_id.to_string(), &serde_json::to_string(entry).unwrap()).await?;
+        commit_change().await?;
+        record_audit(&entry.created_by, "override.set", &region_target(entry.region_id), previous.map(|previous| json!(previous)), Some(json!(entry))).await;
+        Ok(())
+    }
+
+    /// Removes the override of a region, the state from the alarm API is used again
+    pub async fn remove_override(region_id: i32, removed_by: &str) -> StoreResult<Option<Override>> {
+        let removed: Option<Override> = take(OVERRIDES_KEY, region_id).await?;
+        if let Some(entry) = &removed {
+            commit_change().await?;
+            record_audit(removed_by, "override.remove", &region_target(region_id), Some(json!(entry)), None).await;
+        }
+        Ok(removed)
+    }
+
+    /// Returns the overrides that did not expire yet
+    pub async fn get_overrides() -> StoreResult<HashMap<i32, Override>> {
+        read_active(OVERRIDES_KEY).await
+    }
+
+    /// Same as get_overrides(), without overrides if the store is not available
+    pub async fn active_overrides() -> HashMap<i32, Override> {
+        get_overrides().await.unwrap_or_default()
+    }
+
+    /// Attaches a note to a region, the previous note of the region is replaced
+    pub async fn set_note(note: &RegionNote) -> StoreResult<()> {
+        let previous: Option<RegionNote> = read(NOTES_KEY, note.region_id).await?;
+        store().hset(NOTES_KEY, &note.region_id.to_string(), &serde_json::to_string(note).unwrap()).await?;
+        commit_change().await?;
+        record_audit(&note.created_by, "note.set", &region_target(note.region_id), previous.map(|previous| json!(previous)), Some(json!(note))).await;
+        Ok(())
+    }
+
+    /// Removes the note of a region
+    pub async fn remove_note(region_id: i32, removed_by: &str) -> StoreResult<Option<RegionNote>> {
+        let removed: Option<RegionNote> = take(NOTES_KEY, region_id).await?;
+        if let Some(note) = &removed {
+            commit_change().await?;
+            record_audit(removed_by, "note.remove", &region_target(region_id), Some(json!(note)), None).await;
+        }
+        Ok(removed)
+    }
+
+    /// Returns the notes that did not expire yet
+    pub async fn get_notes() -> StoreResult<HashMap<i32, RegionNote>> {
+        read_active(NOTES_KEY).await
+    }
+
+    /// Removes the expired entries of a hash and the entries that can not be read,
+    /// returns the removed entries with their regions
+    async fn remove_expired<T: DeserializeOwned + Expiring>(key: &str) -> StoreResult<Vec<(String, Option<T>)>> {
+        let now = Utc::now();
+        let mut removed = Vec::new();
+        for (region_id, value) in store().hgetall(key).await? {
+            let entry = serde_json::from_str::<T>(&value).ok();
+            // Only the caller that has removed the entry returns it, when several readers expire it at once
+            if entry.as_ref().is_none_or(|entry| entry.is_expired(now)) && store().hdel(key, &region_id).await? {
+                removed.push((region_id, entry));
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes the expired overrides and notes and changes the validators of the alarm data if any were removed.
+    /// It is called by the poller and by the readers of the alarm data when the next expiry time has come,
+    /// so the clients get the new state as soon as an override ends, not at the next poll.
+    pub async fn expire_overrides() {
+
+        let result = async {
+            let overrides = remove_expired::<Override>(OVERRIDES_KEY).await?;
+            let notes = remove_expired::<RegionNote>(NOTES_KEY).await?;
+            for (region_id, entry) in &overrides {
+                record_audit(SYSTEM_ACTOR, "override.expire", &region_target(region_id), entry.as_ref().map(|entry| json!(entry)), None).await;
+            }
+            for (region_id, note) in &notes {
+                record_audit(SYSTEM_ACTOR, "note.expire", &region_target(region_id), note.as_ref().map(|note| json!(note)), None).await;
+            }
+            if overrides.is_empty() && notes.is_empty() {
+                save_next_expiry().await?;
+            } else {
+                commit_change().await?;
+            }
+            StoreResult::Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            debug!("{} - error removing the expired overrides\n{}", chrono::Local::now(), e);
+        }
+    }
+
+}
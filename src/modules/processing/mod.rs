@@ -1,51 +1,181 @@
 pub mod processing {
 
-    use dotenv::dotenv;
+    use std::collections::{HashMap, HashSet};
     use std::env;
-    use std::collections::HashSet;
-    use std::time::Duration;
-    use tokio::sync::Mutex; 
-    use std::sync::Arc;
+    use chrono::{DateTime, Utc};
+    use dotenv::dotenv;
     use tokio::task;
-    use redis;
-    use redis::{Client, AsyncCommands, RedisResult};
     use log::{debug, info};
+    use serde::{Deserialize, Serialize};
+
+    use crate::modules::{
+        data_actions::data_actions::{deserialize_current_alarms_data, CurrentAlarm, UpstreamMeta},
+        fetch_data::fetch_data::poll_interval,
+        audit::audit::prune_audit,
+        diff::diff::{diff, AlarmEventKind, AlarmSnapshot, AlertRecord, RegionAlarm, AIR_RAID},
+        leader::leader::{confirm_leadership, leader_status, write_as_leader},
+        metrics::metrics::record_events,
+        notifications::notifications::dispatch,
+        other_data::other_data::get_all_cities,
+        overrides::overrides::{active_overrides, expire_overrides},
+        store::store::{store, HashWrite, StoreResult},
+    };
+    #[cfg(feature = "mqtt")]
+    use crate::modules::mqtt::mqtt::sync_mqtt_state;
+
+    /// Number of state changes kept in the history of every location
+    const HISTORY_LENGTH: usize = 50;
+
+    /// Hash with the alerts received with the last poll
+    const SNAPSHOT_KEY: &str = "alarm_snapshot";
+
+    /// Hash with the metadata of the alarm API
+    const SOURCE_KEY: &str = "source";
+
+    /// Struct for representing a change of the alarm state in a location
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct AlarmTransition {
+        pub location_uid: i32,
+        pub is_active_air_alarm: bool,
+        pub changed_at: String,
+    }
 
-    use crate::modules::{data_actions::data_actions::{deserialize_current_alarms_data, CurrentAlarm}, other_data::other_data::get_all_cities};
+    /// Part of a location covered by the active alarm
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum AlarmScope {
+        /// The alarm covers the whole location
+        Full,
+        /// The alarm covers only some raions or hromadas of the location
+        Partial,
+    }
 
-    ///Get the Redis-host
-    fn get_redis_host() -> String {
-        dotenv().ok();
-        let host = env::var("REDIS_HOST").expect("INCORRECT REDIS ADDRESS");
-        host
+    impl AlarmScope {
+
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                AlarmScope::Full => "full",
+                AlarmScope::Partial => "partial",
+            }
+        }
+
+        pub fn parse(value: &str) -> Option<Self> {
+            match value {
+                "full" => Some(AlarmScope::Full),
+                "partial" => Some(AlarmScope::Partial),
+                _ => None,
+            }
+        }
     }
-    
-    ///Get the password for Redis connection
-    fn get_redis_passwd() -> String {
-        dotenv().ok();
-        let pwd = env::var("REDIS_PASSWD").expect("THE REDIS PASSWORD IS UNAVAILABLE");
-        pwd
+
+    /// Struct for representing the version of the alarm data in the store
+    #[derive(Debug, Clone, Default)]
+    pub struct SnapshotInfo {
+        pub version: u64,
+        pub last_modified: Option<String>,
+        pub last_poll: Option<String>,
+        pub upstream_updated_at: Option<String>,
     }
 
-    fn get_redis_usr() -> String {
+    /// Struct for representing the metadata of the last response of the alarm API
+    /// and the result of its timestamp checks
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    pub struct SourceInfo {
+        /// Name of the source of the alarm data
+        pub source: String,
+        /// Time of the data update reported in the "meta" of the response
+        pub last_updated_at: Option<String>,
+        #[serde(rename = "type")]
+        pub meta_type: Option<String>,
+        pub disclaimer: Option<String>,
+        /// Value of the "last-modified" header of the response
+        pub last_modified: Option<String>,
+        pub received_at: String,
+        /// Seconds between the data update and the receipt of the response
+        pub lag_seconds: Option<i64>,
+        /// Seconds between the data update and the "last-modified" header
+        pub skew_seconds: Option<i64>,
+        /// "ok", "lagging", "skewed" or "unknown" if the timestamps can not be compared
+        pub status: String,
+    }
+
+    /// Parses the time formats used by the alarm API:
+    /// RFC 3339, RFC 2822 (HTTP headers) and "2024/01/31 10:00:00 +0000" of the "meta"
+    pub fn parse_upstream_time(value: &str) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(value)
+            .or_else(|_| DateTime::parse_from_rfc2822(value))
+            .or_else(|_| DateTime::parse_from_str(value, "%Y/%m/%d %H:%M:%S %z"))
+            .ok()
+            .map(|time| time.with_timezone(&Utc))
+    }
+
+    fn threshold_from_env(name: &str, default: i64) -> i64 {
         dotenv().ok();
-        let usr = env::var("REDIS_USR").expect("THE REDIS USER IS UNAVAILABLE");
-        usr
+        env::var(name)
+            .map(|value| value.parse().unwrap_or_else(|_| panic!("INCORRECT {}", name)))
+            .unwrap_or(default)
+    }
+
+    impl SourceInfo {
+
+        /// Compares the time of the data update with the time of the receipt
+        /// and with the "last-modified" header.
+        /// The upstream lags if the data is older than max_lag seconds
+        /// and its clock is skewed if the header differs from the data by more than max_skew seconds.
+        pub fn new(meta: &UpstreamMeta, last_modified: &str, received_at: DateTime<Utc>, max_lag: i64, max_skew: i64) -> Self {
+
+            let updated_at = meta.last_updated_at.as_deref().and_then(parse_upstream_time);
+            let lag_seconds = updated_at.map(|updated_at| (received_at - updated_at).num_seconds());
+            let skew_seconds = updated_at
+                .zip(parse_upstream_time(last_modified))
+                .map(|(updated_at, modified_at)| (modified_at - updated_at).num_seconds());
+
+            let status = match (lag_seconds, skew_seconds) {
+                (None, _) => "unknown",
+                (_, Some(skew)) if skew.abs() > max_skew => "skewed",
+                (Some(lag), _) if lag > max_lag => "lagging",
+                _ => "ok",
+            };
+
+            SourceInfo {
+                source: meta.source.clone(),
+                last_updated_at: meta.last_updated_at.clone(),
+                meta_type: meta.meta_type.clone(),
+                disclaimer: meta.disclaimer.clone(),
+                last_modified: Some(last_modified.to_string()).filter(|value| !value.is_empty()),
+                received_at: received_at.to_rfc3339(),
+                lag_seconds,
+                skew_seconds,
+                status: status.to_string(),
+            }
+        }
+
+        /// Same as new() with the thresholds from UPSTREAM_MAX_LAG_SECS (300 by default)
+        /// and UPSTREAM_MAX_SKEW_SECS (60 by default)
+        pub fn checked(meta: &UpstreamMeta, last_modified: &str, received_at: DateTime<Utc>) -> Self {
+            Self::new(
+                meta,
+                last_modified,
+                received_at,
+                threshold_from_env("UPSTREAM_MAX_LAG_SECS", 300),
+                threshold_from_env("UPSTREAM_MAX_SKEW_SECS", 60),
+            )
+        }
     }
 
-    
 
     /// Function for checking location alarm.
     /// To determine the location, its ID is used.
+    /// The manual override of the location is used instead of the stored state.
     pub async fn check_alarm(location_id: i32) -> bool {
 
-        let conn_str = format!("redis://{}:{}@{}:6380/0", get_redis_usr(), get_redis_passwd(), get_redis_host());
-        let client = Client::open(conn_str);
-        let conn = client.unwrap().get_multiplexed_async_connection().await;
+        if let Some(entry) = active_overrides().await.get(&location_id) {
+            return entry.alarm_active;
+        }
 
-        //Getting value by location_id key.
-        let value: RedisResult<Option<String>> = conn.unwrap().hget("regions", location_id).await;
-        let result = if value.unwrap().unwrap() == "true" {true} else {false};
+        //Getting value by location_id key, a location without data (before the first poll) has no alarm
+        let value = store().hget("regions", &location_id.to_string()).await;
+        let result = value.ok().flatten().map(|value| value == "true").unwrap_or(false);
 
         debug!("{} - the current state of alarm on location {:?} is {}", chrono::Local::now(), location_id, result);
         result
@@ -57,14 +187,11 @@ pub mod processing {
     /// as parameters to the function.
     pub async fn set_alarm_status(location_id: i32, new_status: bool) {
 
-        let conn_str = format!("redis://{}:{}@{}:6380/0", get_redis_usr(), get_redis_passwd(), get_redis_host());
-        let client = Client::open(conn_str);
-        let mut conn = client.unwrap().get_multiplexed_async_connection().await.unwrap();
         let new_status = if new_status { "true".to_string() } else { "false".to_string() };
 
 
         // Calling the hset function, which is passed the key "regions",region ID and new alarm value
-        let result: RedisResult<()> = conn.hset("regions", location_id, new_status.clone()).await;
+        let result = store().hset("regions", &location_id.to_string(), &new_status).await;
         debug!("{} - trying to set new alarm status - {} on location {}", chrono::Local::now(), new_status, location_id);
 
         // Checking if the new value was set successfully
@@ -79,113 +206,408 @@ pub mod processing {
 
     }
 
-    /// The function in an infinite loop
-    /// makes a request using the alarm API
-    /// and asynchronously updates the data in Redis
-    pub async fn update_data() {
+    /// Returns the alarm state of all locations with the manual overrides.
+    /// Locations that were never updated and have no override are absent in the result.
+    pub async fn get_all_alarm_statuses() -> HashMap<i32, bool> {
 
-        let interval: i32 = (60/9) as i32;
-
-        /*
-            Initialization with an empty variable value.
-            Later, the value of the "last-modified" header is stored
-            in this variable. The value of the variable changes
-            when a response containing this header
-            is received from the server. 
-        */
-        let updated_date_time = Arc::new(Mutex::new(String::from("")));
-        
-        loop {
+        let mut statuses = get_upstream_alarm_statuses().await;
+        for (location_id, entry) in active_overrides().await {
+            statuses.insert(location_id, entry.alarm_active);
+        }
+        statuses
+    }
 
-            // Set the pause of the current thread in the loop
-            tokio::time::sleep(Duration::from_secs(interval as u64)).await;
-            debug!("{} - getting new data for air alarms", chrono::Local::now());
-
-
-            //Getting regions ids(keys) of all available regions from get_all_cities()
-            let all_available_regions_keys: HashSet<i32> = get_all_cities().keys().cloned().collect();
-            let mut current_alerts = Vec::<CurrentAlarm>::new();
-
-            debug!("{} - creating task for update data", chrono::Local::now());
-
-            let updated_date_time_clone = Arc::clone(&updated_date_time);
-            let update_data_task = task::spawn(async move {
-
-            let mut updated_date_time_lock = updated_date_time_clone.lock().await;
-
-
-                /*
-                    If there was an update of information on the server,data on current alarms is received. 
-                    The data is placed in the variables current_alerts and updated_date_time_lock,
-                    where current_alert is a vector of CurrentAlarm structures, 
-                    and updated_at is the time of the last data change on the server.
-                */
-                let data = deserialize_current_alarms_data(updated_date_time_lock.clone().to_string()).await.unwrap();
-                debug!("{} - getting for new data \t{}\n{:?}", chrono::Local::now(), data.1, data.0);
-
-                debug!("{} - updating date_time from new value - {}", chrono::Local::now(), data.1);
-                *updated_date_time_lock = data.1.clone(); 
-
-                debug!("{} - getting current alerts - {:?}", chrono::Local::now(), data.0);
-                current_alerts = data.0.clone();
-
-
-                //Getting region IDs where the alarm is active
-                let _current_alarm_location_ids: HashSet<i32> = {
-
-                    let mut result = HashSet::<i32>::new();
-                    for _v in current_alerts {
-                        result.insert(_v._location_oblast_uid.into());
-                    }
-                    result
-
-                };
-                debug!("{} - regions with active alerts - {:?}", chrono::Local::now(), _current_alarm_location_ids);
-                
-                //Getting region IDs where the alarm is not active
-                let _incactive_alarm_location_ids: HashSet<_> = all_available_regions_keys
-                    .difference(&_current_alarm_location_ids)
-                    .cloned()
-                    .collect();
-                debug!("{} - regions with inactive alerts - {:?}", chrono::Local::now(), _incactive_alarm_location_ids);
-                
-
-                //Update data using tasks
-                for _v in _current_alarm_location_ids {
-                    match task::spawn(
-                        set_alarm_status(_v, true)
-                    ).await {
-                        Ok(_) => {
-                            debug!("{:?} - current alarm in region {} updated", chrono::Local::now(), _v);
-                        },
-                        Err(e) => info!("Error occurred: {:?}", e),
-                    }
+    /// Returns the alarm state of all locations from the alarm API as it is saved by the poller
+    pub async fn get_upstream_alarm_statuses() -> HashMap<i32, bool> {
+
+        store()
+            .hgetall("regions")
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(location_id, value)| Some((location_id.parse().ok()?, value == "true")))
+            .collect()
+    }
+
+    /// Returns the time when the current alarm was started
+    /// for all locations with an active alarm, including the manual overrides.
+    /// An alarm started by an override starts at the time of the override.
+    pub async fn get_all_alarm_start_times() -> HashMap<i32, String> {
+
+        let mut start_times = get_upstream_alarm_start_times().await;
+        for (location_id, entry) in active_overrides().await {
+            match entry.alarm_active {
+                true => {
+                    start_times.entry(location_id).or_insert(entry.created_at);
                 }
-                for _z in _incactive_alarm_location_ids {
-                    match task::spawn(
-                        set_alarm_status(_z, false)
-                    ).await {
-                        Ok(_) => {
-                            debug!("{:?} - current alarm in region {} updated", chrono::Local::now(), _z);
-                        },
-                        Err(e) => info!("Error occurred: {:?}", e),
-                    }
+                false => {
+                    start_times.remove(&location_id);
                 }
+            }
+        }
+        start_times
+    }
 
-            });
+    /// Returns the start times saved by the poller
+    pub async fn get_upstream_alarm_start_times() -> HashMap<i32, String> {
 
-            match update_data_task.await {
-                Ok(_) => {
+        store()
+            .hgetall("regions_started_at")
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(location_id, value)| Some((location_id.parse().ok()?, value)))
+            .collect()
+    }
 
-                    debug!("{} - data update was succesfully", chrono::Local::now());
+    /// Sets the time when the current alarm was started in a location.
+    /// The value is removed if the alarm is not active.
+    pub async fn set_alarm_started_at(location_id: i32, started_at: Option<String>) {
+
+        let result = match started_at {
+            Some(value) => store().hset("regions_started_at", &location_id.to_string(), &value).await,
+            None => store().hdel("regions_started_at", &location_id.to_string()).await.map(|_| ()),
+        };
+
+        if let Err(e) = result {
+            debug!("{} - {} - error updating alarm start time\n{}", chrono::Local::now(), location_id, e);
+        }
+    }
+
+    /// Returns the scope of the alarm for all locations with an active alarm, including the manual overrides.
+    /// An alarm started by an override covers the whole location.
+    pub async fn get_all_alarm_scopes() -> HashMap<i32, AlarmScope> {
+
+        let mut scopes = get_upstream_alarm_scopes().await;
+        for (location_id, entry) in active_overrides().await {
+            match entry.alarm_active {
+                true => {
+                    scopes.entry(location_id).or_insert(AlarmScope::Full);
+                }
+                false => {
+                    scopes.remove(&location_id);
+                }
+            }
+        }
+        scopes
+    }
+
+    /// Returns the scopes saved by the poller
+    pub async fn get_upstream_alarm_scopes() -> HashMap<i32, AlarmScope> {
+
+        store()
+            .hgetall("regions_scope")
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(location_id, value)| Some((location_id.parse().ok()?, AlarmScope::parse(&value)?)))
+            .collect()
+    }
+
+    /// Sets the scope of the alarm in a location.
+    /// The value is removed if the alarm is not active.
+    pub async fn set_alarm_scope(location_id: i32, scope: Option<AlarmScope>) {
+
+        let result = match scope {
+            Some(value) => store().hset("regions_scope", &location_id.to_string(), value.as_str()).await,
+            None => store().hdel("regions_scope", &location_id.to_string()).await.map(|_| ()),
+        };
+
+        if let Err(e) = result {
+            debug!("{} - {} - error updating alarm scope\n{}", chrono::Local::now(), location_id, e);
+        }
+    }
+
+    /// Adds a state change to the history of a location.
+    /// Only the last HISTORY_LENGTH changes are kept.
+    pub async fn push_alarm_history(transition: &AlarmTransition) {
+
+        let key = format!("history:{}", transition.location_uid);
+        let value = serde_json::to_string(transition).unwrap();
+
+        if let Err(e) = store().lpush_capped(&key, &value, HISTORY_LENGTH).await {
+            debug!("{} - {} - error updating alarm history\n{}", chrono::Local::now(), transition.location_uid, e);
+        }
+    }
+
+    /// Returns the last state changes of a location, newest first.
+    pub async fn get_alarm_history(location_id: i32) -> Vec<AlarmTransition> {
+
+        store()
+            .lrange(&format!("history:{}", location_id), 0, HISTORY_LENGTH as isize - 1)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .collect()
+    }
+
+    /// Fields of the stored alarm data written after a poll: the state, the start time and the scope of every region,
+    /// the alerts of the poll, the time of the data update reported by the API and the time when the data was received.
+    /// The validators are changed after the write (see bump_snapshot_version).
+    fn poll_writes(
+        region_ids: &HashSet<i32>,
+        region_alarms: &HashMap<i32, RegionAlarm>,
+        snapshot: &AlarmSnapshot,
+        upstream_updated_at: Option<&str>,
+    ) -> Vec<HashWrite> {
+
+        let write = |key: &str, field: String, value: Option<String>| (key.to_string(), field, value);
+        let mut writes = Vec::new();
+
+        for region_id in region_ids.union(&region_alarms.keys().cloned().collect()) {
+            let alarm = region_alarms.get(region_id);
+            writes.push(write("regions", region_id.to_string(), Some(alarm.is_some().to_string())));
+            writes.push(write("regions_started_at", region_id.to_string(), alarm.map(|alarm| alarm.started_at.clone())));
+            writes.push(write("regions_scope", region_id.to_string(), alarm.map(|alarm| alarm.scope.as_str().to_string())));
+        }
+
+        writes.push(write(SNAPSHOT_KEY, "alerts".to_string(), Some(serde_json::to_string(snapshot).unwrap())));
+        writes.push(write("status", "last_poll".to_string(), Some(chrono::Utc::now().to_rfc3339())));
+        writes.push(write("status", "upstream_updated_at".to_string(), upstream_updated_at.map(str::to_string)));
+        if let Some(token) = leader_status().fencing_token {
+            writes.push(write("status", "fencing_token".to_string(), Some(token.to_string())));
+        }
+        writes
+    }
+
+    /// Changes both validators of the stored alarm data after it is changed, in one step of the store:
+    /// increments the version and moves the time of the last modification forward.
+    /// The time is the "last-modified" header of the alarm API after a poll, if it is later than the last change,
+    /// otherwise the current time, so a client with "If-Modified-Since" gets every change,
+    /// the changes made outside of the poll (for example by an override) included.
+    pub async fn bump_snapshot_version(upstream_time: Option<DateTime<Utc>>) -> StoreResult<()> {
+        store().bump_version("status", upstream_time.map(|time| time.timestamp())).await.map(|_| ())
+    }
+
+    /// Formats the time of the last modification as the value of the "Last-Modified" header
+    fn http_date(timestamp: i64) -> Option<String> {
+        DateTime::from_timestamp(timestamp, 0).map(|time| time.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    }
+
+    /// Saves the time when the next override or note expires.
+    /// The readers remove the expired entries at that time (see get_snapshot_info),
+    /// so the validators change together with the data they see, not at the next poll.
+    pub async fn set_next_expiry(next_expiry: Option<DateTime<Utc>>) -> StoreResult<()> {
+        match next_expiry {
+            Some(time) => store().hset("status", "next_expiry", &time.to_rfc3339()).await,
+            None => store().hdel("status", "next_expiry").await.map(|_| ()),
+        }
+    }
+
+    /// Returns the version of the stored alarm data, the time of its last modification
+    /// as the value of the "Last-Modified" header and the time when the data was received.
+    pub async fn get_snapshot_info() -> SnapshotInfo {
+
+        let mut values = store().hgetall("status").await.unwrap_or_default();
+
+        // An override or a note has expired: it is removed before the validators are read
+        let next_expiry = values.get("next_expiry").and_then(|value| parse_upstream_time(value));
+        if next_expiry.is_some_and(|time| time <= Utc::now()) {
+            expire_overrides().await;
+            values = store().hgetall("status").await.unwrap_or_default();
+        }
+
+        SnapshotInfo {
+            version: values.get("version").and_then(|value| value.parse().ok()).unwrap_or(0),
+            last_modified: values.get("modified_at").and_then(|value| value.parse().ok()).and_then(http_date),
+            last_poll: values.remove("last_poll"),
+            upstream_updated_at: values.remove("upstream_updated_at"),
+        }
+    }
+
+    /// Saves the metadata of the last response of the alarm API
+    pub async fn set_source_info(info: &SourceInfo) {
+        if let Err(e) = store().hset(SOURCE_KEY, "info", &serde_json::to_string(info).unwrap()).await {
+            debug!("{} - error saving the metadata of the alarm API\n{}", chrono::Local::now(), e);
+        }
+    }
+
+    /// Returns the metadata of the last response of the alarm API,
+    /// None before the first response
+    pub async fn get_source_info() -> Option<SourceInfo> {
+        let value = store().hget(SOURCE_KEY, "info").await.ok()??;
+        serde_json::from_str(&value).ok()
+    }
+
+    /// Returns the alerts saved with the last poll.
+    /// Before the first saved snapshot the alerts are restored from the alarm state of the regions,
+    /// so the upgrade of the service does not repeat the events of the active alarms.
+    pub async fn get_alarm_snapshot() -> AlarmSnapshot {
+
+        if let Ok(Some(value)) = store().hget(SNAPSHOT_KEY, "alerts").await {
+            if let Ok(snapshot) = serde_json::from_str(&value) {
+                return snapshot;
+            }
+        }
+
+        let start_times = get_upstream_alarm_start_times().await;
+        let scopes = get_upstream_alarm_scopes().await;
+        let alerts = get_upstream_alarm_statuses()
+            .await
+            .into_iter()
+            .filter(|(_, is_active)| *is_active)
+            .map(|(location_id, _)| {
+                let is_full = scopes.get(&location_id).copied().unwrap_or(AlarmScope::Full) == AlarmScope::Full;
+                AlertRecord {
+                    id: None,
+                    region_id: location_id,
+                    location_uid: if is_full { location_id.to_string() } else { String::new() },
+                    location_type: if is_full { "oblast".to_string() } else { "raion".to_string() },
+                    alert_type: AIR_RAID.to_string(),
+                    started_at: start_times.get(&location_id).cloned().unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        AlarmSnapshot { alerts }
+    }
+
+    fn alert_record(alert: &CurrentAlarm) -> AlertRecord {
+        AlertRecord {
+            id: Some(alert._id),
+            region_id: alert._location_oblast_uid.into(),
+            location_uid: alert._location_uid.clone(),
+            location_type: alert._location_type.clone(),
+            alert_type: alert._alert_type.clone(),
+            started_at: alert._started_at.clone(),
+        }
+    }
+
+    /// Polls the sources of the alarm data once and updates the data in the store.
+    /// Returns true if new data was received and saved.
+    pub async fn poll_once() -> bool {
+
+        // Only the leader polls the alarm API, the followers serve the data from the store
+        if !confirm_leadership().await {
+            debug!("{} - the instance is a follower, polling skipped", chrono::Local::now());
+            return false;
+        }
+        expire_overrides().await;
+        prune_audit().await;
+        debug!("{} - getting new data for air alarms", chrono::Local::now());
 
-                },
+
+        //Getting regions ids(keys) of all available regions from get_all_cities()
+        let all_available_regions = get_all_cities();
+        let all_available_regions_keys: HashSet<i32> = all_available_regions.keys().cloned().collect();
+
+        debug!("{} - creating task for update data", chrono::Local::now());
+
+        let update_data_task = task::spawn(async move {
+
+            /*
+                If there was an update of information on the server,data on current alarms is received. 
+                The sources keep the value of the "last-modified" header themselves,
+                data.1 is the time of the last data change on the server.
+            */
+            let data = match deserialize_current_alarms_data().await {
+                Ok(data) => data,
                 Err(e) => {
+                    debug!("{} - no new data for air alarms\n{}", chrono::Local::now(), e);
+                    return false;
+                }
+            };
+            debug!("{} - getting for new data from {} \t{}\n{:?}", chrono::Local::now(), data.2.source, data.1, data.0);
 
-                    debug!("{} - data was not updated - {:?}", chrono::Local::now(), e);
+            debug!("{} - getting current alerts - {:?}", chrono::Local::now(), data.0);
+            let current_alerts: Vec<CurrentAlarm> = data.0.clone();
+
+            //Checking the time of the data update reported by the alarm API
+            let source_info = SourceInfo::checked(&data.2, &data.1, Utc::now());
+            let previous_status = get_source_info().await.map(|info| info.status);
+            if source_info.status != "ok" && previous_status.as_ref() != Some(&source_info.status) {
+                info!("{} - the alarm API is {}: lag {:?} s, skew {:?} s", chrono::Local::now(), source_info.status, source_info.lag_seconds, source_info.skew_seconds);
+            }
+            set_source_info(&source_info).await;
+
+
+            //Comparing the alerts with the alerts of the previous poll
+            let next_snapshot = AlarmSnapshot { alerts: current_alerts.iter().map(alert_record).collect() };
+            let previous_snapshot = get_alarm_snapshot().await;
+            let events = diff(&previous_snapshot, &next_snapshot, &all_available_regions, &chrono::Utc::now().to_rfc3339());
+            let region_alarms = next_snapshot.region_alarms();
+
+            //Getting region IDs where the alarm is active
+            let _current_alarm_location_ids: HashSet<i32> = region_alarms.keys().cloned().collect();
+            debug!("{} - regions with active alerts - {:?}", chrono::Local::now(), _current_alarm_location_ids);
+
+            /*
+                The regions, the alerts and the time of the update are written in one step fenced by the token of the leader,
+                so a poller that has lost its lease while it was fetching the data can not overwrite the data of the new leader.
+                The version changes only after the write, otherwise a request in between
+                gets the previous data with the new ETag and keeps it until the next change.
+            */
+            let writes = poll_writes(&all_available_regions_keys, &region_alarms, &next_snapshot, data.2.last_updated_at.as_deref());
+            match write_as_leader(&writes).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("{} - the lease is lost, the data of the poll is dropped", chrono::Local::now());
+                    return false;
+                }
+                Err(e) => {
+                    debug!("{} - error saving the alarm data\n{}", chrono::Local::now(), e);
+                    return false;
+                }
+            }
+            if let Err(e) = bump_snapshot_version(parse_upstream_time(&data.1)).await {
+                debug!("{} - error updating the version of the alarm data\n{}", chrono::Local::now(), e);
+            }
 
+            //Saving the changes of the alarm state and sending the events
+            for event in &events {
+                debug!("{} - alarm event - {:?}", chrono::Local::now(), event);
+                if matches!(event.kind, AlarmEventKind::Started { .. } | AlarmEventKind::Ended) {
+                    push_alarm_history(&AlarmTransition {
+                        location_uid: event.region_id,
+                        is_active_air_alarm: event.alarm_active,
+                        changed_at: event.changed_at.clone(),
+                    }).await;
                 }
+                dispatch(event);
             }
+            record_events(&events).await;
+            true
+
+        });
+
+        let updated = match update_data_task.await {
+            Ok(updated) => {
+
+                if updated {
+                    debug!("{} - data update was succesfully", chrono::Local::now());
+                }
+                updated
+
+            },
+            Err(e) => {
+
+                debug!("{} - data was not updated - {:?}", chrono::Local::now(), e);
+                false
+
+            }
+        };
+
+        // The manual overrides change the alarm state without alarm events
+        #[cfg(feature = "mqtt")]
+        sync_mqtt_state().await;
+
+        updated
+
+    }
+
+    /// The function in an infinite loop
+    /// makes a request using the alarm API
+    /// and asynchronously updates the data in the store
+    pub async fn update_data() {
+
+        let interval = poll_interval();
+
+        loop {
+
+            // Set the pause of the current thread in the loop
+            tokio::time::sleep(interval).await;
+            poll_once().await;
 
         }
 
@@ -0,0 +1,460 @@
+pub mod providers {
+
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::env;
+    use std::path::PathBuf;
+    use chrono::{DateTime, Utc};
+    use dotenv::dotenv;
+    use log::{debug, info};
+    use once_cell::sync::Lazy;
+    use reqwest::StatusCode;
+    use rocket::{get, routes, Route, http::Status, serde::json::Json};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use tokio::sync::Mutex;
+
+    use crate::modules::{
+        api_keys::api_keys::ApiKey,
+        api_v2::api_v2::{error_response, Envelope, ErrorResponse, Meta},
+        data_actions::data_actions::UpstreamMeta,
+        diff::diff::AIR_RAID,
+        fetch_data::fetch_data::{alert_sources, data_views::AlertsResponseResult, AlertSource, RawResponse, SourceError, HTTP_POLL_INTERVAL},
+        other_data::other_data::get_all_cities,
+        processing::processing::SourceInfo,
+        store::store::store,
+    };
+
+    /// Hash with the health of the sources of the alarm data
+    const PROVIDERS_KEY: &str = "providers";
+
+    /// Failures of a source in a row after which the poller uses the next source, if SOURCE_MAX_FAILURES is not set
+    const DEFAULT_MAX_FAILURES: u32 = 3;
+
+    /// How the poller uses several sources of the alarm data
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SourcesMode {
+        /// The sources are requested in the order of priority until one of them works
+        Failover,
+        /// All sources are requested on every poll and their air raid alerts are compared
+        Consensus,
+    }
+
+    /// Settings of the sources from the ALERT_SOURCES_MODE and SOURCE_MAX_FAILURES variables
+    #[derive(Debug, Clone)]
+    struct ProvidersConfig {
+        mode: SourcesMode,
+        max_failures: u32,
+    }
+
+    fn get_providers_config() -> ProvidersConfig {
+        dotenv().ok();
+        ProvidersConfig {
+            mode: match env::var("ALERT_SOURCES_MODE").as_deref() {
+                Ok("consensus") => SourcesMode::Consensus,
+                Ok("failover") | Err(_) => SourcesMode::Failover,
+                Ok(_) => panic!("INCORRECT ALERT_SOURCES_MODE, EXPECTED failover OR consensus"),
+            },
+            max_failures: env::var("SOURCE_MAX_FAILURES")
+                .map(|value| value.parse().expect("INCORRECT SOURCE_MAX_FAILURES"))
+                .unwrap_or(DEFAULT_MAX_FAILURES),
+        }
+    }
+
+    static CONFIG: Lazy<ProvidersConfig> = Lazy::new(get_providers_config);
+
+    /// Health of one source of the alarm data
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct SourceHealth {
+        pub name: String,
+        /// Position in ALERT_SOURCES, 0 is the highest priority
+        pub priority: usize,
+        /// "ok", "failing" (SOURCE_MAX_FAILURES failures in a row), "stale" (the data lags) or "unknown" before the first request
+        pub status: String,
+        /// The poller uses the data of this source
+        pub active: bool,
+        pub consecutive_failures: u32,
+        pub last_success: Option<String>,
+        pub last_error: Option<String>,
+        /// Age of the data of the last response with data
+        pub lag_seconds: Option<i64>,
+        /// Regions with an air raid alert in the last response with data
+        pub alarm_regions: Option<Vec<i32>>,
+    }
+
+    impl SourceHealth {
+
+        fn new(name: &str, priority: usize) -> Self {
+            SourceHealth {
+                name: name.to_string(),
+                priority,
+                status: "unknown".to_string(),
+                active: false,
+                consecutive_failures: 0,
+                last_success: None,
+                last_error: None,
+                lag_seconds: None,
+                alarm_regions: None,
+            }
+        }
+
+        /// Whether the poller may use the data of the source
+        fn is_usable(&self) -> bool {
+            matches!(self.status.as_str(), "ok" | "unknown")
+        }
+    }
+
+    /// Regions where the sources report different air raid alerts
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Disagreement {
+        pub regions: Vec<i32>,
+        /// Regions with an air raid alert by the name of the source
+        pub alarm_regions: BTreeMap<String, Vec<i32>>,
+        pub detected_at: String,
+    }
+
+    /// Health of all sources after the last poll
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SourcesStatus {
+        pub mode: SourcesMode,
+        /// Name of the source used by the poller
+        pub active: Option<String>,
+        pub sources: Vec<SourceHealth>,
+        /// Found only in the consensus mode
+        pub disagreement: Option<Disagreement>,
+        pub updated_at: String,
+    }
+
+    /// Response of the source chosen by the poller
+    pub struct PolledData {
+        pub source: String,
+        pub response: AlertsResponseResult,
+        pub last_modified: String,
+    }
+
+    /// Result of one request to a source
+    enum Fetched {
+        Data(AlertsResponseResult, String),
+        NotModified,
+        Failed(String),
+    }
+
+    /// State of the sources kept by the poller between the polls
+    struct PollerState {
+        last_modified: Vec<String>,
+        health: Vec<SourceHealth>,
+        active: Option<usize>,
+        disagreement: Option<Disagreement>,
+    }
+
+    static STATE: Lazy<Mutex<PollerState>> = Lazy::new(|| {
+        let sources = alert_sources();
+        Mutex::new(PollerState {
+            last_modified: vec![String::new(); sources.len()],
+            health: sources.iter().enumerate().map(|(priority, source)| SourceHealth::new(source.name(), priority)).collect(),
+            active: None,
+            disagreement: None,
+        })
+    });
+
+    /// Regions with an air raid alert in a response of the alarm API format
+    pub fn alarm_regions(response: &AlertsResponseResult) -> Vec<i32> {
+        response
+            .alerts
+            .iter()
+            .filter(|alert| alert["alert_type"] == AIR_RAID)
+            .filter_map(|alert| alert["location_oblast_uid"].as_i64())
+            .map(|region_id| region_id as i32)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Requests a source and updates its health
+    async fn fetch_source(source: &dyn AlertSource, last_modified: &str, health: &mut SourceHealth) -> Fetched {
+
+        let now = Utc::now();
+        let result = source.fetch(last_modified).await.and_then(|raw| match raw.status == StatusCode::NOT_MODIFIED.as_u16() {
+            true => Ok(None),
+            false => raw.parse().map(Some),
+        });
+
+        match result {
+            Ok(None) => {
+                health.consecutive_failures = 0;
+                health.last_success = Some(now.to_rfc3339());
+                if health.status != "stale" {
+                    health.status = "ok".to_string();
+                }
+                Fetched::NotModified
+            }
+            Ok(Some((response, last_modified))) => {
+                let info = SourceInfo::checked(&UpstreamMeta::new(source.name(), &response), &last_modified, now);
+                health.consecutive_failures = 0;
+                health.last_success = Some(now.to_rfc3339());
+                health.lag_seconds = info.lag_seconds;
+                health.alarm_regions = Some(alarm_regions(&response));
+                health.status = match info.status.as_str() {
+                    "lagging" => "stale".to_string(),
+                    _ => "ok".to_string(),
+                };
+                Fetched::Data(response, last_modified)
+            }
+            Err(e) => {
+                debug!("{} - the source {} failed\n{}", chrono::Local::now(), source.name(), e);
+                health.consecutive_failures += 1;
+                health.last_error = Some(format!("{} - {}", now.to_rfc3339(), e));
+                if health.consecutive_failures >= CONFIG.max_failures {
+                    health.status = "failing".to_string();
+                }
+                Fetched::Failed(e.to_string())
+            }
+        }
+    }
+
+    /// Compares the air raid alerts of the sources that are not failing.
+    /// Returns the regions reported by some of the sources only.
+    fn find_disagreement(health: &[SourceHealth]) -> Option<Disagreement> {
+
+        let reports: BTreeMap<String, Vec<i32>> = health
+            .iter()
+            .filter(|source| source.status != "failing")
+            .filter_map(|source| Some((source.name.clone(), source.alarm_regions.clone()?)))
+            .collect();
+        if reports.len() < 2 {
+            return None;
+        }
+
+        let sets: Vec<BTreeSet<i32>> = reports.values().map(|regions| regions.iter().copied().collect()).collect();
+        let union: BTreeSet<i32> = sets.iter().flatten().copied().collect();
+        let regions: Vec<i32> = union.into_iter().filter(|region_id| !sets.iter().all(|set| set.contains(region_id))).collect();
+
+        match regions.is_empty() {
+            true => None,
+            false => Some(Disagreement { regions, alarm_regions: reports, detected_at: Utc::now().to_rfc3339() }),
+        }
+    }
+
+    /// Requests the sources of the alarm data and returns the response of the source used by the poller.
+    ///
+    /// In the failover mode the sources are requested in the order of priority:
+    /// a source is used until it fails SOURCE_MAX_FAILURES times in a row or its data lags (see SourceInfo),
+    /// then the next source is used. The sources with a higher priority are requested on every poll,
+    /// so the poller returns to them as soon as they work again.
+    /// In the consensus mode every source is requested and the regions with different alerts are reported.
+    pub async fn poll_sources() -> Result<PolledData, SourceError> {
+
+        let sources = alert_sources();
+        let mut state = STATE.lock().await;
+
+        let mut chosen: Option<(usize, Fetched)> = None;
+        let mut fallback: Option<(usize, Fetched)> = None;
+
+        for (index, source) in sources.iter().enumerate() {
+
+            if chosen.is_some() && CONFIG.mode == SourcesMode::Failover {
+                break;
+            }
+
+            // Only the active source is requested with "If-Modified-Since", the others return the whole data
+            let last_modified = match state.active == Some(index) {
+                true => state.last_modified[index].clone(),
+                false => String::new(),
+            };
+            let fetched = fetch_source(source.as_ref(), &last_modified, &mut state.health[index]).await;
+            if let Fetched::Data(_, header) = &fetched {
+                state.last_modified[index] = header.clone();
+            }
+
+            if chosen.is_none() && state.health[index].is_usable() {
+                chosen = Some((index, fetched));
+            } else if fallback.is_none() && !matches!(fetched, Fetched::Failed(_)) {
+                fallback = Some((index, fetched));
+            }
+        }
+
+        // Lagging data is better than no data when every source lags
+        let chosen = chosen.or(fallback);
+
+        if let Some((index, _)) = &chosen {
+            if state.active != Some(*index) {
+                info!(
+                    "{} - the alarm data is taken from the source {} instead of {}",
+                    chrono::Local::now(),
+                    sources[*index].name(),
+                    state.active.map(|active| sources[active].name()).unwrap_or("none")
+                );
+                state.active = Some(*index);
+            }
+        }
+        let active = state.active;
+        for (index, health) in state.health.iter_mut().enumerate() {
+            health.active = active == Some(index);
+        }
+
+        if CONFIG.mode == SourcesMode::Consensus {
+            state.disagreement = find_disagreement(&state.health).map(|mut disagreement| {
+                match &state.disagreement {
+                    Some(previous) if previous.regions == disagreement.regions => disagreement.detected_at = previous.detected_at.clone(),
+                    _ => info!(
+                        "{} - the sources disagree about the air raid alerts in the regions {:?}: {:?}",
+                        chrono::Local::now(),
+                        disagreement.regions,
+                        disagreement.alarm_regions
+                    ),
+                }
+                disagreement
+            });
+        }
+
+        let status = SourcesStatus {
+            mode: CONFIG.mode,
+            active: active.map(|index| sources[index].name().to_string()),
+            sources: state.health.clone(),
+            disagreement: state.disagreement.clone(),
+            updated_at: Utc::now().to_rfc3339(),
+        };
+        if let Err(e) = store().hset(PROVIDERS_KEY, "status", &serde_json::to_string(&status).unwrap()).await {
+            debug!("{} - error saving the health of the sources\n{}", chrono::Local::now(), e);
+        }
+
+        match chosen {
+            Some((index, Fetched::Data(response, last_modified))) => Ok(PolledData {
+                source: sources[index].name().to_string(),
+                response,
+                last_modified,
+            }),
+            Some((_, Fetched::NotModified)) => Err("No new data available (304 Not Modified)".into()),
+            Some((_, Fetched::Failed(e))) => Err(e.into()),
+            None => Err("All sources of the alarm data are failing".into()),
+        }
+    }
+
+    /// Returns the health of the sources after the last poll,
+    /// None before the first poll
+    pub async fn get_sources_status() -> Option<SourcesStatus> {
+        let value = store().hget(PROVIDERS_KEY, "status").await.ok()??;
+        serde_json::from_str(&value).ok()
+    }
+
+    fn default_alert_type() -> String {
+        AIR_RAID.to_string()
+    }
+
+    /// Alert of the static feed
+    #[derive(Debug, Deserialize)]
+    struct StaticAlert {
+        region_id: i32,
+        #[serde(default = "default_alert_type")]
+        alert_type: String,
+        started_at: Option<String>,
+    }
+
+    /// Content of the file of the static feed
+    #[derive(Debug, Deserialize)]
+    struct StaticFeed {
+        updated_at: Option<String>,
+        #[serde(default)]
+        alerts: Vec<StaticAlert>,
+    }
+
+    /// Alerts written by hand to a JSON file, for the time when the other sources are not available:
+    /// {"updated_at": "2024-05-01T10:00:00Z", "alerts": [{"region_id": 9, "alert_type": "air_raid", "started_at": "2024-05-01T09:58:00Z"}]}
+    /// The file is read again when it is modified. Every alert covers the whole region.
+    pub struct StaticSource {
+        path: PathBuf,
+    }
+
+    impl StaticSource {
+
+        pub fn new(path: impl Into<PathBuf>) -> Self {
+            StaticSource { path: path.into() }
+        }
+
+        /// Creates the source from the STATIC_FEED_PATH variable
+        pub fn from_env() -> Self {
+            dotenv().ok();
+            StaticSource::new(env::var("STATIC_FEED_PATH").expect("INCORRECT STATIC_FEED_PATH"))
+        }
+    }
+
+    /// Converts the static feed to a response of the alarm API
+    fn static_response(feed: StaticFeed, last_modified: String, modified_at: DateTime<Utc>) -> RawResponse {
+
+        let names = get_all_cities();
+        let alerts: Vec<Value> = feed
+            .alerts
+            .into_iter()
+            .map(|alert| {
+                let name = names.get(&alert.region_id).cloned().unwrap_or_default();
+                json!({
+                    "id": 0,
+                    "location_title": name,
+                    "location_type": "oblast",
+                    "started_at": alert.started_at.or_else(|| feed.updated_at.clone()).unwrap_or_else(|| modified_at.to_rfc3339()),
+                    "finished_at": null,
+                    "updated_at": feed.updated_at,
+                    "alert_type": alert.alert_type,
+                    "location_uid": alert.region_id.to_string(),
+                    "location_oblast": name,
+                    "location_oblast_uid": alert.region_id,
+                    "location_raion": null,
+                    "notes": null,
+                    "calculated": null
+                })
+            })
+            .collect();
+
+        RawResponse {
+            status: StatusCode::OK.as_u16(),
+            headers: BTreeMap::from([("last-modified".to_string(), last_modified)]),
+            body: json!({ "alerts": alerts, "meta": { "last_updated_at": null, "type": "static" }, "disclaimer": null }).to_string(),
+        }
+    }
+
+    #[rocket::async_trait]
+    impl AlertSource for StaticSource {
+
+        fn name(&self) -> &str {
+            "static"
+        }
+
+        async fn fetch(&self, last_modified: &str) -> Result<RawResponse, SourceError> {
+
+            let modified_at: DateTime<Utc> = tokio::fs::metadata(&self.path).await?.modified()?.into();
+            let header = modified_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            if header == last_modified {
+                return Ok(RawResponse {
+                    status: StatusCode::NOT_MODIFIED.as_u16(),
+                    headers: BTreeMap::from([("last-modified".to_string(), header)]),
+                    body: String::new(),
+                });
+            }
+
+            let feed: StaticFeed = serde_json::from_str(&tokio::fs::read_to_string(&self.path).await?)?;
+            Ok(static_response(feed, header, modified_at))
+        }
+
+        fn poll_interval(&self) -> std::time::Duration {
+            HTTP_POLL_INTERVAL
+        }
+    }
+
+    /// Health of the sources of the alarm data
+    #[get("/sources")]
+    pub async fn get_sources(_api_key: ApiKey) -> Result<Json<Envelope<SourcesStatus>>, ErrorResponse> {
+
+        let status = get_sources_status()
+            .await
+            .ok_or_else(|| error_response(Status::ServiceUnavailable, "the sources of the alarm data were not polled yet"))?;
+
+        let meta = Meta { count: Some(status.sources.len()), ..Meta::new() };
+        Ok(Json(Envelope::data(status, meta)))
+    }
+
+    /// Returns all routes of the sources
+    pub fn routes() -> Vec<Route> {
+        routes![get_sources]
+    }
+
+}
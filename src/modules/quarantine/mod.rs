@@ -0,0 +1,176 @@
+pub mod quarantine {
+
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use log::{debug, warn};
+    use once_cell::sync::Lazy;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::modules::store::store::{store, StoreResult};
+
+    /// List with the last quarantined records
+    const QUARANTINE_KEY: &str = "quarantine";
+
+    /// Hash with the number of quarantined records of every kind
+    const COUNTS_KEY: &str = "metrics:quarantine";
+
+    /// Number of records kept in the quarantine
+    pub const QUARANTINE_LENGTH: usize = 200;
+
+    /// Minimum pause between the warnings in the log
+    const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Why a record of the alarm API is not processed as usual
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum QuarantineReason {
+        /// The record has no value in a required field
+        MissingField(&'static str),
+        /// The record has fields that the poller does not know.
+        /// Such records are still processed.
+        UnexpectedFields(Vec<String>),
+        UnknownAlertType(String),
+        /// The region of the record is not in the registry
+        UnknownRegion(i64),
+        /// The record can not be deserialized
+        InvalidRecord(String),
+    }
+
+    impl QuarantineReason {
+
+        /// Kind of the reason in the metrics
+        pub fn kind(&self) -> &'static str {
+            match self {
+                QuarantineReason::MissingField(_) => "missing_field",
+                QuarantineReason::UnexpectedFields(_) => "unexpected_fields",
+                QuarantineReason::UnknownAlertType(_) => "unknown_alert_type",
+                QuarantineReason::UnknownRegion(_) => "unknown_region",
+                QuarantineReason::InvalidRecord(_) => "invalid_record",
+            }
+        }
+
+        /// Whether the record is left out of the alarm state
+        pub fn is_dropped(&self) -> bool {
+            !matches!(self, QuarantineReason::UnexpectedFields(_))
+        }
+    }
+
+    impl fmt::Display for QuarantineReason {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                QuarantineReason::MissingField(field) => write!(f, "missing field {}", field),
+                QuarantineReason::UnexpectedFields(fields) => write!(f, "unexpected fields {}", fields.join(", ")),
+                QuarantineReason::UnknownAlertType(alert_type) => write!(f, "unknown alert type {}", alert_type),
+                QuarantineReason::UnknownRegion(region_id) => write!(f, "unknown region {}", region_id),
+                QuarantineReason::InvalidRecord(error) => write!(f, "invalid record: {}", error),
+            }
+        }
+    }
+
+    /// Record of the alarm API in the quarantine
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct QuarantineEntry {
+        pub quarantined_at: String,
+        pub kind: String,
+        pub reason: String,
+        /// Whether the record was left out of the alarm state
+        pub dropped: bool,
+        pub record: Value,
+    }
+
+    /// Records quarantined by the previous poll, they are not added again while the alarm API keeps sending them
+    static PREVIOUS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+    /// Time of the last warning and the number of records quarantined since then
+    static WARNINGS: Lazy<Mutex<(Option<Instant>, usize)>> = Lazy::new(|| Mutex::new((None, 0)));
+
+    /// Identity of a quarantined record: its ID (or the whole record) with the kind of the reason
+    fn record_key(record: &Value, reason: &QuarantineReason) -> String {
+        match record.get("id") {
+            Some(id) => format!("{}:{}", id, reason.kind()),
+            None => format!("{}:{}", record, reason.kind()),
+        }
+    }
+
+    /// Logs a warning about the new quarantined records, at most once per WARN_INTERVAL
+    fn warn_rate_limited(count: usize, example: &str) {
+
+        let mut warnings = WARNINGS.lock().unwrap();
+        warnings.1 += count;
+
+        if warnings.0.is_some_and(|warned_at| warned_at.elapsed() < WARN_INTERVAL) {
+            return;
+        }
+        warn!("{} - {} records of the alarm API are quarantined, for example: {}", chrono::Local::now(), warnings.1, example);
+        *warnings = (Some(Instant::now()), 0);
+    }
+
+    /// Saves the records with problems found by one poll.
+    /// A record that was also quarantined by the previous poll is not saved again.
+    pub async fn quarantine(records: Vec<(Value, QuarantineReason)>) {
+
+        let keys: Vec<String> = records.iter().map(|(record, reason)| record_key(record, reason)).collect();
+        let new_records: Vec<(Value, QuarantineReason)> = {
+            let mut previous = PREVIOUS.lock().unwrap();
+            let result = records
+                .into_iter()
+                .zip(&keys)
+                .filter(|(_, key)| !previous.contains(*key))
+                .map(|(record, _)| record)
+                .collect();
+            *previous = keys.into_iter().collect();
+            result
+        };
+
+        if new_records.is_empty() {
+            return;
+        }
+        warn_rate_limited(new_records.len(), &new_records[0].1.to_string());
+
+        for (record, reason) in new_records {
+            let entry = QuarantineEntry {
+                quarantined_at: chrono::Utc::now().to_rfc3339(),
+                kind: reason.kind().to_string(),
+                reason: reason.to_string(),
+                dropped: reason.is_dropped(),
+                record,
+            };
+            let result = async {
+                store().lpush_capped(QUARANTINE_KEY, &serde_json::to_string(&entry).unwrap(), QUARANTINE_LENGTH).await?;
+                store().hincr(COUNTS_KEY, reason.kind(), 1, None).await.map(|_| ())
+            };
+            if let Err(e) = result.await {
+                debug!("{} - error saving a quarantined record\n{}", chrono::Local::now(), e);
+            }
+        }
+    }
+
+    /// Returns the last quarantined records, newest first
+    pub async fn get_quarantine(limit: usize) -> StoreResult<Vec<QuarantineEntry>> {
+
+        // LRANGE 0 -1 would return the whole list
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(store()
+            .lrange(QUARANTINE_KEY, 0, limit.min(QUARANTINE_LENGTH) as isize - 1)
+            .await?
+            .iter()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .collect())
+    }
+
+    /// Returns the number of quarantined records of every kind
+    pub async fn get_quarantine_counts() -> HashMap<String, u64> {
+        store()
+            .hgetall(COUNTS_KEY)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(kind, value)| Some((kind, value.parse().ok()?)))
+            .collect()
+    }
+
+}
@@ -0,0 +1,309 @@
+pub mod rate_limit {
+
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Instant;
+    use log::debug;
+    use rocket::{
+        delete, get, options, patch, post, put, routes, Build, Data, Request, Response, Rocket, Route,
+        fairing::{self, Fairing, Info, Kind},
+        http::{Header, Status},
+        request::{self, FromRequest},
+    };
+    use serde::Deserialize;
+
+    use crate::modules::{
+        api_keys::api_keys::{active_key_id, request_api_key},
+        api_v2::api_v2::ErrorDetail,
+        cors::cors::is_preflight,
+        store::store::store,
+    };
+
+    /// Groups of routes with their own limits.
+    /// A request belongs to the first group whose prefix matches its path.
+    const ROUTE_GROUPS: [(&str, &str); 4] = [
+        ("/v2/admin", "admin"),
+        ("/v2", "v2"),
+        ("/get_", "v1"),
+        ("/", "dashboard"),
+    ];
+
+    /// The idle buckets are removed from memory after this number of requests
+    const PRUNE_INTERVAL: u64 = 1024;
+
+    /// Settings of one token bucket
+    #[derive(Debug, Clone, Copy, Deserialize)]
+    pub struct BucketConfig {
+        pub capacity: f64,
+        pub refill_per_second: f64,
+    }
+
+    /// Rate limiting settings from the "rate_limit" section of the Rocket configuration.
+    /// Groups without settings are not limited.
+    /// In the shared mode the buckets are kept in the store, so all instances of the service share them.
+    #[derive(Debug, Clone, Deserialize)]
+    pub struct RateLimitConfig {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(default)]
+        pub shared: bool,
+        #[serde(default)]
+        pub groups: HashMap<String, BucketConfig>,
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+
+    impl Default for RateLimitConfig {
+        fn default() -> Self {
+            RateLimitConfig { enabled: default_enabled(), shared: false, groups: HashMap::new() }
+        }
+    }
+
+    /// Result of the check of a request
+    #[derive(Debug, Clone)]
+    pub struct Decision {
+        pub allowed: bool,
+        pub limit: u64,
+        pub remaining: u64,
+        /// Seconds until the bucket is full again
+        pub reset: u64,
+        /// Seconds until the next request is allowed
+        pub retry_after: u64,
+    }
+
+    impl BucketConfig {
+
+        /// Builds the decision from the number of tokens left in the bucket
+        fn decision(&self, allowed: bool, tokens: f64) -> Decision {
+            Decision {
+                allowed,
+                limit: self.capacity as u64,
+                remaining: tokens.max(0.0).floor() as u64,
+                reset: ((self.capacity - tokens) / self.refill_per_second).max(0.0).ceil() as u64,
+                retry_after: ((1.0 - tokens) / self.refill_per_second).max(0.0).ceil() as u64,
+            }
+        }
+    }
+
+    /// Token bucket kept in memory
+    struct Bucket {
+        tokens: f64,
+        updated_at: Instant,
+    }
+
+    impl Bucket {
+
+        /// Returns the number of tokens in the bucket at the given time
+        fn tokens_at(&self, now: Instant, config: &BucketConfig) -> f64 {
+            let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+            (self.tokens + elapsed * config.refill_per_second).min(config.capacity)
+        }
+    }
+
+    /// Decision of the rate limiter stored in the cache of a request
+    #[derive(Debug, Default)]
+    struct RequestDecision(Option<Decision>);
+
+    /// State of the rate limiter managed by Rocket
+    pub struct RateLimiterState {
+        config: RateLimitConfig,
+        buckets: Mutex<HashMap<String, (Bucket, BucketConfig)>>,
+        requests: AtomicU64,
+    }
+
+    impl RateLimiterState {
+
+        /// Takes one token from the bucket kept in memory
+        fn take_local(&self, key: &str, config: &BucketConfig) -> (bool, f64) {
+
+            let now = Instant::now();
+            let mut buckets = self.buckets.lock().unwrap();
+
+            // u64::is_multiple_of needs Rust 1.87
+            #[allow(clippy::manual_is_multiple_of)]
+            let prune = self.requests.fetch_add(1, Ordering::Relaxed) % PRUNE_INTERVAL == 0;
+            if prune {
+                buckets.retain(|_, (bucket, config)| bucket.tokens_at(now, config) < config.capacity);
+            }
+
+            let (bucket, _) = buckets
+                .entry(key.to_string())
+                .or_insert((Bucket { tokens: config.capacity, updated_at: now }, *config));
+
+            let mut tokens = bucket.tokens_at(now, config);
+            let allowed = tokens >= 1.0;
+            if allowed {
+                tokens -= 1.0;
+            }
+            bucket.tokens = tokens;
+            bucket.updated_at = now;
+
+            (allowed, tokens)
+        }
+
+        /// Takes one token from the bucket of the client in the group.
+        /// If the store is unavailable in the shared mode, the bucket in memory is used.
+        async fn take(&self, key: &str, config: &BucketConfig) -> Decision {
+
+            let (allowed, tokens) = match self.config.shared {
+                true => match store().take_token(&format!("rate_limit:{}", key), config.capacity, config.refill_per_second).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        debug!("{} - the shared rate limiter is unavailable, using the local one\n{}", chrono::Local::now(), e);
+                        self.take_local(key, config)
+                    }
+                },
+                false => self.take_local(key, config),
+            };
+
+            config.decision(allowed, tokens)
+        }
+    }
+
+    /// Returns the group of routes of a path
+    fn route_group(path: &str) -> &'static str {
+        ROUTE_GROUPS
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix))
+            .map(|(_, group)| *group)
+            .unwrap_or("dashboard")
+    }
+
+    /// Returns the identity of the client: the identifier of its API key or its IP address.
+    /// Unknown keys share the bucket of the IP address, otherwise a new random key
+    /// would get a new bucket with every request.
+    async fn client_key(request: &Request<'_>) -> String {
+
+        if let Some(key) = request_api_key(request) {
+            match active_key_id(key).await {
+                Ok(Some(id)) => return format!("key:{}", id),
+                Ok(None) => {}
+                Err(e) => debug!("{} - the API key can not be checked by the rate limiter\n{}", chrono::Local::now(), e),
+            }
+        }
+
+        match request.client_ip() {
+            Some(ip) => format!("ip:{}", ip),
+            None => "unknown".to_string(),
+        }
+    }
+
+    /// Request guard of the routes that reject the requests over the limit.
+    /// A rejected request fails with "429 Too Many Requests" and is answered by the catchers of its path
+    /// with the message cached by the fairing, other requests are forwarded to their routes.
+    pub struct RateLimited;
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for RateLimited {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+            match request.local_cache(RequestDecision::default) {
+                RequestDecision(Some(decision)) if !decision.allowed => request::Outcome::Error((Status::TooManyRequests, ())),
+                // The status is used only if no other route matches the request
+                _ => request::Outcome::Forward(Status::NotFound),
+            }
+        }
+    }
+
+    #[get("/<_..>")]
+    fn reject_get(_limited: RateLimited) {}
+
+    #[post("/<_..>")]
+    fn reject_post(_limited: RateLimited) {}
+
+    #[put("/<_..>")]
+    fn reject_put(_limited: RateLimited) {}
+
+    #[patch("/<_..>")]
+    fn reject_patch(_limited: RateLimited) {}
+
+    #[delete("/<_..>")]
+    fn reject_delete(_limited: RateLimited) {}
+
+    #[options("/<_..>")]
+    fn reject_options(_limited: RateLimited) {}
+
+    /// Returns the routes that reject the requests, they are checked before all other routes
+    fn reject_routes() -> Vec<Route> {
+        let mut routes = routes![reject_get, reject_post, reject_put, reject_patch, reject_delete, reject_options];
+        for route in &mut routes {
+            route.rank = -100;
+        }
+        routes
+    }
+
+    /// Fairing that limits the rate of requests of every client with token buckets.
+    ///
+    /// The settings are read from the "rate_limit" section of the Rocket configuration.
+    /// Limited responses have the RateLimit-Limit, RateLimit-Remaining and RateLimit-Reset headers,
+    /// rejected requests get "429 Too Many Requests" with the Retry-After header.
+    pub struct RateLimiter;
+
+    #[rocket::async_trait]
+    impl Fairing for RateLimiter {
+
+        fn info(&self) -> Info {
+            Info { name: "Rate limiter", kind: Kind::Ignite | Kind::Request | Kind::Response }
+        }
+
+        async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+
+            let config: RateLimitConfig = rocket.figment().extract_inner("rate_limit").unwrap_or_default();
+            let state = RateLimiterState { config, buckets: Mutex::new(HashMap::new()), requests: AtomicU64::new(0) };
+
+            Ok(rocket
+                .manage(state)
+                .mount("/", reject_routes()))
+        }
+
+        async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+
+            let state = match request.rocket().state::<RateLimiterState>() {
+                Some(state) if state.config.enabled => state,
+                _ => return,
+            };
+
+            // Preflight requests are sent by browsers on their own and are not counted
+            if is_preflight(request) {
+                return;
+            }
+
+            let group = route_group(request.uri().path().as_str());
+            let config = match state.config.groups.get(group) {
+                Some(config) => *config,
+                None => return,
+            };
+
+            let key = format!("{}:{}", group, client_key(request).await);
+            let decision = state.take(&key, &config).await;
+
+            // The request is rejected by the guard of the routes with the highest rank (see RateLimited)
+            if !decision.allowed {
+                debug!("{} - rate limit exceeded by {}", chrono::Local::now(), key);
+                request.local_cache(|| ErrorDetail(Some(format!("rate limit exceeded, retry in {} seconds", decision.retry_after))));
+            }
+
+            request.local_cache(|| RequestDecision(Some(decision)));
+        }
+
+        async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+
+            let decision = match request.local_cache(RequestDecision::default) {
+                RequestDecision(Some(decision)) => decision,
+                RequestDecision(None) => return,
+            };
+
+            response.set_header(Header::new("RateLimit-Limit", decision.limit.to_string()));
+            response.set_header(Header::new("RateLimit-Remaining", decision.remaining.to_string()));
+            response.set_header(Header::new("RateLimit-Reset", decision.reset.to_string()));
+            if !decision.allowed {
+                response.set_header(Header::new("Retry-After", decision.retry_after.to_string()));
+            }
+        }
+    }
+
+}
@@ -0,0 +1,178 @@
+pub mod replay {
+
+    use dotenv::dotenv;
+    use std::collections::VecDeque;
+    use std::env;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use chrono::{DateTime, Utc};
+    use log::{debug, info};
+    use serde::{Deserialize, Serialize};
+    use tokio::time::Instant;
+
+    use crate::modules::fetch_data::fetch_data::{AlertSource, RawResponse, SourceError};
+
+    /// Number of daily directories kept by the recorder if RECORD_MAX_DAYS is not set
+    const DEFAULT_MAX_DAYS: usize = 7;
+
+    /// Response of the alarm API with the time when it was received
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Recording {
+        pub recorded_at: DateTime<Utc>,
+        #[serde(flatten)]
+        pub response: RawResponse,
+    }
+
+    /// Saves the responses of the alarm API to files, one directory per day:
+    /// <RECORD_DIR>/2024-05-01/063015.123456.json.
+    /// Only the last RECORD_MAX_DAYS directories are kept.
+    pub struct Recorder {
+        dir: PathBuf,
+        max_days: usize,
+    }
+
+    impl Recorder {
+
+        /// Creates the recorder if RECORD_DIR is set
+        pub fn from_env() -> Option<Self> {
+            dotenv().ok();
+            let dir = env::var("RECORD_DIR").ok().filter(|value| !value.is_empty())?;
+            Some(Recorder {
+                dir: PathBuf::from(dir),
+                max_days: env::var("RECORD_MAX_DAYS")
+                    .map(|value| value.parse().expect("INCORRECT RECORD_MAX_DAYS"))
+                    .unwrap_or(DEFAULT_MAX_DAYS),
+            })
+        }
+
+        pub async fn record(&self, response: &RawResponse) {
+
+            let recording = Recording { recorded_at: Utc::now(), response: response.clone() };
+            let day_dir = self.dir.join(recording.recorded_at.format("%Y-%m-%d").to_string());
+            let path = day_dir.join(format!("{}.json", recording.recorded_at.format("%H%M%S%.6f")));
+
+            let is_new_day = !day_dir.exists();
+            let result = async {
+                tokio::fs::create_dir_all(&day_dir).await?;
+                tokio::fs::write(&path, serde_json::to_vec(&recording)?).await
+            };
+            if let Err(e) = result.await {
+                debug!("{} - error recording the response to {:?}\n{}", chrono::Local::now(), path, e);
+                return;
+            }
+
+            if is_new_day {
+                self.remove_old_days().await;
+            }
+        }
+
+        /// Removes the oldest daily directories above the limit
+        async fn remove_old_days(&self) {
+
+            let mut days = match std::fs::read_dir(&self.dir) {
+                Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_dir()).collect::<Vec<_>>(),
+                Err(_) => return,
+            };
+            days.sort();
+
+            let excess = days.len().saturating_sub(self.max_days);
+            for day in days.into_iter().take(excess) {
+                match tokio::fs::remove_dir_all(&day).await {
+                    Ok(_) => debug!("{} - old recordings {:?} removed", chrono::Local::now(), day),
+                    Err(e) => debug!("{} - error removing the recordings {:?}\n{}", chrono::Local::now(), day, e),
+                }
+            }
+        }
+    }
+
+    /// Reads all recordings of a directory and its subdirectories ordered by the time of the recording
+    pub fn load_recordings(dir: &Path) -> std::io::Result<Vec<Recording>> {
+
+        let mut result = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                result.extend(load_recordings(&path)?);
+            } else if path.extension().is_some_and(|extension| extension == "json") {
+                match serde_json::from_slice(&std::fs::read(&path)?) {
+                    Ok(recording) => result.push(recording),
+                    Err(e) => debug!("{} - {:?} is not a recording\n{}", chrono::Local::now(), path, e),
+                }
+            }
+        }
+        result.sort_by_key(|recording: &Recording| recording.recorded_at);
+        Ok(result)
+    }
+
+    /// Feeds the recorded responses to the poller with the original pauses divided by the speed.
+    /// After the last recording the poller waits, so the resulting state can be inspected with the API.
+    pub struct ReplaySource {
+        recordings: Mutex<VecDeque<Recording>>,
+        speed: f64,
+        /// Start of the replay and the time of the first recording
+        start: Mutex<Option<(Instant, DateTime<Utc>)>>,
+    }
+
+    impl ReplaySource {
+
+        pub fn new(recordings: Vec<Recording>, speed: f64) -> Self {
+            ReplaySource { recordings: Mutex::new(recordings.into()), speed, start: Mutex::new(None) }
+        }
+
+        /// Creates the source from the recordings in REPLAY_DIR.
+        /// REPLAY_SPEED is the acceleration (1 by default, 0 - without pauses).
+        pub fn from_env() -> Option<Self> {
+
+            dotenv().ok();
+            let dir = env::var("REPLAY_DIR").ok().filter(|value| !value.is_empty())?;
+            let speed = env::var("REPLAY_SPEED")
+                .map(|value| value.parse().expect("INCORRECT REPLAY_SPEED"))
+                .unwrap_or(1.0);
+
+            let recordings = load_recordings(Path::new(&dir)).expect("INCORRECT REPLAY_DIR");
+            info!("{} - replaying {} responses from {} at speed {}", chrono::Local::now(), recordings.len(), dir, speed);
+            Some(ReplaySource::new(recordings, speed))
+        }
+
+        /// Time when the recording has to be returned
+        fn due(&self, recorded_at: DateTime<Utc>) -> Option<Instant> {
+
+            if self.speed <= 0.0 {
+                return None;
+            }
+            let (started, first_recorded_at) = *self.start.lock().unwrap().get_or_insert((Instant::now(), recorded_at));
+            let offset = (recorded_at - first_recorded_at).to_std().unwrap_or_default();
+            Some(started + offset.div_f64(self.speed))
+        }
+    }
+
+    #[rocket::async_trait]
+    impl AlertSource for ReplaySource {
+
+        fn name(&self) -> &str {
+            "replay"
+        }
+
+        async fn fetch(&self, _last_modified: &str) -> Result<RawResponse, SourceError> {
+
+            let next = self.recordings.lock().unwrap().pop_front();
+            let Some(recording) = next else {
+                info!("{} - the replay is finished", chrono::Local::now());
+                return std::future::pending().await;
+            };
+
+            if let Some(due) = self.due(recording.recorded_at) {
+                tokio::time::sleep_until(due).await;
+            }
+            debug!("{} - replaying the response recorded at {}", chrono::Local::now(), recording.recorded_at);
+            Ok(recording.response)
+        }
+
+        /// The pauses are made by the source
+        fn poll_interval(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+}
@@ -0,0 +1,93 @@
+pub mod sse {
+
+    use std::time::Duration;
+    use futures::StreamExt;
+    use log::debug;
+    use once_cell::sync::Lazy;
+    use rocket::{
+        get, routes, Route, Shutdown,
+        response::stream::{Event, EventStream},
+    };
+    use tokio::sync::broadcast::{self, error::RecvError};
+
+    use crate::modules::{
+        api_keys::api_keys::ApiKey,
+        events::events::get_events_config,
+        store::store::store,
+    };
+
+    /// Number of events kept for a slow client before it starts to miss them
+    const CLIENT_BUFFER: usize = 64;
+
+    /// Pause before the subscription to the events channel is opened again after an error
+    const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+    /// Events of the channel for the clients of this instance, as the event name and the JSON of the event.
+    /// The relay is started by the first client.
+    static EVENTS: Lazy<broadcast::Sender<(String, String)>> = Lazy::new(|| {
+        let (sender, _) = broadcast::channel(CLIENT_BUFFER);
+        tokio::spawn(run_relay(sender.clone()));
+        sender
+    });
+
+    /// Passes the events published by the poller to the ALARM_EVENTS_CHANNEL channel
+    /// to the clients of /v2/events, so every instance of the API serves the stream.
+    async fn run_relay(events: broadcast::Sender<(String, String)>) {
+
+        let channel = get_events_config().channel;
+        if channel.is_empty() {
+            debug!("{} - ALARM_EVENTS_CHANNEL is empty, /v2/events has no events", chrono::Local::now());
+            return;
+        }
+
+        loop {
+            match store().subscribe(&channel).await {
+                Ok(mut messages) => {
+                    while let Some(message) = messages.next().await {
+                        let name = serde_json::from_str::<serde_json::Value>(&message)
+                            .ok()
+                            .and_then(|event| event["event"].as_str().map(str::to_string));
+                        match name {
+                            Some(name) => {
+                                // No connected clients is not an error
+                                let _ = events.send((name, message));
+                            }
+                            None => debug!("{} - unknown message in channel {}\n{}", chrono::Local::now(), channel, message),
+                        }
+                    }
+                    debug!("{} - the subscription to channel {} is closed", chrono::Local::now(), channel);
+                }
+                Err(e) => debug!("{} - error subscribing to channel {}\n{}", chrono::Local::now(), channel, e),
+            }
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+
+    /// Stream of the alarm events as server-sent events.
+    /// The name of an event is its type, the data is the event as JSON.
+    #[get("/events")]
+    async fn get_events(_api_key: ApiKey, mut shutdown: Shutdown) -> EventStream![] {
+
+        let mut receiver = EVENTS.subscribe();
+
+        EventStream! {
+            loop {
+                let (name, data) = tokio::select! {
+                    event = receiver.recv() => match event {
+                        Ok(event) => event,
+                        Err(RecvError::Closed) => break,
+                        Err(RecvError::Lagged(_)) => continue,
+                    },
+                    _ = &mut shutdown => break,
+                };
+                yield Event::data(data).event(name);
+            }
+        }
+    }
+
+    /// Returns all routes of the event stream
+    pub fn routes() -> Vec<Route> {
+        routes![get_events]
+    }
+
+}
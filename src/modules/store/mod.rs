@@ -0,0 +1,783 @@
+pub mod store {
+
+    use dotenv::dotenv;
+    use std::env;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Mutex, MutexGuard};
+    use std::time::{Duration, Instant};
+    use futures::stream::{BoxStream, StreamExt};
+    use once_cell::sync::OnceCell;
+    use redis::{aio::{ConnectionManager, ConnectionManagerConfig}, AsyncCommands, Client};
+    use tokio::sync::broadcast::{self, error::RecvError};
+
+    /// Error returned by the store backend
+    #[derive(Debug, Clone)]
+    pub struct StoreError(pub String);
+
+    impl fmt::Display for StoreError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "store error: {}", self.0)
+        }
+    }
+
+    impl std::error::Error for StoreError {}
+
+    impl From<redis::RedisError> for StoreError {
+        fn from(e: redis::RedisError) -> Self {
+            StoreError(e.to_string())
+        }
+    }
+
+    pub type StoreResult<T> = Result<T, StoreError>;
+
+    /// Field of a hash written by Store::hwrite: the key, the field and the value, None removes the field
+    pub type HashWrite = (String, String, Option<String>);
+
+    /// Storage shared by the poller and the API.
+    ///
+    /// The operations follow the data types of Redis (strings, hashes and lists),
+    /// so every part of the service keeps its data in the same way
+    /// regardless of the backend.
+    #[rocket::async_trait]
+    pub trait Store: Send + Sync {
+
+        async fn hget(&self, key: &str, field: &str) -> StoreResult<Option<String>>;
+
+        async fn hgetall(&self, key: &str) -> StoreResult<HashMap<String, String>>;
+
+        async fn hset_multiple(&self, key: &str, values: &[(String, String)]) -> StoreResult<()>;
+
+        /// Removes a field of a hash, returns false if the hash had no such field
+        async fn hdel(&self, key: &str, field: &str) -> StoreResult<bool>;
+
+        /// Increments a field of a hash and returns the new value.
+        /// The hash expires after ttl if it is passed.
+        async fn hincr(&self, key: &str, field: &str, delta: i64, ttl: Option<Duration>) -> StoreResult<i64>;
+
+        /// Atomically increments the counter in the "version" field of a hash and moves the time of the change
+        /// in the "modified_at" field (Unix seconds) forward: to the given time if it is later than the stored one,
+        /// otherwise to the current time of the store or one second after the stored time, whichever is later.
+        /// Returns the new version and time.
+        async fn bump_version(&self, key: &str, time: Option<i64>) -> StoreResult<(u64, i64)>;
+
+        /// Adds a value to the head of a list and keeps only the first max_len values
+        async fn lpush_capped(&self, key: &str, value: &str, max_len: usize) -> StoreResult<()>;
+
+        /// Returns the values of a list from start to stop inclusive
+        async fn lrange(&self, key: &str, start: isize, stop: isize) -> StoreResult<Vec<String>>;
+
+        /// Removes the last count values of a list, the list is removed if nothing is left.
+        /// The values pushed to the head of the list meanwhile are kept.
+        async fn rtrim(&self, key: &str, count: usize) -> StoreResult<()>;
+
+        /// Atomically takes one token from the token bucket stored in the key.
+        /// The bucket holds up to capacity tokens and gets refill_per_second tokens every second.
+        /// Returns whether a token was taken and the number of tokens left.
+        async fn take_token(&self, key: &str, capacity: f64, refill_per_second: f64) -> StoreResult<(bool, f64)>;
+
+        /// Publishes a message to the subscribers of a channel
+        async fn publish(&self, channel: &str, message: &str) -> StoreResult<()>;
+
+        /// Subscribes to a channel and returns the messages published after the subscription.
+        /// The stream ends when the connection is lost.
+        async fn subscribe(&self, channel: &str) -> StoreResult<BoxStream<'static, String>>;
+
+        /// Appends an entry with an automatically generated ID to a stream
+        /// that keeps about max_len last entries. Returns the ID of the entry.
+        async fn xadd_capped(&self, key: &str, fields: &[(String, String)], max_len: usize) -> StoreResult<String>;
+
+        /// Creates a consumer group of a stream, the stream is created if it does not exist.
+        /// An existing group is left as is.
+        async fn xgroup_create(&self, key: &str, group: &str) -> StoreResult<()>;
+
+        /// Takes the lease in the key for the holder if it is free (SET NX PX) or renews it if the holder already has it.
+        /// Every new lease increments the counter in token_key, which is the fencing token of the holder.
+        /// Returns the fencing token while the holder has the lease and None if the lease belongs to another holder.
+        async fn acquire_lease(&self, key: &str, token_key: &str, holder: &str, ttl: Duration) -> StoreResult<Option<u64>>;
+
+        /// Releases the lease if it belongs to the holder
+        async fn release_lease(&self, key: &str, holder: &str) -> StoreResult<()>;
+
+        /// Writes the fields of the hashes in one step.
+        /// With a fence (token_key, token) the write is made only if token_key still has the token,
+        /// so a holder whose lease has expired can not overwrite the data of the new holder.
+        /// Returns false if the write was rejected by the fence.
+        async fn hwrite(&self, writes: &[HashWrite], fence: Option<(&str, u64)>) -> StoreResult<bool>;
+
+        async fn hset(&self, key: &str, field: &str, value: &str) -> StoreResult<()> {
+            self.hset_multiple(key, &[(field.to_string(), value.to_string())]).await
+        }
+    }
+
+    /// Token bucket in Redis. The time of the Redis server is used,
+    /// so all instances of the service share the same clock.
+    const TAKE_TOKEN_SCRIPT: &str = r#"
+        local capacity = tonumber(ARGV[1])
+        local rate = tonumber(ARGV[2])
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+        local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+        local tokens = tonumber(state[1]) or capacity
+        local updated_at = tonumber(state[2]) or now
+        tokens = math.min(capacity, tokens + math.max(0, now - updated_at) * rate)
+        local allowed = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+            allowed = 1
+        end
+        redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_at', tostring(now))
+        redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate * 1000) + 1000)
+        return {allowed, tostring(tokens)}
+    "#;
+
+    /// Version and time of a change, see Store::bump_version. The time of Redis is used,
+    /// so the instances with different clocks do not move the time back.
+    const BUMP_VERSION_SCRIPT: &str = r#"
+        local stored = tonumber(redis.call('HGET', KEYS[1], 'modified_at'))
+        local time = tonumber(ARGV[1])
+        local now = tonumber(redis.call('TIME')[1])
+        local modified_at = now
+        if time and (not stored or time > stored) then
+            modified_at = time
+        elseif stored and now <= stored then
+            modified_at = stored + 1
+        end
+        redis.call('HSET', KEYS[1], 'modified_at', modified_at)
+        local version = redis.call('HINCRBY', KEYS[1], 'version', 1)
+        return {version, modified_at}
+    "#;
+
+    /// Lease with a fencing token. The lease is checked and renewed atomically,
+    /// so a holder whose lease has expired can not renew the lease of the new holder.
+    const ACQUIRE_LEASE_SCRIPT: &str = r#"
+        if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+            return redis.call('INCR', KEYS[2])
+        end
+        if redis.call('GET', KEYS[1]) == ARGV[1] then
+            redis.call('PEXPIRE', KEYS[1], ARGV[2])
+            return tonumber(redis.call('GET', KEYS[2])) or 0
+        end
+        return 0
+    "#;
+
+    const RELEASE_LEASE_SCRIPT: &str = r#"
+        if redis.call('GET', KEYS[1]) == ARGV[1] then
+            return redis.call('DEL', KEYS[1])
+        end
+        return 0
+    "#;
+
+    ///Get the Redis-host
+    fn get_redis_host() -> String {
+        dotenv().ok();
+        env::var("REDIS_HOST").expect("INCORRECT REDIS ADDRESS")
+    }
+    
+    ///Get the password for Redis connection
+    fn get_redis_passwd() -> String {
+        dotenv().ok();
+        env::var("REDIS_PASSWD").expect("THE REDIS PASSWORD IS UNAVAILABLE")
+    }
+
+    fn get_redis_usr() -> String {
+        dotenv().ok();
+        env::var("REDIS_USR").expect("THE REDIS USER IS UNAVAILABLE")
+    }
+
+    /// Writes the fields of the hashes in KEYS[2..] if KEYS[1] has the token in ARGV[1] (an empty token is no fence).
+    /// Every write has the field, "set" or "del" and the value in ARGV.
+    const HWRITE_SCRIPT: &str = r#"
+        if ARGV[1] ~= '' and redis.call('GET', KEYS[1]) ~= ARGV[1] then
+            return 0
+        end
+        for i = 1, #KEYS - 1 do
+            if ARGV[3 * i] == 'set' then
+                redis.call('HSET', KEYS[i + 1], ARGV[3 * i - 1], ARGV[3 * i + 1])
+            else
+                redis.call('HDEL', KEYS[i + 1], ARGV[3 * i - 1])
+            end
+        end
+        return 1
+    "#;
+
+    /// Reconnection settings: the requests fail within a few seconds while Redis is not available
+    /// instead of waiting for the whole backoff of the connection manager
+    fn connection_manager_config() -> ConnectionManagerConfig {
+        ConnectionManagerConfig::new()
+            .set_number_of_retries(2)
+            .set_max_delay(1000)
+            .set_connection_timeout(Duration::from_secs(2))
+            .set_response_timeout(Duration::from_secs(5))
+    }
+
+    /// Store backend that keeps the data in Redis.
+    /// One multiplexed connection is opened on first use and shared by all callers,
+    /// the connection manager opens it again after a restart of Redis or a network error.
+    pub struct RedisStore {
+        client: Client,
+        connection: tokio::sync::OnceCell<ConnectionManager>,
+    }
+
+    impl RedisStore {
+
+        /// Creates the store from the REDIS_HOST, REDIS_USR and REDIS_PASSWD variables
+        pub fn from_env() -> Self {
+            let conn_str = format!("redis://{}:{}@{}:6380/0", get_redis_usr(), get_redis_passwd(), get_redis_host());
+            RedisStore {
+                client: Client::open(conn_str).expect("INCORRECT REDIS CONNECTION STRING"),
+                connection: tokio::sync::OnceCell::new(),
+            }
+        }
+
+        async fn connection(&self) -> StoreResult<ConnectionManager> {
+            let connection = self.connection
+                .get_or_try_init(|| self.client.get_connection_manager_with_config(connection_manager_config()))
+                .await?;
+            Ok(connection.clone())
+        }
+    }
+
+    #[rocket::async_trait]
+    impl Store for RedisStore {
+
+        async fn hget(&self, key: &str, field: &str) -> StoreResult<Option<String>> {
+            Ok(self.connection().await?.hget(key, field).await?)
+        }
+
+        async fn hgetall(&self, key: &str) -> StoreResult<HashMap<String, String>> {
+            Ok(self.connection().await?.hgetall(key).await?)
+        }
+
+        async fn hset_multiple(&self, key: &str, values: &[(String, String)]) -> StoreResult<()> {
+            Ok(self.connection().await?.hset_multiple(key, values).await?)
+        }
+
+        async fn hdel(&self, key: &str, field: &str) -> StoreResult<bool> {
+            let removed: i64 = self.connection().await?.hdel(key, field).await?;
+            Ok(removed > 0)
+        }
+
+        async fn hincr(&self, key: &str, field: &str, delta: i64, ttl: Option<Duration>) -> StoreResult<i64> {
+            let mut conn = self.connection().await?;
+            let mut pipe = redis::pipe();
+            pipe.hincr(key, field, delta);
+            if let Some(ttl) = ttl {
+                pipe.pexpire(key, ttl.as_millis() as i64).ignore();
+            }
+            let (value,): (i64,) = pipe.query_async(&mut conn).await?;
+            Ok(value)
+        }
+
+        async fn bump_version(&self, key: &str, time: Option<i64>) -> StoreResult<(u64, i64)> {
+            let mut conn = self.connection().await?;
+            let script = redis::Script::new(BUMP_VERSION_SCRIPT);
+            let mut invocation = script.key(key);
+            // An empty time is no time
+            invocation.arg(time.map(|time| time.to_string()).unwrap_or_default());
+            let (version, modified_at): (u64, i64) = invocation.invoke_async(&mut conn).await?;
+            Ok((version, modified_at))
+        }
+
+        async fn lpush_capped(&self, key: &str, value: &str, max_len: usize) -> StoreResult<()> {
+            let mut conn = self.connection().await?;
+            redis::pipe()
+                .lpush(key, value).ignore()
+                .ltrim(key, 0, max_len as isize - 1).ignore()
+                .query_async::<()>(&mut conn)
+                .await?;
+            Ok(())
+        }
+
+        async fn lrange(&self, key: &str, start: isize, stop: isize) -> StoreResult<Vec<String>> {
+            Ok(self.connection().await?.lrange(key, start, stop).await?)
+        }
+
+        async fn rtrim(&self, key: &str, count: usize) -> StoreResult<()> {
+            // The indexes from the tail do not change when a value is pushed to the head,
+            // Redis removes the key when the list is empty
+            Ok(self.connection().await?.ltrim::<_, ()>(key, 0, -(count as isize) - 1).await?)
+        }
+
+        async fn take_token(&self, key: &str, capacity: f64, refill_per_second: f64) -> StoreResult<(bool, f64)> {
+            let mut conn = self.connection().await?;
+            let (allowed, tokens): (i64, String) = redis::Script::new(TAKE_TOKEN_SCRIPT)
+                .key(key)
+                .arg(capacity)
+                .arg(refill_per_second)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok((allowed == 1, tokens.parse().unwrap_or(0.0)))
+        }
+
+        async fn publish(&self, channel: &str, message: &str) -> StoreResult<()> {
+            Ok(self.connection().await?.publish(channel, message).await?)
+        }
+
+        async fn subscribe(&self, channel: &str) -> StoreResult<BoxStream<'static, String>> {
+            // A subscribed connection can not run other commands, so it is not shared
+            let mut pubsub = self.client.get_async_pubsub().await?;
+            pubsub.subscribe(channel).await?;
+            Ok(pubsub.into_on_message().map(|message| message.get_payload().unwrap_or_default()).boxed())
+        }
+
+        async fn xadd_capped(&self, key: &str, fields: &[(String, String)], max_len: usize) -> StoreResult<String> {
+            let mut conn = self.connection().await?;
+            let mut cmd = redis::cmd("XADD");
+            cmd.arg(key).arg("MAXLEN").arg("~").arg(max_len).arg("*");
+            for (field, value) in fields {
+                cmd.arg(field).arg(value);
+            }
+            Ok(cmd.query_async(&mut conn).await?)
+        }
+
+        async fn acquire_lease(&self, key: &str, token_key: &str, holder: &str, ttl: Duration) -> StoreResult<Option<u64>> {
+            let mut conn = self.connection().await?;
+            let token: u64 = redis::Script::new(ACQUIRE_LEASE_SCRIPT)
+                .key(key)
+                .key(token_key)
+                .arg(holder)
+                .arg(ttl.as_millis() as u64)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(Some(token).filter(|token| *token > 0))
+        }
+
+        async fn release_lease(&self, key: &str, holder: &str) -> StoreResult<()> {
+            let mut conn = self.connection().await?;
+            let _: i64 = redis::Script::new(RELEASE_LEASE_SCRIPT)
+                .key(key)
+                .arg(holder)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(())
+        }
+
+        async fn hwrite(&self, writes: &[HashWrite], fence: Option<(&str, u64)>) -> StoreResult<bool> {
+            let mut conn = self.connection().await?;
+            let script = redis::Script::new(HWRITE_SCRIPT);
+            let mut script = script.prepare_invoke();
+            match fence {
+                Some((token_key, token)) => script.key(token_key).arg(token),
+                None => script.key("").arg(""),
+            };
+            for (key, field, value) in writes {
+                script.key(key).arg(field);
+                match value {
+                    Some(value) => script.arg("set").arg(value),
+                    None => script.arg("del").arg(""),
+                };
+            }
+            let written: i64 = script.invoke_async(&mut conn).await?;
+            Ok(written == 1)
+        }
+
+        async fn xgroup_create(&self, key: &str, group: &str) -> StoreResult<()> {
+            let mut conn = self.connection().await?;
+            let result: redis::RedisResult<()> = redis::cmd("XGROUP")
+                .arg("CREATE")
+                .arg(key)
+                .arg(group)
+                .arg("$")
+                .arg("MKSTREAM")
+                .query_async(&mut conn)
+                .await;
+            match result {
+                Err(e) if e.code() == Some("BUSYGROUP") => Ok(()),
+                result => Ok(result?),
+            }
+        }
+    }
+
+    /// Entry of a stream: the ID and the fields
+    type StreamEntry = (String, Vec<(String, String)>);
+
+    /// Stream of the memory store with the last ID (milliseconds and sequence number),
+    /// which is kept when the entries are trimmed, as in Redis
+    #[derive(Default)]
+    struct MemoryStream {
+        entries: Vec<StreamEntry>,
+        last_id: (i64, u64),
+    }
+
+    impl MemoryStream {
+
+        /// Returns the next ID: the current time, or the time of the last ID with the next sequence number
+        /// if the clock has not moved forward since then
+        fn next_id(&mut self) -> String {
+            let now = chrono::Utc::now().timestamp_millis();
+            self.last_id = match self.last_id {
+                (millis, sequence) if now <= millis => (millis, sequence + 1),
+                _ => (now, 0),
+            };
+            format!("{}-{}", self.last_id.0, self.last_id.1)
+        }
+    }
+
+    /// Data of the memory store
+    #[derive(Default)]
+    struct MemoryData {
+        strings: HashMap<String, String>,
+        hashes: HashMap<String, HashMap<String, String>>,
+        lists: HashMap<String, Vec<String>>,
+        streams: HashMap<String, MemoryStream>,
+        expires: HashMap<String, Instant>,
+    }
+
+    impl MemoryData {
+
+        /// Removes the key if its time has passed
+        fn expire(&mut self, key: &str) {
+            if self.expires.get(key).is_some_and(|expires_at| *expires_at <= Instant::now()) {
+                self.expires.remove(key);
+                self.strings.remove(key);
+                self.hashes.remove(key);
+                self.lists.remove(key);
+                self.streams.remove(key);
+            }
+        }
+    }
+
+    /// Converts an index of Redis (negative values count from the end) to a position in a list
+    fn list_position(index: isize, len: usize) -> usize {
+        match index < 0 {
+            true => len.saturating_sub(index.unsigned_abs()),
+            false => index as usize,
+        }
+    }
+
+    /// Number of messages kept for a slow subscriber of the memory store
+    const MEMORY_CHANNEL_CAPACITY: usize = 256;
+
+    /// Store backend that keeps the data in the memory of the process.
+    /// It is used to run the service without Redis, for example to replay the recorded data of the alarm API.
+    /// Published messages reach only the subscribers in the same process.
+    #[derive(Default)]
+    pub struct MemoryStore {
+        data: Mutex<MemoryData>,
+        channels: Mutex<HashMap<String, broadcast::Sender<String>>>,
+    }
+
+    impl MemoryStore {
+
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        fn data(&self, key: &str) -> MutexGuard<'_, MemoryData> {
+            let mut data = self.data.lock().unwrap();
+            data.expire(key);
+            data
+        }
+    }
+
+    #[rocket::async_trait]
+    impl Store for MemoryStore {
+
+        async fn hget(&self, key: &str, field: &str) -> StoreResult<Option<String>> {
+            Ok(self.data(key).hashes.get(key).and_then(|hash| hash.get(field).cloned()))
+        }
+
+        async fn hgetall(&self, key: &str) -> StoreResult<HashMap<String, String>> {
+            Ok(self.data(key).hashes.get(key).cloned().unwrap_or_default())
+        }
+
+        async fn hset_multiple(&self, key: &str, values: &[(String, String)]) -> StoreResult<()> {
+            self.data(key).hashes.entry(key.to_string()).or_default().extend(values.iter().cloned());
+            Ok(())
+        }
+
+        async fn hdel(&self, key: &str, field: &str) -> StoreResult<bool> {
+            Ok(self.data(key).hashes.get_mut(key).is_some_and(|hash| hash.remove(field).is_some()))
+        }
+
+        async fn hincr(&self, key: &str, field: &str, delta: i64, ttl: Option<Duration>) -> StoreResult<i64> {
+            let mut data = self.data(key);
+            let value = data.hashes.entry(key.to_string()).or_default().entry(field.to_string()).or_insert_with(|| "0".to_string());
+            let result = value.parse::<i64>().map_err(|e| StoreError(e.to_string()))? + delta;
+            *value = result.to_string();
+            if let Some(ttl) = ttl {
+                data.expires.insert(key.to_string(), Instant::now() + ttl);
+            }
+            Ok(result)
+        }
+
+        async fn bump_version(&self, key: &str, time: Option<i64>) -> StoreResult<(u64, i64)> {
+
+            let mut data = self.data(key);
+            let hash = data.hashes.entry(key.to_string()).or_default();
+            let stored = hash.get("modified_at").and_then(|value| value.parse::<i64>().ok());
+            let now = chrono::Utc::now().timestamp();
+            let modified_at = match (time, stored) {
+                (Some(time), None) => time,
+                (Some(time), Some(stored)) if time > stored => time,
+                (_, Some(stored)) if now <= stored => stored + 1,
+                _ => now,
+            };
+            let version = hash.get("version").and_then(|value| value.parse::<u64>().ok()).unwrap_or(0) + 1;
+            hash.insert("modified_at".to_string(), modified_at.to_string());
+            hash.insert("version".to_string(), version.to_string());
+            Ok((version, modified_at))
+        }
+
+        async fn lpush_capped(&self, key: &str, value: &str, max_len: usize) -> StoreResult<()> {
+            let mut data = self.data(key);
+            let list = data.lists.entry(key.to_string()).or_default();
+            list.insert(0, value.to_string());
+            list.truncate(max_len);
+            Ok(())
+        }
+
+        async fn lrange(&self, key: &str, start: isize, stop: isize) -> StoreResult<Vec<String>> {
+            let data = self.data(key);
+            let list = data.lists.get(key).map(Vec::as_slice).unwrap_or_default();
+            // A negative stop before the head of the list selects nothing, as in Redis
+            if stop < 0 && stop.unsigned_abs() > list.len() {
+                return Ok(Vec::new());
+            }
+            let start = list_position(start, list.len());
+            let stop = (list_position(stop, list.len()) + 1).min(list.len());
+            Ok(list.get(start..stop).map(<[String]>::to_vec).unwrap_or_default())
+        }
+
+        async fn rtrim(&self, key: &str, count: usize) -> StoreResult<()> {
+            let mut data = self.data(key);
+            if let Some(list) = data.lists.get_mut(key) {
+                list.truncate(list.len().saturating_sub(count));
+                if list.is_empty() {
+                    data.lists.remove(key);
+                }
+            }
+            Ok(())
+        }
+
+        async fn take_token(&self, key: &str, capacity: f64, refill_per_second: f64) -> StoreResult<(bool, f64)> {
+
+            let mut data = self.data(key);
+            let now = chrono::Utc::now().timestamp_micros() as f64 / 1_000_000.0;
+            let bucket = data.hashes.entry(key.to_string()).or_default();
+            let value = |field: &str| bucket.get(field).and_then(|value| value.parse::<f64>().ok());
+
+            let updated_at = value("updated_at").unwrap_or(now);
+            let mut tokens = capacity.min(value("tokens").unwrap_or(capacity) + (now - updated_at).max(0.0) * refill_per_second);
+            let allowed = tokens >= 1.0;
+            if allowed {
+                tokens -= 1.0;
+            }
+
+            bucket.insert("tokens".to_string(), tokens.to_string());
+            bucket.insert("updated_at".to_string(), now.to_string());
+            let ttl = Duration::from_secs_f64(capacity / refill_per_second + 1.0);
+            data.expires.insert(key.to_string(), Instant::now() + ttl);
+            Ok((allowed, tokens))
+        }
+
+        async fn publish(&self, channel: &str, message: &str) -> StoreResult<()> {
+            if let Some(sender) = self.channels.lock().unwrap().get(channel) {
+                // No subscribers is not an error
+                let _ = sender.send(message.to_string());
+            }
+            Ok(())
+        }
+
+        async fn subscribe(&self, channel: &str) -> StoreResult<BoxStream<'static, String>> {
+            let receiver = self.channels
+                .lock()
+                .unwrap()
+                .entry(channel.to_string())
+                .or_insert_with(|| broadcast::channel(MEMORY_CHANNEL_CAPACITY).0)
+                .subscribe();
+
+            Ok(futures::stream::unfold(receiver, |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(message) => return Some((message, receiver)),
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }).boxed())
+        }
+
+        async fn xadd_capped(&self, key: &str, fields: &[(String, String)], max_len: usize) -> StoreResult<String> {
+            let mut data = self.data(key);
+            let stream = data.streams.entry(key.to_string()).or_default();
+            let id = stream.next_id();
+            stream.entries.push((id.clone(), fields.to_vec()));
+            let excess = stream.entries.len().saturating_sub(max_len);
+            stream.entries.drain(..excess);
+            Ok(id)
+        }
+
+        async fn xgroup_create(&self, key: &str, _group: &str) -> StoreResult<()> {
+            self.data(key).streams.entry(key.to_string()).or_default();
+            Ok(())
+        }
+
+        async fn acquire_lease(&self, key: &str, token_key: &str, holder: &str, ttl: Duration) -> StoreResult<Option<u64>> {
+
+            let mut data = self.data(key);
+            let token = match data.strings.get(key) {
+                None => {
+                    let token = data.strings.get(token_key).and_then(|value| value.parse::<u64>().ok()).unwrap_or(0) + 1;
+                    data.strings.insert(token_key.to_string(), token.to_string());
+                    data.strings.insert(key.to_string(), holder.to_string());
+                    token
+                }
+                Some(current) if current == holder => data.strings.get(token_key).and_then(|value| value.parse().ok()).unwrap_or(0),
+                Some(_) => return Ok(None),
+            };
+            data.expires.insert(key.to_string(), Instant::now() + ttl);
+            Ok(Some(token).filter(|token| *token > 0))
+        }
+
+        async fn release_lease(&self, key: &str, holder: &str) -> StoreResult<()> {
+            let mut data = self.data(key);
+            if data.strings.get(key).is_some_and(|current| current == holder) {
+                data.strings.remove(key);
+                data.expires.remove(key);
+            }
+            Ok(())
+        }
+
+        async fn hwrite(&self, writes: &[HashWrite], fence: Option<(&str, u64)>) -> StoreResult<bool> {
+
+            let mut data = self.data.lock().unwrap();
+            if let Some((token_key, token)) = fence {
+                data.expire(token_key);
+                if data.strings.get(token_key) != Some(&token.to_string()) {
+                    return Ok(false);
+                }
+            }
+
+            for (key, field, value) in writes {
+                data.expire(key);
+                let hash = data.hashes.entry(key.clone()).or_default();
+                match value {
+                    Some(value) => hash.insert(field.clone(), value.clone()),
+                    None => hash.remove(field),
+                };
+            }
+            Ok(true)
+        }
+    }
+
+    static STORE: OnceCell<Box<dyn Store>> = OnceCell::new();
+
+    /// Returns the store backend of the service.
+    /// The backend is selected by STORE_BACKEND: "redis" (default) or "memory".
+    /// The connection to Redis is opened on the first call.
+    pub fn store() -> &'static dyn Store {
+        STORE
+            .get_or_init(|| {
+                dotenv().ok();
+                match env::var("STORE_BACKEND").as_deref() {
+                    Ok("memory") => Box::new(MemoryStore::new()),
+                    Ok("redis") | Err(_) => Box::new(RedisStore::from_env()),
+                    Ok(_) => panic!("INCORRECT STORE_BACKEND, EXPECTED redis OR memory"),
+                }
+            })
+            .as_ref()
+    }
+
+    /// Replaces the store backend before its first use, for example with a MemoryStore in the tests.
+    /// Returns false if the store is already initialized.
+    pub fn set_store(backend: Box<dyn Store>) -> bool {
+        STORE.set(backend).is_ok()
+    }
+
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        fn write(key: &str, field: &str, value: Option<&str>) -> HashWrite {
+            (key.to_string(), field.to_string(), value.map(str::to_string))
+        }
+
+        #[rocket::async_test]
+        async fn fenced_write_is_rejected_after_a_new_lease() {
+
+            let store = MemoryStore::new();
+            let ttl = Duration::from_millis(50);
+            let old_token = store.acquire_lease("lease", "token", "old", ttl).await.unwrap().unwrap();
+
+            let writes = [write("regions", "9", Some("true")), write("regions", "14", None)];
+            store.hset("regions", "14", "true").await.unwrap();
+            assert!(store.hwrite(&writes, Some(("token", old_token))).await.unwrap());
+            assert_eq!(store.hget("regions", "9").await.unwrap().as_deref(), Some("true"));
+            assert_eq!(store.hget("regions", "14").await.unwrap(), None);
+
+            // The lease of the old holder expires while it is busy and another holder takes it
+            tokio::time::sleep(ttl * 2).await;
+            let new_token = store.acquire_lease("lease", "token", "new", ttl).await.unwrap().unwrap();
+            assert!(new_token > old_token);
+
+            assert!(!store.hwrite(&[write("regions", "9", Some("false"))], Some(("token", old_token))).await.unwrap());
+            assert_eq!(store.hget("regions", "9").await.unwrap().as_deref(), Some("true"));
+            assert!(store.hwrite(&[write("regions", "9", Some("false"))], Some(("token", new_token))).await.unwrap());
+            assert!(store.hwrite(&[write("regions", "20", Some("true"))], None).await.unwrap());
+            assert_eq!(store.hget("regions", "9").await.unwrap().as_deref(), Some("false"));
+        }
+
+        #[rocket::async_test]
+        async fn version_time_never_goes_back() {
+
+            let store = MemoryStore::new();
+            let now = chrono::Utc::now().timestamp();
+
+            // A later time is taken as is, the changes within one second get the next seconds
+            assert_eq!(store.bump_version("status", Some(now + 60)).await.unwrap(), (1, now + 60));
+            assert_eq!(store.bump_version("status", None).await.unwrap(), (2, now + 61));
+            assert_eq!(store.bump_version("status", Some(now - 60)).await.unwrap(), (3, now + 62));
+            assert_eq!(store.hget("status", "modified_at").await.unwrap(), Some((now + 62).to_string()));
+
+            let (version, modified_at) = MemoryStore::new().bump_version("status", Some(now - 60)).await.unwrap();
+            assert_eq!((version, modified_at), (1, now - 60));
+        }
+
+        #[rocket::async_test]
+        async fn stream_ids_are_unique_in_a_full_stream() {
+
+            let store = MemoryStore::new();
+            let fields = [("event".to_string(), "alarm_started".to_string())];
+            // Most of the entries are added in the same millisecond
+            let mut ids = std::collections::HashSet::new();
+            for _ in 0..50 {
+                assert!(ids.insert(store.xadd_capped("events", &fields, 3).await.unwrap()));
+            }
+            assert_eq!(store.data("events").streams["events"].entries.len(), 3);
+        }
+
+        #[rocket::async_test]
+        async fn expired_stream_is_removed() {
+
+            let store = MemoryStore::new();
+            store.xadd_capped("events", &[], 10).await.unwrap();
+            store.data("events").expires.insert("events".to_string(), Instant::now());
+            assert!(!store.data("events").streams.contains_key("events"));
+        }
+
+        #[rocket::async_test]
+        async fn tail_is_trimmed_by_the_indexes_from_the_tail() {
+
+            let store = MemoryStore::new();
+            for value in ["1", "2", "3", "4"] {
+                store.lpush_capped("list", value, 10).await.unwrap();
+            }
+            // The oldest values are read from the tail, a range before the head is empty as in Redis
+            assert_eq!(store.lrange("list", -2, -1).await.unwrap(), vec!["2", "1"]);
+            assert!(store.lrange("list", -20, -11).await.unwrap().is_empty());
+
+            // A value pushed after the read does not shift the values that are removed
+            store.lpush_capped("list", "5", 10).await.unwrap();
+            store.rtrim("list", 2).await.unwrap();
+            assert_eq!(store.lrange("list", 0, -1).await.unwrap(), vec!["5", "4", "3"]);
+
+            store.rtrim("list", 5).await.unwrap();
+            assert!(store.lrange("list", 0, -1).await.unwrap().is_empty());
+        }
+    }
+
+}
@@ -0,0 +1,808 @@
+pub mod telegram {
+
+    use dotenv::dotenv;
+    use std::collections::{HashMap, VecDeque};
+    use std::env;
+    use std::time::Duration;
+    use log::debug;
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+    use tokio::time::Instant;
+
+    use crate::modules::{
+        delivery_policy::delivery_policy::{DeliveryPolicy, PolicyEvent, PolicyFilter, QuietHours},
+        diff::diff::{AlarmEvent, AlarmEventKind},
+        notifications::notifications::{Notifier, NotifyError},
+        other_data::other_data::{find_region, get_regions, Region},
+        leader::leader::is_leader,
+        processing::processing::{get_all_alarm_statuses, AlarmScope},
+        store::store::store,
+    };
+
+    /// Seconds the Bot API keeps a getUpdates request open
+    const LONG_POLL_TIMEOUT: u64 = 30;
+
+    /// The Bot API allows about 30 messages per second for a bot
+    const GLOBAL_SEND_INTERVAL: Duration = Duration::from_millis(35);
+
+    /// Number of messages waiting to be sent
+    const SEND_QUEUE_CAPACITY: usize = 1024;
+
+    /// Hash with the subscription of every chat
+    const SUBSCRIPTIONS_KEY: &str = "telegram:chats";
+
+    /// Settings of the bot from the TELEGRAM_* variables
+    #[derive(Debug, Clone)]
+    pub struct TelegramConfig {
+        pub token: String,
+        pub api_base: String,
+        pub chat_interval: Duration,
+        pub default_language: Language,
+    }
+
+    ///Get the settings of the bot.
+    ///Returns None if TELEGRAM_BOT_TOKEN is not set.
+    fn get_telegram_config() -> Option<TelegramConfig> {
+        dotenv().ok();
+        let token = env::var("TELEGRAM_BOT_TOKEN").ok().filter(|value| !value.is_empty())?;
+
+        Some(TelegramConfig {
+            token,
+            api_base: env::var("TELEGRAM_API_BASE")
+                .unwrap_or_else(|_| "https://api.telegram.org".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            chat_interval: Duration::from_millis(
+                env::var("TELEGRAM_CHAT_INTERVAL_MS")
+                    .map(|value| value.parse().expect("INCORRECT TELEGRAM_CHAT_INTERVAL_MS"))
+                    .unwrap_or(1000),
+            ),
+            default_language: match env::var("TELEGRAM_DEFAULT_LANGUAGE").as_deref() {
+                Ok("en") => Language::En,
+                _ => Language::Uk,
+            },
+        })
+    }
+
+    /// Language of the messages sent to a chat
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Language {
+        Uk,
+        En,
+    }
+
+    /// Regions a chat is subscribed to, the language of the chat and its delivery rules.
+    /// The debounce time is also used as the flap window of the chat.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ChatSubscription {
+        pub regions: Vec<i32>,
+        pub language: Language,
+        #[serde(default)]
+        pub debounce_seconds: u64,
+        #[serde(default)]
+        pub quiet_hours: Option<String>,
+    }
+
+    impl ChatSubscription {
+
+        fn new(language: Language) -> Self {
+            ChatSubscription { regions: Vec::new(), language, debounce_seconds: 0, quiet_hours: None }
+        }
+
+        fn policy(&self) -> DeliveryPolicy {
+            DeliveryPolicy {
+                debounce: Duration::from_secs(self.debounce_seconds),
+                flap_window: Duration::from_secs(self.debounce_seconds),
+                quiet_hours: self.quiet_hours.as_deref().and_then(QuietHours::parse),
+            }
+        }
+    }
+
+    /// Event addressed to a chat, waiting for the delivery rules of the chat
+    #[derive(Debug)]
+    struct ChatEvent {
+        event: AlarmEvent,
+        language: Language,
+        policy: DeliveryPolicy,
+    }
+
+    impl PolicyEvent for ChatEvent {
+
+        fn toggle(&self) -> (String, Option<bool>) {
+            self.event.toggle()
+        }
+
+        fn is_critical(&self) -> bool {
+            self.event.is_critical()
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct BotResponse<T> {
+        ok: bool,
+        result: Option<T>,
+        description: Option<String>,
+        parameters: Option<ResponseParameters>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ResponseParameters {
+        retry_after: Option<u64>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Update {
+        update_id: i64,
+        message: Option<Message>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Message {
+        chat: Chat,
+        text: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Chat {
+        id: i64,
+    }
+
+    /// Message waiting in the send queue
+    #[derive(Debug)]
+    struct OutgoingMessage {
+        chat_id: i64,
+        text: String,
+        /// The message was rejected once because of the limits of the Bot API
+        is_retry: bool,
+    }
+
+    impl OutgoingMessage {
+
+        fn new(chat_id: i64, text: String) -> Self {
+            OutgoingMessage { chat_id, text, is_retry: false }
+        }
+    }
+
+    /// Client of the Bot API
+    struct Bot {
+        client: reqwest::Client,
+        config: TelegramConfig,
+    }
+
+    impl Bot {
+
+        fn method_url(&self, method: &str) -> String {
+            format!("{}/bot{}/{}", self.config.api_base, self.config.token, method)
+        }
+
+        /// Waits for new updates after the offset
+        async fn get_updates(&self, offset: i64) -> Result<Vec<Update>, String> {
+
+            let response: BotResponse<Vec<Update>> = self.client
+                .get(self.method_url("getUpdates"))
+                .query(&[("offset", offset.to_string()), ("timeout", LONG_POLL_TIMEOUT.to_string())])
+                .timeout(Duration::from_secs(LONG_POLL_TIMEOUT + 10))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .json()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            match response.ok {
+                true => Ok(response.result.unwrap_or_default()),
+                false => Err(response.description.unwrap_or_default()),
+            }
+        }
+
+        /// Sends a message. Returns the number of seconds to wait
+        /// if the Bot API rejected the message because of its own limits.
+        async fn send_message(&self, message: &OutgoingMessage) -> Result<(), Option<u64>> {
+
+            let result = self.client
+                .post(self.method_url("sendMessage"))
+                .json(&serde_json::json!({ "chat_id": message.chat_id, "text": message.text }))
+                .send()
+                .await;
+
+            let response: BotResponse<serde_json::Value> = match result {
+                Ok(response) => response.json().await.map_err(|_| None)?,
+                Err(e) => {
+                    debug!("{} - error sending a Telegram message to {}\n{}", chrono::Local::now(), message.chat_id, e);
+                    return Err(None);
+                }
+            };
+
+            if response.ok {
+                return Ok(());
+            }
+            debug!("{} - Telegram rejected a message to {}: {:?}", chrono::Local::now(), message.chat_id, response.description);
+            Err(response.parameters.and_then(|parameters| parameters.retry_after))
+        }
+    }
+
+    /// Returns the subscription of a chat
+    async fn get_subscription(chat_id: i64, default_language: Language) -> ChatSubscription {
+        store()
+            .hget(SUBSCRIPTIONS_KEY, &chat_id.to_string())
+            .await
+            .ok()
+            .flatten()
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_else(|| ChatSubscription::new(default_language))
+    }
+
+    /// Saves the subscription of a chat
+    async fn set_subscription(chat_id: i64, subscription: &ChatSubscription) {
+        let value = serde_json::to_string(subscription).unwrap();
+        if let Err(e) = store().hset(SUBSCRIPTIONS_KEY, &chat_id.to_string(), &value).await {
+            debug!("{} - {} - error saving the Telegram subscription\n{}", chrono::Local::now(), chat_id, e);
+        }
+    }
+
+    /// Returns the subscriptions of all chats
+    async fn get_all_subscriptions() -> HashMap<i64, ChatSubscription> {
+        store()
+            .hgetall(SUBSCRIPTIONS_KEY)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(chat_id, value)| Some((chat_id.parse().ok()?, serde_json::from_str(&value).ok()?)))
+            .collect()
+    }
+
+    fn region_name(region: &Region, language: Language) -> &str {
+        match language {
+            Language::Uk => &region.names.uk,
+            Language::En => &region.names.en,
+        }
+    }
+
+    /// Converts an RFC 3339 time to the local time of the server
+    fn format_time(value: &str) -> String {
+        match chrono::DateTime::parse_from_rfc3339(value) {
+            Ok(time) => time.with_timezone(&chrono::Local).format("%H:%M").to_string(),
+            Err(_) => value.to_string(),
+        }
+    }
+
+    /// Text of the notification about a change of the alarm state
+    fn event_text(event: &AlarmEvent, region: &Region, language: Language) -> String {
+        let name = region_name(region, language);
+        let time = format_time(&event.changed_at);
+        match (&event.kind, language) {
+            (AlarmEventKind::Started { .. }, Language::Uk) => format!("🔴 Повітряна тривога: {} ({})", name, time),
+            (AlarmEventKind::Started { .. }, Language::En) => format!("🔴 Air raid alert: {} ({})", name, time),
+            (AlarmEventKind::Ended, Language::Uk) => format!("🟢 Відбій тривоги: {} ({})", name, time),
+            (AlarmEventKind::Ended, Language::En) => format!("🟢 All clear: {} ({})", name, time),
+            (AlarmEventKind::TypeAdded { alert_type }, Language::Uk) => format!("🟠 Тривога ({}): {} ({})", alert_type, name, time),
+            (AlarmEventKind::TypeAdded { alert_type }, Language::En) => format!("🟠 Alert ({}): {} ({})", alert_type, name, time),
+            (AlarmEventKind::TypeRemoved { alert_type }, Language::Uk) => format!("⚪ Відбій ({}): {} ({})", alert_type, name, time),
+            (AlarmEventKind::TypeRemoved { alert_type }, Language::En) => format!("⚪ All clear ({}): {} ({})", alert_type, name, time),
+            (AlarmEventKind::ScopeChanged { scope: AlarmScope::Full, .. }, Language::Uk) => format!("🔴 Тривога поширилась на всю область: {} ({})", name, time),
+            (AlarmEventKind::ScopeChanged { scope: AlarmScope::Full, .. }, Language::En) => format!("🔴 The alert now covers the whole region: {} ({})", name, time),
+            (AlarmEventKind::ScopeChanged { scope: AlarmScope::Partial, .. }, Language::Uk) => format!("🟡 Тривога лише в частині області: {} ({})", name, time),
+            (AlarmEventKind::ScopeChanged { scope: AlarmScope::Partial, .. }, Language::En) => format!("🟡 The alert now covers a part of the region: {} ({})", name, time),
+        }
+    }
+
+    fn help_text(language: Language) -> String {
+        match language {
+            Language::Uk => [
+                "/subscribe <id або назва> ... - підписатися на регіони",
+                "/unsubscribe <id або назва> ... | all - відписатися",
+                "/status - стан тривоги в регіонах підписки",
+                "/regions - список регіонів",
+                "/language uk|en - мова повідомлень",
+                "/debounce <секунди> - повідомляти про тривогу, лише якщо вона триває довше",
+                "/quiet 23:00-07:00 | off - не повідомляти в ці години про інші тривоги, крім повітряної",
+            ].join("\n"),
+            Language::En => [
+                "/subscribe <id or slug> ... - subscribe to regions",
+                "/unsubscribe <id or slug> ... | all - unsubscribe",
+                "/status - alarm state of the subscribed regions",
+                "/regions - list of regions",
+                "/language uk|en - language of the messages",
+                "/debounce <seconds> - announce only alarms that last longer",
+                "/quiet 23:00-07:00 | off - do not announce the alerts other than the air raid in these hours",
+            ].join("\n"),
+        }
+    }
+
+    /// Handles a command from a chat and returns the answer
+    async fn handle_command(chat_id: i64, text: &str, default_language: Language) -> String {
+
+        let mut words = text.split_whitespace();
+        // Commands in groups may have the name of the bot: /status@alarm_bot
+        let command = words.next().unwrap_or_default().split('@').next().unwrap_or_default().to_string();
+        let arguments: Vec<&str> = words.collect();
+
+        let regions = get_regions();
+        let mut subscription = get_subscription(chat_id, default_language).await;
+        let language = subscription.language;
+
+        match command.as_str() {
+            "/subscribe" | "/unsubscribe" => {
+
+                let is_subscribe = command == "/subscribe";
+                let mut unknown = Vec::new();
+
+                if !is_subscribe && arguments.contains(&"all") {
+                    subscription.regions.clear();
+                }
+                for token in arguments.iter().filter(|token| **token != "all") {
+                    match find_region(&regions, token) {
+                        Some(region) if is_subscribe && !subscription.regions.contains(&region.id) => subscription.regions.push(region.id),
+                        Some(region) if !is_subscribe => subscription.regions.retain(|id| *id != region.id),
+                        Some(_) => {}
+                        None => unknown.push(token.to_string()),
+                    }
+                }
+                set_subscription(chat_id, &subscription).await;
+
+                let names: Vec<&str> = subscription.regions
+                    .iter()
+                    .filter_map(|id| regions.iter().find(|region| region.id == *id))
+                    .map(|region| region_name(region, language))
+                    .collect();
+
+                let mut answer = match language {
+                    Language::Uk => format!("Підписка: {}", if names.is_empty() { "немає".to_string() } else { names.join(", ") }),
+                    Language::En => format!("Subscribed to: {}", if names.is_empty() { "none".to_string() } else { names.join(", ") }),
+                };
+                if !unknown.is_empty() {
+                    answer += &match language {
+                        Language::Uk => format!("\nНевідомі регіони: {}", unknown.join(", ")),
+                        Language::En => format!("\nUnknown regions: {}", unknown.join(", ")),
+                    };
+                }
+                answer
+            }
+            "/status" => {
+
+                let statuses = get_all_alarm_statuses().await;
+                let selected: Vec<&Region> = match subscription.regions.is_empty() {
+                    true => regions.iter().filter(|region| statuses.get(&region.id).copied().unwrap_or(false)).collect(),
+                    false => regions.iter().filter(|region| subscription.regions.contains(&region.id)).collect(),
+                };
+
+                if selected.is_empty() {
+                    return match language {
+                        Language::Uk => "Тривог немає".to_string(),
+                        Language::En => "No active alarms".to_string(),
+                    };
+                }
+
+                selected
+                    .iter()
+                    .map(|region| {
+                        let mark = if statuses.get(&region.id).copied().unwrap_or(false) { "🔴" } else { "🟢" };
+                        format!("{} {}", mark, region_name(region, language))
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            "/regions" => regions
+                .iter()
+                .map(|region| format!("{} {} - {}", region.id, region.codes.slug, region_name(region, language)))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            "/language" => {
+                subscription.language = match arguments.first().copied() {
+                    Some("en") => Language::En,
+                    Some("uk") => Language::Uk,
+                    _ => return help_text(language),
+                };
+                set_subscription(chat_id, &subscription).await;
+                match subscription.language {
+                    Language::Uk => "Мову змінено".to_string(),
+                    Language::En => "Language changed".to_string(),
+                }
+            }
+            "/debounce" => {
+                subscription.debounce_seconds = match arguments.first().and_then(|value| value.parse().ok()) {
+                    Some(seconds) => seconds,
+                    None => return help_text(language),
+                };
+                set_subscription(chat_id, &subscription).await;
+                match language {
+                    Language::Uk => format!("Затримка повідомлень: {} с", subscription.debounce_seconds),
+                    Language::En => format!("Notification delay: {} s", subscription.debounce_seconds),
+                }
+            }
+            "/quiet" => {
+                subscription.quiet_hours = match arguments.first().copied() {
+                    Some("off") => None,
+                    Some(value) => match QuietHours::parse(value) {
+                        Some(quiet_hours) => Some(quiet_hours.to_string()),
+                        None => return help_text(language),
+                    },
+                    None => return help_text(language),
+                };
+                set_subscription(chat_id, &subscription).await;
+                match (&subscription.quiet_hours, language) {
+                    (Some(quiet_hours), Language::Uk) => format!("Тихі години: {}", quiet_hours),
+                    (Some(quiet_hours), Language::En) => format!("Quiet hours: {}", quiet_hours),
+                    (None, Language::Uk) => "Тихі години вимкнено".to_string(),
+                    (None, Language::En) => "Quiet hours are off".to_string(),
+                }
+            }
+            _ => help_text(language),
+        }
+    }
+
+    /// Sends the queued messages.
+    /// Every chat gets at most one message per chat_interval and the bot stays under the global limit of the Bot API.
+    /// The messages wait in the queue of their chat, so a busy chat does not delay the messages to the other chats.
+    async fn run_sender(bot: &Bot, mut queue: mpsc::Receiver<OutgoingMessage>) {
+
+        let mut chats: HashMap<i64, VecDeque<OutgoingMessage>> = HashMap::new();
+        // Time when a chat may get its next message
+        let mut ready_at: HashMap<i64, Instant> = HashMap::new();
+        let mut is_closed = false;
+
+        loop {
+            while let Ok(message) = queue.try_recv() {
+                chats.entry(message.chat_id).or_default().push_back(message);
+            }
+
+            // The chat that has been waiting for its turn the longest goes first
+            let now = Instant::now();
+            let next = chats.keys().map(|chat_id| (ready_at.get(chat_id).copied().unwrap_or(now), *chat_id)).min();
+            ready_at.retain(|chat_id, ready| *ready > now || chats.contains_key(chat_id));
+
+            match next {
+                Some((ready, chat_id)) if ready <= now => {
+
+                    let messages = chats.get_mut(&chat_id).unwrap();
+                    let mut message = messages.pop_front().unwrap();
+                    if messages.is_empty() {
+                        chats.remove(&chat_id);
+                    }
+
+                    let next_ready = match bot.send_message(&message).await {
+                        // The message is sent once more when the chat may get it, the other chats do not wait
+                        Err(Some(retry_after)) if !message.is_retry => {
+                            message.is_retry = true;
+                            chats.entry(chat_id).or_default().push_front(message);
+                            Instant::now() + Duration::from_secs(retry_after)
+                        }
+                        _ => Instant::now() + bot.config.chat_interval,
+                    };
+                    ready_at.insert(chat_id, next_ready);
+                    tokio::time::sleep(GLOBAL_SEND_INTERVAL).await;
+                }
+                None if is_closed => return,
+                _ => tokio::select! {
+                    message = queue.recv(), if !is_closed => match message {
+                        Some(message) => chats.entry(message.chat_id).or_default().push_back(message),
+                        None => is_closed = true,
+                    },
+                    _ = tokio::time::sleep_until(next.map(|(ready, _)| ready).unwrap_or(now)), if next.is_some() => {}
+                },
+            }
+        }
+    }
+
+    /// Applies the delivery rules of every chat to its events
+    /// and queues the messages for the events that pass them
+    async fn run_chat_filter(mut events: mpsc::Receiver<(i64, ChatEvent)>, queue: mpsc::Sender<OutgoingMessage>) {
+
+        let regions = get_regions();
+        let mut filter = PolicyFilter::<(i64, i32), ChatEvent>::default();
+
+        loop {
+            let deadline = filter.next_deadline();
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some((chat_id, event)) => {
+                        let policy = event.policy;
+                        filter.push((chat_id, event.event.region_id), event, policy, Instant::now());
+                    }
+                    None => return,
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+            }
+
+            for ((chat_id, region_id), event) in filter.take_ready(Instant::now(), chrono::Local::now().time()) {
+                if let Some(region) = regions.iter().find(|region| region.id == region_id) {
+                    let message = OutgoingMessage::new(chat_id, event_text(&event.event, region, event.language));
+                    if queue.try_send(message).is_err() {
+                        debug!("{} - the Telegram send queue is full, a message to {} is dropped", chrono::Local::now(), chat_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Passes the events to the chats subscribed to their regions
+    pub struct TelegramNotifier {
+        queue: mpsc::Sender<(i64, ChatEvent)>,
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for TelegramNotifier {
+
+        fn name(&self) -> String {
+            "telegram".to_string()
+        }
+
+        /// Every chat has its own delivery rules, they are applied by run_chat_filter
+        fn uses_delivery_policy(&self) -> bool {
+            false
+        }
+
+        async fn notify(&self, event: &AlarmEvent) -> Result<(), NotifyError> {
+
+            let mut dropped = 0;
+            for (chat_id, subscription) in get_all_subscriptions().await {
+                if subscription.regions.contains(&event.region_id) {
+                    let chat_event = ChatEvent { event: event.clone(), language: subscription.language, policy: subscription.policy() };
+                    if self.queue.try_send((chat_id, chat_event)).is_err() {
+                        dropped += 1;
+                    }
+                }
+            }
+
+            match dropped {
+                0 => Ok(()),
+                _ => Err(NotifyError(format!("the queue is full, {} messages are dropped", dropped))),
+            }
+        }
+    }
+
+    /// Receives the commands with long polling and answers them
+    async fn run_commands(bot: &Bot, queue: mpsc::Sender<OutgoingMessage>) {
+
+        let mut offset = 0;
+
+        loop {
+            // Telegram allows only one getUpdates consumer of a bot
+            if !is_leader() {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+
+            let updates = match bot.get_updates(offset).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    debug!("{} - error getting Telegram updates\n{}", chrono::Local::now(), e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = offset.max(update.update_id + 1);
+
+                let (chat_id, text) = match update.message {
+                    Some(Message { chat, text: Some(text) }) if text.starts_with('/') => (chat.id, text),
+                    _ => continue,
+                };
+
+                debug!("{} - Telegram command from {}: {}", chrono::Local::now(), chat_id, text);
+                let answer = handle_command(chat_id, &text, bot.config.default_language).await;
+                let _ = queue.send(OutgoingMessage::new(chat_id, answer)).await;
+            }
+        }
+    }
+
+    /// Starts the Telegram bot if TELEGRAM_BOT_TOKEN is set.
+    /// The bot answers the commands of the chats, the returned notifier
+    /// sends the alarm events to the chats subscribed to their regions.
+    pub fn start_telegram_bot() -> Option<TelegramNotifier> {
+
+        let config = match get_telegram_config() {
+            Some(config) => config,
+            None => {
+                debug!("{} - TELEGRAM_BOT_TOKEN is not set, the Telegram bot is disabled", chrono::Local::now());
+                return None;
+            }
+        };
+
+        let bot = Arc::new(Bot { client: reqwest::Client::new(), config });
+        let (sender, receiver) = mpsc::channel(SEND_QUEUE_CAPACITY);
+
+        let sender_bot = Arc::clone(&bot);
+        tokio::spawn(async move { run_sender(&sender_bot, receiver).await });
+        let (events_sender, events_receiver) = mpsc::channel(SEND_QUEUE_CAPACITY);
+        tokio::spawn(run_chat_filter(events_receiver, sender.clone()));
+        tokio::spawn(async move { run_commands(&bot, sender).await });
+
+        Some(TelegramNotifier { queue: events_sender })
+    }
+
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use std::sync::Mutex;
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::{TcpListener, TcpStream};
+
+        use crate::modules::{
+            diff::diff::AlarmEvent,
+            processing::processing::AlarmScope,
+            store::store::{set_store, MemoryStore},
+        };
+
+        /// Message accepted by the fake Bot API
+        #[derive(Debug, Clone)]
+        struct SentMessage {
+            chat_id: i64,
+            text: String,
+            at: Instant,
+        }
+
+        /// Bot API that accepts every sendMessage request except the first one to the limited chat,
+        /// which is rejected with retry_after
+        struct FakeBotApi {
+            sent: Mutex<Vec<SentMessage>>,
+            limited_chat: Option<i64>,
+            is_limited: Mutex<bool>,
+        }
+
+        impl FakeBotApi {
+
+            /// Starts the server and returns the base URL of the Bot API
+            async fn start(limited_chat: Option<i64>) -> (Arc<FakeBotApi>, String) {
+
+                let api = Arc::new(FakeBotApi { sent: Mutex::new(Vec::new()), limited_chat, is_limited: Mutex::new(limited_chat.is_some()) });
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let url = format!("http://{}", listener.local_addr().unwrap());
+
+                let server = Arc::clone(&api);
+                tokio::spawn(async move {
+                    while let Ok((stream, _)) = listener.accept().await {
+                        tokio::spawn(Arc::clone(&server).serve(stream));
+                    }
+                });
+                (api, url)
+            }
+
+            /// Answers the requests of a keep-alive connection
+            async fn serve(self: Arc<Self>, stream: TcpStream) {
+
+                let mut stream = BufReader::new(stream);
+                loop {
+                    let mut content_length = 0;
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    stream.read_exact(&mut body).await.unwrap();
+
+                    let response = self.answer(serde_json::from_slice(&body).unwrap_or_default());
+                    let http = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", response.len(), response);
+                    stream.get_mut().write_all(http.as_bytes()).await.unwrap();
+                }
+            }
+
+            fn answer(&self, request: serde_json::Value) -> String {
+
+                let chat_id = request["chat_id"].as_i64().unwrap_or_default();
+                let mut is_limited = self.is_limited.lock().unwrap();
+                if *is_limited && Some(chat_id) == self.limited_chat {
+                    *is_limited = false;
+                    return serde_json::json!({ "ok": false, "description": "Too Many Requests", "parameters": { "retry_after": 1 } }).to_string();
+                }
+
+                let text = request["text"].as_str().unwrap_or_default().to_string();
+                self.sent.lock().unwrap().push(SentMessage { chat_id, text, at: Instant::now() });
+                serde_json::json!({ "ok": true, "result": {} }).to_string()
+            }
+
+            /// Waits until the Bot API accepts the number of messages
+            async fn wait_for(&self, count: usize) -> Vec<SentMessage> {
+                let result = tokio::time::timeout(Duration::from_secs(10), async {
+                    loop {
+                        let sent = self.sent.lock().unwrap().clone();
+                        if sent.len() >= count {
+                            return sent;
+                        }
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                });
+                result.await.unwrap_or_else(|_| panic!("the Bot API got only {:?}", self.sent.lock().unwrap()))
+            }
+        }
+
+        fn start_sender(url: String, chat_interval: Duration) -> mpsc::Sender<OutgoingMessage> {
+            let config = TelegramConfig { token: "test".to_string(), api_base: url, chat_interval, default_language: Language::En };
+            let bot = Bot { client: reqwest::Client::new(), config };
+            let (sender, receiver) = mpsc::channel(SEND_QUEUE_CAPACITY);
+            tokio::spawn(async move { run_sender(&bot, receiver).await });
+            sender
+        }
+
+        fn texts(sent: &[SentMessage]) -> Vec<(i64, &str)> {
+            sent.iter().map(|message| (message.chat_id, message.text.as_str())).collect()
+        }
+
+        #[rocket::async_test]
+        async fn busy_chat_does_not_delay_other_chats() {
+
+            let (api, url) = FakeBotApi::start(None).await;
+            let queue = start_sender(url, Duration::from_millis(500));
+
+            queue.send(OutgoingMessage::new(1, "first".to_string())).await.unwrap();
+            queue.send(OutgoingMessage::new(1, "second".to_string())).await.unwrap();
+            queue.send(OutgoingMessage::new(2, "other".to_string())).await.unwrap();
+
+            let sent = api.wait_for(3).await;
+            assert_eq!(texts(&sent), vec![(1, "first"), (2, "other"), (1, "second")]);
+            assert!(sent[1].at - sent[0].at < Duration::from_millis(500));
+            assert!(sent[2].at - sent[0].at >= Duration::from_millis(500));
+        }
+
+        #[rocket::async_test]
+        async fn rejected_message_is_sent_again_after_retry_after() {
+
+            let (api, url) = FakeBotApi::start(Some(3)).await;
+            let queue = start_sender(url, Duration::from_millis(100));
+            let started_at = Instant::now();
+
+            queue.send(OutgoingMessage::new(3, "limited".to_string())).await.unwrap();
+            queue.send(OutgoingMessage::new(4, "other".to_string())).await.unwrap();
+
+            let sent = api.wait_for(2).await;
+            assert_eq!(texts(&sent), vec![(4, "other"), (3, "limited")]);
+            assert!(sent[0].at - started_at < Duration::from_secs(1));
+            assert!(sent[1].at - started_at >= Duration::from_secs(1));
+        }
+
+        #[rocket::async_test]
+        async fn events_reach_only_the_subscribed_chats() {
+
+            set_store(Box::new(MemoryStore::new()));
+            handle_command(10, "/subscribe 9", Language::En).await;
+            handle_command(11, "/subscribe 14", Language::En).await;
+            handle_command(11, "/language uk", Language::En).await;
+
+            let (api, url) = FakeBotApi::start(None).await;
+            let queue = start_sender(url, Duration::from_millis(100));
+            let (events_sender, events_receiver) = mpsc::channel(SEND_QUEUE_CAPACITY);
+            tokio::spawn(run_chat_filter(events_receiver, queue));
+            let notifier = TelegramNotifier { queue: events_sender };
+
+            let event = |region_id: i32, kind: AlarmEventKind| AlarmEvent {
+                kind,
+                region_id,
+                region_name: String::new(),
+                alarm_active: true,
+                changed_at: "2026-10-19T05:00:00Z".to_string(),
+                detected_at: "2026-10-19T05:00:10Z".to_string(),
+                upstream_ids: vec![1],
+            };
+            notifier.notify(&event(9, AlarmEventKind::Started { scope: AlarmScope::Full })).await.unwrap();
+            notifier.notify(&event(14, AlarmEventKind::Started { scope: AlarmScope::Partial })).await.unwrap();
+            notifier.notify(&event(20, AlarmEventKind::Started { scope: AlarmScope::Full })).await.unwrap();
+
+            let mut sent = api.wait_for(2).await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(api.sent.lock().unwrap().len(), 2);
+
+            sent.sort_by_key(|message| message.chat_id);
+            assert_eq!(sent[0].chat_id, 10);
+            assert!(sent[0].text.starts_with("🔴 Air raid alert: Dnipropetrovsk"), "{}", sent[0].text);
+            assert_eq!(sent[1].chat_id, 11);
+            assert!(sent[1].text.starts_with("🔴 Повітряна тривога: Київська область"), "{}", sent[1].text);
+        }
+    }
+
+}
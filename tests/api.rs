@@ -0,0 +1,95 @@
+//! End-to-end tests of the first version of the API:
+//! the poller is driven tick by tick with the scripted responses of the alarm API.
+
+mod common;
+
+use std::collections::BTreeSet;
+
+use alerts_api_app::modules::other_data::other_data::get_regions;
+use common::Harness;
+use rocket::http::Status;
+use serde_json::Value;
+
+/// Identifiers of the regions with the active air raid alert in the response of /get_alarms
+fn active_regions(alarms: &Value) -> BTreeSet<i64> {
+    alarms
+        .as_array()
+        .expect("a list of the alarm states")
+        .iter()
+        .filter(|alarm| alarm["is_active_air_alarm"] == true)
+        .map(|alarm| alarm["location_uid"].as_i64().unwrap())
+        .collect()
+}
+
+#[rocket::async_test]
+async fn regions_are_listed_in_the_order_of_identifiers() {
+
+    let harness = Harness::start().await;
+    let regions = harness.get_json("/get_regions").await;
+    let regions = regions.as_array().expect("a list of regions");
+
+    let ids: Vec<i64> = regions.iter().map(|region| region["id"].as_i64().unwrap()).collect();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+    assert_eq!(ids.len(), get_regions().len());
+
+    let kyiv = regions.iter().find(|region| region["id"] == 31).expect("Kyiv in the registry");
+    assert_eq!(kyiv["name"], "м. Київ");
+}
+
+#[rocket::async_test]
+async fn alarm_state_follows_the_alarm_api() {
+
+    let harness = Harness::start().await;
+
+    // Oblast alerts: the air raid alerts count, the artillery shelling in Sumy Oblast does not
+    harness.respond_with("oblast_alerts.json", "Sun, 18 Oct 2026 05:35:02 GMT");
+    assert!(harness.tick().await);
+
+    let alarms = harness.get_json("/get_alarms?all=true").await;
+    assert_eq!(alarms.as_array().unwrap().len(), get_regions().len());
+    assert_eq!(active_regions(&alarms), BTreeSet::from([22, 31]));
+
+    let (status, etag, kharkiv) = harness.get("/get_alarm/22", None).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(kharkiv["location_uid"], 22);
+    assert_eq!(kharkiv["location_name"], "Харківська область");
+    assert_eq!(kharkiv["is_active_air_alarm"], true);
+    assert_eq!(harness.get_json("/get_alarm/20").await["is_active_air_alarm"], false);
+    let etag = etag.expect("the ETag header");
+
+    // 304: the state and the version of the data are kept
+    harness.respond_not_modified();
+    assert!(!harness.tick().await);
+
+    let (status, _, _) = harness.get("/get_alarm/22", Some(&etag)).await;
+    assert_eq!(status, Status::NotModified);
+    assert_eq!(active_regions(&harness.get_json("/get_alarms?all=true").await), BTreeSet::from([22, 31]));
+
+    // Raion alerts make the whole region active, the records with an unknown region
+    // or without location_uid are skipped, the alert in Kyiv ended
+    harness.respond_with("raion_alerts.json", "Sun, 18 Oct 2026 05:42:31 GMT");
+    assert!(harness.tick().await);
+
+    let (status, new_etag, _) = harness.get("/get_alarm/22", Some(&etag)).await;
+    assert_eq!(status, Status::Ok);
+    assert_ne!(new_etag.as_deref(), Some(etag.as_str()));
+
+    let alarms = harness.get_json("/get_alarms?ids=9,31,12").await;
+    assert_eq!(alarms.as_array().unwrap().iter().map(|alarm| alarm["location_uid"].as_i64().unwrap()).collect::<Vec<_>>(), vec![9, 31, 12]);
+    assert_eq!(active_regions(&alarms), BTreeSet::from([9]));
+    assert_eq!(active_regions(&harness.get_json("/get_alarms?all=true").await), BTreeSet::from([9, 22]));
+
+    let (status, _, _) = harness.get("/get_alarm/1000", None).await;
+    assert_eq!(status, Status::NotFound);
+
+    // All alerts ended
+    harness.respond_with("no_alerts.json", "Sun, 18 Oct 2026 06:20:05 GMT");
+    assert!(harness.tick().await);
+    assert!(active_regions(&harness.get_json("/get_alarms?all=true").await).is_empty());
+
+    // A failed poll keeps the last state
+    assert!(!harness.tick().await);
+    assert_eq!(harness.get_json("/get_alarm/9").await["is_active_air_alarm"], false);
+}
@@ -0,0 +1,115 @@
+//! Harness of the integration tests: the HTTP server with the in-memory store
+//! and a scripted source of the alarm data instead of the alarm API.
+//!
+//! The store and the sources are global, so they are set once per test binary
+//! and every test builds its own client on top of them.
+
+// Every test binary uses only a part of the harness
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+use alerts_api_app::{
+    build_rocket,
+    modules::{
+        fetch_data::fetch_data::{set_alert_source, AlertSource, RawResponse, SourceError},
+        processing::processing::poll_once,
+        store::store::{set_store, MemoryStore},
+    },
+};
+use rocket::{
+    http::{Header, Status},
+    local::asynchronous::Client,
+};
+use serde_json::Value;
+
+/// Responses of the scripted source, one per poll
+static SCRIPT: Mutex<VecDeque<RawResponse>> = Mutex::new(VecDeque::new());
+
+static INIT: Once = Once::new();
+
+/// Source that returns the queued responses in their order and fails when the queue is empty
+struct ScriptedSource;
+
+#[rocket::async_trait]
+impl AlertSource for ScriptedSource {
+
+    fn name(&self) -> &str {
+        "scripted"
+    }
+
+    async fn fetch(&self, _last_modified: &str) -> Result<RawResponse, SourceError> {
+        SCRIPT.lock().unwrap().pop_front().ok_or_else(|| "no scripted response".into())
+    }
+
+    /// The polls are made by the tests
+    fn poll_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Reads a response body of the alarm API from tests/fixtures
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("can not read the fixture {:?}: {}", path, e))
+}
+
+pub struct Harness {
+    pub client: Client,
+}
+
+impl Harness {
+
+    pub async fn start() -> Self {
+
+        INIT.call_once(|| {
+            // Only one instance polls in the tests, there is nobody to elect
+            std::env::set_var("LEADER_ELECTION", "false");
+            assert!(set_store(Box::new(MemoryStore::new())), "the store is already initialized");
+            assert!(set_alert_source(Box::new(ScriptedSource)), "the sources are already initialized");
+        });
+
+        let client = Client::tracked(build_rocket()).await.expect("valid rocket instance");
+        Harness { client }
+    }
+
+    /// Queues a response of the alarm API with the body from the fixture
+    pub fn respond_with(&self, fixture_name: &str, last_modified: &str) {
+        let headers = BTreeMap::from([("last-modified".to_string(), last_modified.to_string())]);
+        SCRIPT.lock().unwrap().push_back(RawResponse { status: 200, headers, body: fixture(fixture_name) });
+    }
+
+    /// Queues the "304 Not Modified" response of the alarm API
+    pub fn respond_not_modified(&self) {
+        SCRIPT.lock().unwrap().push_back(RawResponse { status: 304, headers: BTreeMap::new(), body: String::new() });
+    }
+
+    /// Makes one poll of the poller, returns true if new data was saved
+    pub async fn tick(&self) -> bool {
+        poll_once().await
+    }
+
+    /// Sends a GET request and returns the status, the ETag header and the JSON body
+    pub async fn get(&self, uri: &str, if_none_match: Option<&str>) -> (Status, Option<String>, Value) {
+
+        let mut request = self.client.get(uri.to_string());
+        if let Some(etag) = if_none_match {
+            request = request.header(Header::new("If-None-Match", etag.to_string()));
+        }
+
+        let response = request.dispatch().await;
+        let status = response.status();
+        let etag = response.headers().get_one("ETag").map(str::to_string);
+        let body = response.into_string().await.unwrap_or_default();
+        (status, etag, serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    pub async fn get_json(&self, uri: &str) -> Value {
+        let (status, _, body) = self.get(uri, None).await;
+        assert_eq!(status, Status::Ok, "GET {} returned {}", uri, status);
+        body
+    }
+}
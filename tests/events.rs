@@ -0,0 +1,43 @@
+//! End-to-end test of the event stream: the poller publishes the events to the store
+//! and /v2/events reads them from there, as on an instance that does not poll.
+
+mod common;
+
+use std::time::Duration;
+
+use alerts_api_app::modules::notifications::notifications::init_notifiers;
+use common::Harness;
+use rocket::{http::Status, tokio::io::AsyncReadExt};
+
+#[rocket::async_test]
+async fn events_stream_serves_the_published_events() {
+
+    let harness = Harness::start().await;
+    init_notifiers().await;
+
+    let mut response = harness.client.get("/v2/events").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    // The relay subscribes to the channel in the background
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    harness.respond_with("oblast_alerts.json", "Sun, 18 Oct 2026 05:35:02 GMT");
+    assert!(harness.tick().await);
+
+    let mut body = String::new();
+    let read = tokio::time::timeout(Duration::from_secs(10), async {
+        let mut buffer = [0; 4096];
+        // Every event ends with an empty line
+        while body.matches("\n\n").count() < 3 {
+            let len = response.read(&mut buffer).await.unwrap();
+            assert!(len > 0, "the stream is closed");
+            body.push_str(&String::from_utf8_lossy(&buffer[..len]));
+        }
+    });
+    read.await.unwrap_or_else(|_| panic!("no events in the stream: {}", body));
+
+    let events: Vec<&str> = body.lines().filter_map(|line| line.strip_prefix("event:")).map(str::trim).collect();
+    // The artillery shelling in Sumy Oblast, the air raid alerts in Kharkiv Oblast and Kyiv
+    assert_eq!(events, vec!["type_added", "alarm_started", "alarm_started"]);
+    assert!(body.contains(r#""region_id":22"#), "{}", body);
+    assert!(body.contains(r#""region_id":31"#), "{}", body);
+}
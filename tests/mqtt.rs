@@ -0,0 +1,142 @@
+//! Test of the MQTT output against an embedded broker (cargo test --features mqtt or cargo test-all).
+
+#![cfg(feature = "mqtt")]
+
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use alerts_api_app::modules::{
+    mqtt::mqtt::{init_mqtt, sync_mqtt_state},
+    overrides::overrides::{expires_after, remove_override, set_override, Override},
+    processing::processing::set_alarm_status,
+    store::store::{set_store, MemoryStore},
+};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+use rumqttd::{Broker, Config, ConnectionSettings, RouterConfig, ServerSettings};
+use serde_json::Value;
+
+const PREFIX: &str = "test_alarms";
+
+/// Starts the broker on a free port and returns the port
+fn start_broker() -> u16 {
+
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = ServerSettings {
+        name: "v4".to_string(),
+        listen: ([127, 0, 0, 1], port).into(),
+        tls: None,
+        next_connection_delay_ms: 1,
+        connections: ConnectionSettings {
+            connection_timeout_ms: 5000,
+            max_payload_size: 20480,
+            max_inflight_count: 100,
+            auth: None,
+            external_auth: None,
+            dynamic_filters: false,
+        },
+    };
+    let config = Config {
+        router: RouterConfig {
+            max_connections: 10,
+            max_outgoing_packet_count: 200,
+            max_segment_size: 1024 * 1024,
+            max_segment_count: 10,
+            ..Default::default()
+        },
+        v4: Some(HashMap::from([("1".to_string(), server)])),
+        ..Default::default()
+    };
+
+    std::thread::spawn(move || Broker::new(config).start().unwrap());
+    port
+}
+
+/// Client that reads the messages of the output, the retained ones included
+struct Subscriber {
+    event_loop: EventLoop,
+    // The client is kept, so the event loop is not closed
+    _client: AsyncClient,
+    messages: HashMap<String, String>,
+}
+
+impl Subscriber {
+
+    async fn connect(port: u16, client_id: &str) -> Self {
+        let (client, event_loop) = AsyncClient::new(MqttOptions::new(client_id, "127.0.0.1", port), 100);
+        client.subscribe(format!("{}/#", PREFIX), QoS::AtLeastOnce).await.unwrap();
+        Subscriber { event_loop, _client: client, messages: HashMap::new() }
+    }
+
+    /// Reads the messages until the topic has the expected payload
+    async fn wait_for(&mut self, topic: &str, expected: impl Fn(&str) -> bool) -> String {
+
+        let topic = format!("{}/{}", PREFIX, topic);
+        let result = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(payload) = self.messages.get(&topic).filter(|payload| expected(payload)) {
+                    return payload.clone();
+                }
+                match self.event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        self.messages.insert(publish.topic, String::from_utf8_lossy(&publish.payload).to_string());
+                    }
+                    Ok(_) => {}
+                    Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+                }
+            }
+        });
+        result.await.unwrap_or_else(|_| panic!("no expected message in {}, the last one: {:?}", topic, self.messages.get(&topic)))
+    }
+}
+
+fn active_region_ids(payload: &str) -> Vec<i64> {
+    let aggregate: Value = serde_json::from_str(payload).unwrap();
+    aggregate["active_region_ids"].as_array().unwrap().iter().map(|id| id.as_i64().unwrap()).collect()
+}
+
+#[rocket::async_test]
+async fn retained_state_follows_the_alarms_and_the_overrides() {
+
+    let port = start_broker();
+    std::env::set_var("MQTT_HOST", "127.0.0.1");
+    std::env::set_var("MQTT_PORT", port.to_string());
+    std::env::set_var("MQTT_TOPIC_PREFIX", PREFIX);
+    assert!(set_store(Box::new(MemoryStore::new())));
+
+    set_alarm_status(9, true).await;
+    set_alarm_status(3, false).await;
+    let _notifier = init_mqtt().expect("the MQTT output is enabled");
+
+    // The state of all regions is published after the connection
+    let mut subscriber = Subscriber::connect(port, "subscriber").await;
+    subscriber.wait_for("status", |payload| payload == "online").await;
+    subscriber.wait_for("9/state", |payload| payload == "active").await;
+    subscriber.wait_for("3/state", |payload| payload == "inactive").await;
+    let all = subscriber.wait_for("all", |payload| active_region_ids(payload) == vec![9]).await;
+    assert_eq!(serde_json::from_str::<Value>(&all).unwrap()["active_count"], 1);
+
+    // An override changes the state without an alarm event
+    let entry = Override {
+        region_id: 3,
+        alarm_active: true,
+        reason: Some("test".to_string()),
+        created_by: "tester".to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        expires_at: expires_after(30),
+    };
+    set_override(&entry).await.unwrap();
+    sync_mqtt_state().await;
+    subscriber.wait_for("3/state", |payload| payload == "active").await;
+    subscriber.wait_for("all", |payload| active_region_ids(payload) == vec![3, 9]).await;
+
+    remove_override(3, "tester").await.unwrap();
+    sync_mqtt_state().await;
+    subscriber.wait_for("3/state", |payload| payload == "inactive").await;
+
+    // A new subscriber gets the last state from the retained messages
+    let mut late_subscriber = Subscriber::connect(port, "late_subscriber").await;
+    late_subscriber.wait_for("3/state", |payload| payload == "inactive").await;
+    late_subscriber.wait_for("9/state", |payload| payload == "active").await;
+    late_subscriber.wait_for("all", |payload| active_region_ids(payload) == vec![9]).await;
+}
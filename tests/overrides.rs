@@ -0,0 +1,70 @@
+//! End-to-end tests of the manual overrides: the validators of the alarm data (ETag and Last-Modified)
+//! change with every override and when an override expires, without a poll.
+
+mod common;
+
+use std::time::Duration;
+
+use alerts_api_app::modules::overrides::overrides::{expires_after, remove_override, set_override, Override};
+use chrono::Utc;
+use common::Harness;
+use rocket::http::Status;
+
+/// Returns the ETag, the Last-Modified header and the alarm state of a region from /v2/alarms/<id>
+async fn validators(harness: &Harness, region_id: i32) -> (String, String, bool) {
+
+    let response = harness.client.get(format!("/v2/alarms/{}", region_id)).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let etag = response.headers().get_one("ETag").expect("the ETag header").to_string();
+    let last_modified = response.headers().get_one("Last-Modified").expect("the Last-Modified header").to_string();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+    (etag, last_modified, body["data"]["alarm_active"] == true)
+}
+
+fn manual_state(region_id: i32, alarm_active: bool, expires_at: String) -> Override {
+    Override {
+        region_id,
+        alarm_active,
+        reason: Some("test".to_string()),
+        created_by: "tester".to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        expires_at,
+    }
+}
+
+#[rocket::async_test]
+async fn overrides_change_both_validators() {
+
+    let harness = Harness::start().await;
+    // A time in the past, the changes of the overrides must still move Last-Modified forward
+    harness.respond_with("oblast_alerts.json", "Sun, 18 Oct 2026 05:35:02 GMT");
+    assert!(harness.tick().await);
+
+    let (etag, last_modified, active) = validators(&harness, 3).await;
+    assert!(!active);
+
+    set_override(&manual_state(3, true, expires_after(30))).await.unwrap();
+    let (set_etag, set_last_modified, active) = validators(&harness, 3).await;
+    assert!(active);
+    assert_ne!(set_etag, etag);
+    assert_ne!(set_last_modified, last_modified);
+
+    // Two changes within one second still have different times
+    remove_override(3, "tester").await.unwrap().expect("the override is removed");
+    let (removed_etag, removed_last_modified, active) = validators(&harness, 3).await;
+    assert!(!active);
+    assert_ne!(removed_etag, set_etag);
+    assert_ne!(removed_last_modified, set_last_modified);
+
+    // An override expires without a poll and the readers see the new validators with the new state
+    let expires_at = (Utc::now() + chrono::Duration::seconds(1)).to_rfc3339();
+    set_override(&manual_state(3, true, expires_at)).await.unwrap();
+    let (etag, last_modified, active) = validators(&harness, 3).await;
+    assert!(active);
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let (expired_etag, expired_last_modified, active) = validators(&harness, 3).await;
+    assert!(!active);
+    assert_ne!(expired_etag, etag);
+    assert_ne!(expired_last_modified, last_modified);
+}